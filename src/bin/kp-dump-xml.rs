@@ -22,6 +22,11 @@ struct Args {
     /// Do not use a password to decrypt the database
     #[arg(short = 'n', long)]
     no_password: bool,
+
+    /// Indent the XML, protected values are then only Base64 encoded
+    #[cfg(feature = "save_kdbx4")]
+    #[arg(short = 'p', long)]
+    pretty: bool,
 }
 
 pub fn main() -> Result<(), BoxError> {
@@ -42,6 +47,13 @@ pub fn main() -> Result<(), BoxError> {
         return Err("No database key was provided.".into());
     }
 
+    #[cfg(feature = "save_kdbx4")]
+    let xml = if args.pretty {
+        Database::open(&mut source, key)?.get_xml_pretty()?
+    } else {
+        Database::get_xml(&mut source, key)?
+    };
+    #[cfg(not(feature = "save_kdbx4"))]
     let xml = Database::get_xml(&mut source, key)?;
 
     File::create(args.out_xml)?.write_all(&xml)?;
@@ -0,0 +1,125 @@
+/// utility to export all entry attachments of a `KeePass` database to a directory
+use clap::Parser;
+use keepass_ng::{
+    db::{with_node, Database, Entry},
+    BoxError, DatabaseKey,
+};
+use std::{
+    collections::HashSet,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Provide a .kdbx database
+    in_kdbx: String,
+
+    /// Directory to write the attachments to
+    out_dir: String,
+
+    /// Provide a keyfile
+    #[arg(short = 'k', long)]
+    keyfile: Option<String>,
+
+    /// Do not use a password to decrypt the database
+    #[arg(short = 'n', long)]
+    no_password: bool,
+
+    /// Only list the files that would be written
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub fn main() -> Result<(), BoxError> {
+    let args = Args::parse();
+
+    let mut source = File::open(&args.in_kdbx)?;
+    let mut key = DatabaseKey::new();
+
+    if let Some(f) = args.keyfile {
+        key = key.with_keyfile(&mut File::open(f)?)?;
+    }
+
+    if !args.no_password {
+        key = key.with_password_from_prompt("Password: ")?;
+    }
+
+    if key.is_empty() {
+        return Err("No database key was provided.".into());
+    }
+
+    let db = Database::open(&mut source, key)?;
+
+    let out_dir = Path::new(&args.out_dir);
+    let mut planned = HashSet::new();
+
+    for (entry_path, node) in db.flatten_with_title_prefix() {
+        let attachments = with_node::<Entry, _, _>(&node, |e| e.get_attachments().to_vec()).unwrap_or_default();
+        for attachment in attachments {
+            let content = match db.resolve_attachment(&attachment) {
+                Ok(Some(content)) => content,
+                Ok(None) => {
+                    eprintln!("{entry_path}: attachment {} not found in database", attachment.name);
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("{entry_path}: attachment {} cannot be read: {err}", attachment.name);
+                    continue;
+                }
+            };
+
+            let mut dir = out_dir.to_path_buf();
+            for component in entry_path.split('/') {
+                dir.push(sanitize(component));
+            }
+            let target = unique_path(&dir, &sanitize(&attachment.name), &planned);
+            planned.insert(target.clone());
+
+            if args.dry_run {
+                println!("{} ({} bytes)", target.display(), content.len());
+            } else {
+                std::fs::create_dir_all(&dir)?;
+                std::fs::write(&target, &content)?;
+                println!("{}", target.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Make a single path component safe to use on common file systems
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    match name.as_str() {
+        "" | "." | ".." => "_".to_string(),
+        _ => name,
+    }
+}
+
+/// Find a file name in `dir` that neither exists nor was already chosen, suffixing " (n)" before the extension
+fn unique_path(dir: &Path, name: &str, planned: &HashSet<PathBuf>) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() && !planned.contains(&candidate) {
+        return candidate;
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        Some(pos) if pos > 0 => (&name[..pos], &name[pos..]),
+        _ => (name, ""),
+    };
+
+    (1..)
+        .map(|n| dir.join(format!("{stem} ({n}){extension}")))
+        .find(|candidate| !candidate.exists() && !planned.contains(candidate))
+        .unwrap()
+}
@@ -4,6 +4,7 @@ use std::fs::File;
 use clap::Parser;
 use keepass_ng::{
     db::{with_node, Database, Entry, Group},
+    error::GetError,
     BoxError, DatabaseKey,
 };
 
@@ -46,7 +47,10 @@ pub fn main() -> Result<(), BoxError> {
     let db = Database::open(&mut source, key)?;
 
     with_node::<Group, _, _>(&db.root, |root| {
-        let entry = root.get(&[&args.entry]).ok_or("Could not find entry with provided name")?;
+        let entry = root.get_typed::<Entry>(&[&args.entry]).map_err(|err| match err {
+            GetError::NotFound => "Could not find entry with provided name",
+            GetError::WrongType => "The provided name is a group, not an entry",
+        })?;
         with_node::<Entry, _, _>(&entry, |entry| {
             let totp = entry.get_otp().unwrap();
             println!("Token is {}", totp.value_now().unwrap().code);
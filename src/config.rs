@@ -64,6 +64,204 @@ impl Default for DatabaseConfig {
     }
 }
 
+impl DatabaseConfig {
+    /// Sensible default configuration for new databases of a specific version. Versions before
+    /// KDBX4 get the settings KDBX3 supports: Salsa20 for protected fields and the AES KDF.
+    pub fn default_for_version(version: DatabaseVersion) -> Self {
+        match version {
+            DatabaseVersion::KDB4(_) => Self {
+                version,
+                ..Self::default()
+            },
+            _ => Self {
+                version,
+                outer_cipher_config: OuterCipherConfig::AES256,
+                compression_config: CompressionConfig::GZip,
+                inner_cipher_config: InnerCipherConfig::Salsa20,
+                kdf_config: KdfConfig::Aes { rounds: 60_000 },
+            },
+        }
+    }
+
+    /// Human-readable description of the configuration, e.g. to show what a database uses
+    pub fn summary(&self) -> ConfigSummary {
+        ConfigSummary {
+            version: self.version.to_string(),
+            outer_cipher: self.outer_cipher_config.to_string(),
+            kdf: self.kdf_config.to_string(),
+            inner_cipher: self.inner_cipher_config.to_string(),
+            compression: self.compression_config.to_string(),
+        }
+    }
+
+    /// Check the encryption settings for deprecated or weak choices
+    pub fn security_assessment(&self) -> SecurityAssessment {
+        let mut assessment = SecurityAssessment::default();
+
+        match self.version {
+            DatabaseVersion::KDB4(_) => {}
+            DatabaseVersion::KDB3(_) => assessment.add(
+                SecurityIssue::OutdatedFormat,
+                Severity::Warning,
+                "Save the database as KDBX4 to use Argon2 and ChaCha20",
+            ),
+            DatabaseVersion::KDB(_) | DatabaseVersion::KDB2(_) => assessment.add(
+                SecurityIssue::OutdatedFormat,
+                Severity::Critical,
+                "Convert the database to KDBX4, this format is no longer maintained",
+            ),
+        }
+
+        let low_cost = match self.kdf_config {
+            KdfConfig::Aes { rounds } => {
+                assessment.add(
+                    SecurityIssue::AesKdf,
+                    Severity::Warning,
+                    "Use Argon2 for key derivation, it is much harder to attack with GPUs than AES-KDF",
+                );
+                rounds < MIN_AES_KDF_ROUNDS
+            }
+            KdfConfig::Argon2 { iterations, memory, .. } | KdfConfig::Argon2id { iterations, memory, .. } => {
+                iterations < MIN_ARGON2_ITERATIONS || memory < MIN_ARGON2_MEMORY
+            }
+        };
+        if low_cost {
+            assessment.add(
+                SecurityIssue::LowKdfCost,
+                Severity::Critical,
+                "Increase the cost of the key derivation so that guessing the password takes longer",
+            );
+        }
+
+        match self.inner_cipher_config {
+            InnerCipherConfig::ChaCha20 => {}
+            InnerCipherConfig::Salsa20 => assessment.add(
+                SecurityIssue::Salsa20InnerCipher,
+                Severity::Info,
+                "Use ChaCha20 to encrypt protected fields, which KDBX4 supports",
+            ),
+            InnerCipherConfig::Plain => assessment.add(
+                SecurityIssue::PlainInnerCipher,
+                Severity::Warning,
+                "Encrypt protected fields with ChaCha20",
+            ),
+        }
+
+        assessment
+    }
+}
+
+/// Fewer AES-KDF rounds than this are reported as `SecurityIssue::LowKdfCost`
+pub const MIN_AES_KDF_ROUNDS: u64 = 60_000;
+
+/// Less Argon2 memory than this, in bytes, is reported as `SecurityIssue::LowKdfCost`. `KeePass`
+/// uses 64 MiB for new databases.
+pub const MIN_ARGON2_MEMORY: u64 = 16 * 1024 * 1024;
+
+/// Fewer Argon2 iterations than this are reported as `SecurityIssue::LowKdfCost`
+pub const MIN_ARGON2_ITERATIONS: u64 = 2;
+
+/// How serious a finding of a `SecurityAssessment` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub enum Severity {
+    /// Works as intended, but a better choice is available
+    Info,
+    /// Weaker than it should be, should be changed
+    Warning,
+    /// Offers little protection, should be changed as soon as possible
+    Critical,
+}
+
+/// A weakness of the encryption settings found by `DatabaseConfig::security_assessment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub enum SecurityIssue {
+    /// The key is derived with AES-KDF, which is cheap to attack with GPUs compared to Argon2
+    AesKdf,
+    /// The key derivation is configured with a low cost, see `MIN_AES_KDF_ROUNDS`,
+    /// `MIN_ARGON2_MEMORY` and `MIN_ARGON2_ITERATIONS`
+    LowKdfCost,
+    /// The file format is older than KDBX4
+    OutdatedFormat,
+    /// Protected fields are encrypted with Salsa20 instead of ChaCha20
+    Salsa20InnerCipher,
+    /// Protected fields are not encrypted inside the database
+    PlainInnerCipher,
+}
+
+/// A finding of a `SecurityAssessment`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct SecurityFinding {
+    pub issue: SecurityIssue,
+    pub severity: Severity,
+    /// What to change, to show to users
+    pub recommendation: String,
+}
+
+/// The result of `DatabaseConfig::security_assessment`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct SecurityAssessment {
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityAssessment {
+    /// The severity of the most serious finding, `None` if nothing was found
+    pub fn highest_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    /// Whether a finding is about `issue`
+    pub fn has_issue(&self, issue: SecurityIssue) -> bool {
+        self.findings.iter().any(|finding| finding.issue == issue)
+    }
+
+    fn add(&mut self, issue: SecurityIssue, severity: Severity, recommendation: &str) {
+        self.findings.push(SecurityFinding {
+            issue,
+            severity,
+            recommendation: recommendation.to_string(),
+        });
+    }
+}
+
+/// Human-readable names of the settings in a `DatabaseConfig`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct ConfigSummary {
+    pub version: String,
+    pub outer_cipher: String,
+    pub kdf: String,
+    pub inner_cipher: String,
+    pub compression: String,
+}
+
+impl std::fmt::Display for ConfigSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Outer cipher: {}", self.outer_cipher)?;
+        writeln!(f, "KDF: {}", self.kdf)?;
+        writeln!(f, "Inner cipher: {}", self.inner_cipher)?;
+        write!(f, "Compression: {}", self.compression)
+    }
+}
+
+/// Options for saving a database
+#[cfg(feature = "save_kdbx4")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpOptions {
+    /// Keep the header and seeds of the file the database was opened from, as long as the key and the
+    /// configuration are unchanged, so that the header fields stay in their original order. The IVs are
+    /// only kept if the content is unchanged too, in which case saving writes identical bytes, since
+    /// reusing an IV for different content weakens the encryption.
+    pub reuse_seeds: bool,
+
+    /// Refuse to save if the timestamps of a group or entry are inconsistent, see `Times::validate`
+    pub validate_times: bool,
+}
+
 /// Choices for outer encryption
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -101,6 +299,16 @@ impl OuterCipherConfig {
     }
 }
 
+impl std::fmt::Display for OuterCipherConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OuterCipherConfig::AES256 => write!(f, "AES-256"),
+            OuterCipherConfig::Twofish => write!(f, "Twofish"),
+            OuterCipherConfig::ChaCha20 => write!(f, "ChaCha20"),
+        }
+    }
+}
+
 impl TryFrom<&[u8]> for OuterCipherConfig {
     type Error = OuterCipherConfigError;
     fn try_from(v: &[u8]) -> Result<OuterCipherConfig, Self::Error> {
@@ -153,6 +361,16 @@ impl InnerCipherConfig {
     }
 }
 
+impl std::fmt::Display for InnerCipherConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InnerCipherConfig::Plain => write!(f, "Plain"),
+            InnerCipherConfig::Salsa20 => write!(f, "Salsa20"),
+            InnerCipherConfig::ChaCha20 => write!(f, "ChaCha20"),
+        }
+    }
+}
+
 impl TryFrom<u32> for InnerCipherConfig {
     type Error = InnerCipherConfigError;
 
@@ -312,6 +530,31 @@ impl KdfConfig {
     }
 }
 
+impl std::fmt::Display for KdfConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (name, iterations, memory, parallelism) = match self {
+            KdfConfig::Aes { rounds } => return write!(f, "AES-KDF ({rounds} rounds)"),
+            KdfConfig::Argon2 {
+                iterations,
+                memory,
+                parallelism,
+                ..
+            } => ("Argon2d", iterations, memory, parallelism),
+            KdfConfig::Argon2id {
+                iterations,
+                memory,
+                parallelism,
+                ..
+            } => ("Argon2id", iterations, memory, parallelism),
+        };
+        write!(
+            f,
+            "{name} ({iterations} iterations, {} KiB memory, {parallelism} threads)",
+            memory / 1024
+        )
+    }
+}
+
 const KDF_AES_KDBX3: [u8; 16] = hex!("c9d9f39a628a4460bf740d08c18a4fea");
 const KDF_AES_KDBX4: [u8; 16] = hex!("7c02bb8279a74ac0927d114a00648238");
 const KDF_ARGON2: [u8; 16] = hex!("ef636ddf8c29444b91f7a9a403e30a0c");
@@ -403,6 +646,15 @@ impl CompressionConfig {
     }
 }
 
+impl std::fmt::Display for CompressionConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionConfig::None => write!(f, "None"),
+            CompressionConfig::GZip => write!(f, "GZip"),
+        }
+    }
+}
+
 impl TryFrom<u32> for CompressionConfig {
     type Error = CompressionConfigError;
 
@@ -1,7 +1,11 @@
 use aes::Aes256;
 #[cfg(feature = "save_kdbx4")]
 use cipher::BlockEncryptMut;
-use cipher::{block_padding::Pkcs7, generic_array::GenericArray, BlockDecryptMut};
+use cipher::{
+    block_padding::{NoPadding, Pkcs7},
+    generic_array::GenericArray,
+    BlockDecryptMut,
+};
 use salsa20::{
     cipher::{KeyIvInit, StreamCipher},
     Salsa20,
@@ -14,6 +18,12 @@ pub(crate) trait Cipher {
     fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, CryptographyError>;
     fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptographyError>;
 
+    /// Decrypt the first blocks of a ciphertext without removing any padding.
+    /// For block ciphers, the length of the ciphertext must be a multiple of the block size.
+    fn decrypt_start(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptographyError> {
+        self.decrypt(ciphertext)
+    }
+
     /// The number of bytes expected by the cipher as an initialization vector.
     #[cfg(feature = "save_kdbx4")]
     fn iv_size() -> usize
@@ -65,6 +75,14 @@ impl Cipher for AES256Cipher {
         Ok(out)
     }
 
+    fn decrypt_start(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptographyError> {
+        let cipher = Aes256CbcDecryptor::new_from_slices(&self.key, &self.iv)?;
+
+        let mut buf = ciphertext.to_vec();
+        cipher.decrypt_padded_mut::<NoPadding>(&mut buf)?;
+        Ok(buf)
+    }
+
     #[cfg(feature = "save_kdbx4")]
     fn iv_size() -> usize {
         16
@@ -111,6 +129,14 @@ impl Cipher for TwofishCipher {
         Ok(buf)
     }
 
+    fn decrypt_start(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, CryptographyError> {
+        let cipher = TwofishCbcDecryptor::new_from_slices(&self.key, &self.iv)?;
+
+        let mut buf = ciphertext.to_vec();
+        cipher.decrypt_padded_mut::<NoPadding>(&mut buf)?;
+        Ok(buf)
+    }
+
     #[cfg(feature = "save_kdbx4")]
     fn iv_size() -> usize {
         16
@@ -5,10 +5,30 @@ use cipher::{
 };
 use sha2::{Digest, Sha256};
 
-use super::CryptographyError;
+use crate::{
+    error::{CryptographyError, DatabaseKeyError},
+    key::KdfProgress,
+};
+
+/// Number of AES-KDF rounds between two progress reports
+const AES_KDF_PROGRESS_INTERVAL: u64 = 10_000;
 
 pub(crate) trait Kdf {
-    fn transform_key(&self, composite_key: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, CryptographyError>;
+    /// Transform the composite key, reporting progress to the optional callback.
+    ///
+    /// Returns `DatabaseKeyError::Cancelled` if the callback asks to stop.
+    fn transform_key(
+        &self,
+        composite_key: &GenericArray<u8, U32>,
+        progress: Option<&KdfProgress>,
+    ) -> Result<GenericArray<u8, U32>, DatabaseKeyError>;
+}
+
+fn report_progress(progress: Option<&KdfProgress>, done: u64, total: u64) -> Result<(), DatabaseKeyError> {
+    match progress {
+        Some(progress) if !progress(done, total) => Err(DatabaseKeyError::Cancelled),
+        _ => Ok(()),
+    }
 }
 
 pub struct AesKdf {
@@ -17,14 +37,22 @@ pub struct AesKdf {
 }
 
 impl Kdf for AesKdf {
-    fn transform_key(&self, composite_key: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, CryptographyError> {
-        let cipher = Aes256::new(&GenericArray::clone_from_slice(&self.seed));
+    fn transform_key(
+        &self,
+        composite_key: &GenericArray<u8, U32>,
+        progress: Option<&KdfProgress>,
+    ) -> Result<GenericArray<u8, U32>, DatabaseKeyError> {
+        let cipher = Aes256::new_from_slice(&self.seed).map_err(CryptographyError::from)?;
         let mut block1 = GenericArray::clone_from_slice(&composite_key[..16]);
         let mut block2 = GenericArray::clone_from_slice(&composite_key[16..]);
-        for _ in 0..self.rounds {
+        for round in 0..self.rounds {
+            if round % AES_KDF_PROGRESS_INTERVAL == 0 {
+                report_progress(progress, round, self.rounds)?;
+            }
             cipher.encrypt_block(&mut block1);
             cipher.encrypt_block(&mut block2);
         }
+        report_progress(progress, self.rounds, self.rounds)?;
 
         let mut digest = Sha256::new();
 
@@ -45,7 +73,14 @@ pub struct Argon2Kdf {
 }
 
 impl Kdf for Argon2Kdf {
-    fn transform_key(&self, composite_key: &GenericArray<u8, U32>) -> Result<GenericArray<u8, U32>, CryptographyError> {
+    fn transform_key(
+        &self,
+        composite_key: &GenericArray<u8, U32>,
+        progress: Option<&KdfProgress>,
+    ) -> Result<GenericArray<u8, U32>, DatabaseKeyError> {
+        // Argon2 cannot be interrupted midway, so only report its start and finish
+        report_progress(progress, 0, 1)?;
+
         #[allow(clippy::cast_possible_truncation)]
         let config = argon2::Config {
             ad: &[],
@@ -58,7 +93,8 @@ impl Kdf for Argon2Kdf {
             version: self.version,
         };
 
-        let key = argon2::hash_raw(composite_key, &self.salt, &config)?;
+        let key = argon2::hash_raw(composite_key, &self.salt, &config).map_err(CryptographyError::from)?;
+        report_progress(progress, 1, 1)?;
 
         Ok(*GenericArray::from_slice(&key))
     }
@@ -3,11 +3,14 @@ use crate::db::otp::{TOTPError, TOTP};
 use crate::db::{
     group::MergeLog,
     node::{Node, NodePtr},
-    rc_refcell_node, with_node, with_node_mut, Color, CustomData, IconId, Times,
+    rc_refcell_node, with_node, with_node_mut, Color, CustomData, CustomDataItem, IconId, Times, STANDARD_FIELD_NAMES,
 };
 use chrono::NaiveDateTime;
 use secstr::SecStr;
-use std::{collections::HashMap, thread, time};
+use std::{
+    collections::{HashMap, HashSet},
+    thread, time,
+};
 use uuid::Uuid;
 
 /// A database entry containing several key-value fields.
@@ -18,6 +21,7 @@ pub struct Entry {
     pub(crate) fields: HashMap<String, Value>,
     pub(crate) autotype: Option<AutoType>,
     pub(crate) tags: Vec<String>,
+    pub(crate) attachments: Vec<AttachmentRef>,
 
     pub(crate) times: Times,
 
@@ -47,6 +51,7 @@ impl Default for Entry {
             fields: HashMap::new(),
             autotype: None,
             tags: Vec::new(),
+            attachments: Vec::new(),
             times: Times::new(),
             custom_data: CustomData::default(),
             icon_id: Some(IconId::KEY),
@@ -64,10 +69,20 @@ impl Default for Entry {
 
 impl PartialEq for Entry {
     fn eq(&self, other: &Self) -> bool {
+        self.eq_ignoring_history(other) && self.history == other.history
+    }
+}
+
+impl Eq for Entry {}
+
+impl Entry {
+    /// Compare the current state of two entries, i.e. like `==` but without looking at their history
+    pub fn eq_ignoring_history(&self, other: &Entry) -> bool {
         self.uuid == other.uuid
             && self.fields == other.fields
             && self.autotype == other.autotype
             && self.tags == other.tags
+            && self.attachments == other.attachments
             && self.times == other.times
             && self.custom_data == other.custom_data
             && self.icon_id == other.icon_id
@@ -76,13 +91,10 @@ impl PartialEq for Entry {
             && self.background_color == other.background_color
             && self.override_url == other.override_url
             && self.quality_check == other.quality_check
-            && self.history == other.history
         // && self.parent == other.parent
     }
 }
 
-impl Eq for Entry {}
-
 impl Node for Entry {
     fn duplicate(&self) -> NodePtr {
         let mut tmp = self.clone();
@@ -145,6 +157,7 @@ impl Node for Entry {
 
 #[allow(dead_code)]
 pub fn entry_set_field_and_commit(entry: &NodePtr, field_name: &str, field_value: &str) -> crate::Result<()> {
+    Entry::validate_field_name(field_name)?;
     with_node_mut::<Entry, _, _>(entry, |entry| {
         entry.set_field_and_commit(field_name, field_value);
         Ok(())
@@ -154,6 +167,16 @@ pub fn entry_set_field_and_commit(entry: &NodePtr, field_name: &str, field_value
 }
 
 impl Entry {
+    /// Set the creation, last modification, last access and location change times to now, e.g.
+    /// for an entry that is imported or copied. The expiry time is left alone.
+    pub fn set_all_times_now(&mut self) {
+        let now = Some(Times::now());
+        self.times.set_creation(now);
+        self.times.set_last_modification(now);
+        self.times.set_last_access(now);
+        self.times.set_location_changed(now);
+    }
+
     pub fn get_history(&self) -> &Option<History> {
         &self.history
     }
@@ -162,6 +185,69 @@ impl Entry {
         self.history = None;
     }
 
+    /// Estimate the size of the history in bytes as the sum of the lengths of all field names and
+    /// values of the history entries. This is an approximation of what the history takes up in the
+    /// XML document, which `Meta::history_max_size` refers to, leaving out tags and timestamps.
+    pub fn history_size_bytes(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |h| h.entries.iter().map(Entry::fields_size_bytes).sum())
+    }
+
+    fn fields_size_bytes(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|(key, value)| {
+                key.len()
+                    + match value {
+                        Value::Bytes(b) => b.len(),
+                        Value::Unprotected(s) => s.len(),
+                        Value::Protected(p) => p.unsecure().len(),
+                    }
+            })
+            .sum()
+    }
+
+    /// Drop the oldest history entries so that at most `max_items` are left and the size of the
+    /// history, as estimated by `history_size_bytes`, stays within `max_size`.
+    /// Returns how many history entries were removed.
+    pub fn truncate_history(&mut self, max_items: Option<usize>, max_size: Option<usize>) -> usize {
+        let Some(history) = self.history.as_mut() else {
+            return 0;
+        };
+        let mut size = 0;
+        let keep = history
+            .entries
+            .iter()
+            .take(max_items.unwrap_or(usize::MAX))
+            .take_while(|entry| {
+                size += entry.fields_size_bytes();
+                size <= max_size.unwrap_or(usize::MAX)
+            })
+            .count();
+        let removed = history.entries.len() - keep;
+        history.entries.truncate(keep);
+        removed
+    }
+
+    /// Find the most recent modification time that appears in the history of both entries,
+    /// i.e. the last version the two entries had in common before they diverged.
+    pub fn common_history_base(&self, other: &Entry) -> Option<NaiveDateTime> {
+        let ours: HashSet<NaiveDateTime> = self
+            .history
+            .iter()
+            .flat_map(|h| &h.entries)
+            .filter_map(|e| e.times.get_last_modification())
+            .collect();
+        other
+            .history
+            .iter()
+            .flat_map(|h| &h.entries)
+            .filter_map(|e| e.times.get_last_modification())
+            .filter(|t| ours.contains(t))
+            .max()
+    }
+
     pub(crate) fn merge(entry: &NodePtr, other: &NodePtr) -> Result<(NodePtr, MergeLog), String> {
         let mut log = MergeLog::default();
 
@@ -200,6 +286,40 @@ impl Entry {
         self.update_history();
     }
 
+    /// Set the field `field_name` to `value`, failing for a field name that cannot be saved, see
+    /// `validate_field_name`
+    pub fn set_field(&mut self, field_name: &str, value: Value) -> crate::Result<()> {
+        Self::validate_field_name(field_name)?;
+        self.fields.insert(field_name.to_string(), value);
+        Ok(())
+    }
+
+    /// Check that a field name can be written to the XML database, that is that it has no
+    /// characters XML 1.0 cannot represent. Tabs and line breaks are allowed.
+    pub fn validate_field_name(field_name: &str) -> crate::Result<()> {
+        match field_name.chars().find(|&c| !crate::xml_db::is_xml_char(c)) {
+            Some(c) => Err(format!(
+                "Field name {field_name:?} contains the character U+{:04X}, which XML cannot represent",
+                u32::from(c)
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the names of all fields of the entry and of its history entries
+    #[cfg(feature = "save_kdbx4")]
+    pub(crate) fn validate_field_names(&self) -> crate::Result<()> {
+        let history = self.history.as_ref().map(|history| &history.entries[..]).unwrap_or_default();
+        for entry in std::iter::once(self).chain(history) {
+            entry
+                .fields
+                .keys()
+                .try_for_each(|field_name| Self::validate_field_name(field_name))?;
+        }
+        Ok(())
+    }
+
     fn set_unprotected_field_pair(&mut self, field_name: &str, field_value: Option<&str>) {
         if let Some(field_value) = field_value {
             self.fields
@@ -217,6 +337,7 @@ impl Entry {
                 entry.fields = other.fields.clone();
                 entry.autotype = other.autotype.clone();
                 entry.tags = other.tags.clone();
+                entry.attachments = other.attachments.clone();
                 entry.times = other.times.clone();
                 entry.custom_data = other.custom_data.clone();
                 entry.icon_id = other.icon_id;
@@ -255,6 +376,12 @@ impl<'a> Entry {
         }
     }
 
+    /// Whether a field is stored as a protected value, so that it should be masked when displayed.
+    /// Returns `None` if the entry has no such field.
+    pub fn is_field_protected(&self, key: &str) -> Option<bool> {
+        self.fields.get(key).map(|value| matches!(value, Value::Protected(_)))
+    }
+
     /// Convenience method for getting a TOTP from this entry
     #[cfg(feature = "totp")]
     pub fn get_otp(&'a self) -> Result<TOTP, TOTPError> {
@@ -281,6 +408,15 @@ impl<'a> Entry {
         self.autotype = autotype;
     }
 
+    /// Get the references to the binary attachments of this entry, see `Database::resolve_attachment`
+    pub fn get_attachments(&self) -> &[AttachmentRef] {
+        &self.attachments
+    }
+
+    pub fn get_attachments_mut(&mut self) -> &mut Vec<AttachmentRef> {
+        &mut self.attachments
+    }
+
     /// Convenience method for getting tags
     /// Returns a Vec of tags
     pub fn get_tags(&self) -> &Vec<String> {
@@ -291,6 +427,35 @@ impl<'a> Entry {
         self.tags.as_mut()
     }
 
+    pub fn get_custom_data(&self) -> &CustomData {
+        &self.custom_data
+    }
+
+    /// Set a custom data item, see `CustomData::set`
+    pub fn set_custom_data(&mut self, key: &str, value: Value) {
+        self.custom_data.set(key, value);
+    }
+
+    pub fn remove_custom_data(&mut self, key: &str) -> Option<CustomDataItem> {
+        self.custom_data.remove(key)
+    }
+
+    /// Get the lines of the 'Notes' field, split on `\n` and `\r\n`. A trailing line break does
+    /// not start another line. Returns no lines if there are no notes.
+    pub fn notes_lines(&self) -> Vec<&str> {
+        self.get_notes().map(|notes| notes.lines().collect()).unwrap_or_default()
+    }
+
+    /// Set the 'Notes' field to `lines` joined with `\n`, removing the notes if there are no lines
+    pub fn set_notes_lines<S: AsRef<str>>(&mut self, lines: &[S]) {
+        if lines.is_empty() {
+            self.set_notes(None);
+        } else {
+            let notes = lines.iter().map(AsRef::as_ref).collect::<Vec<_>>().join("\n");
+            self.set_notes(Some(&notes));
+        }
+    }
+
     /// Convenience method for getting the value of the `UserName` field
     pub fn get_username(&'a self) -> Option<&'a str> {
         self.get("UserName")
@@ -301,10 +466,28 @@ impl<'a> Entry {
     }
 
     /// Convenience method for getting the value of the 'Password' field
+    ///
+    /// The returned `&str` borrows from the protected value. Avoid copying it into long-lived
+    /// `String`s, which are not zeroized on drop, and prefer `with_password` or
+    /// `password_secstr` instead.
     pub fn get_password(&self) -> Option<&str> {
         self.get("Password")
     }
 
+    /// Call `f` with the value of the 'Password' field, scoping access to the plain text
+    pub fn with_password<R>(&self, f: impl FnOnce(&str) -> R) -> Option<R> {
+        self.get_password().map(f)
+    }
+
+    /// Get a copy of the 'Password' field that is zeroized when dropped
+    pub fn password_secstr(&self) -> Option<SecStr> {
+        match self.fields.get("Password")? {
+            Value::Protected(pv) => Some(pv.clone()),
+            Value::Unprotected(uv) => Some(SecStr::new(uv.as_bytes().to_vec())),
+            Value::Bytes(_) => None,
+        }
+    }
+
     pub fn set_password(&mut self, password: Option<&str>) {
         if let Some(password) = password {
             self.fields
@@ -323,6 +506,94 @@ impl<'a> Entry {
         self.set_unprotected_field_pair("URL", url);
     }
 
+    pub fn get_foreground_color(&self) -> Option<&Color> {
+        self.foreground_color.as_ref()
+    }
+
+    /// Set the text color of the entry, or remove it with `None`
+    pub fn set_foreground_color(&mut self, color: Option<Color>) {
+        self.foreground_color = color;
+    }
+
+    pub fn set_foreground_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.set_foreground_color(Some(Color { r, g, b }));
+    }
+
+    pub fn get_background_color(&self) -> Option<&Color> {
+        self.background_color.as_ref()
+    }
+
+    /// Set the background color of the entry, or remove it with `None`
+    pub fn set_background_color(&mut self, color: Option<Color>) {
+        self.background_color = color;
+    }
+
+    pub fn set_background_rgb(&mut self, r: u8, g: u8, b: u8) {
+        self.set_background_color(Some(Color { r, g, b }));
+    }
+
+    /// Whether the password quality of this entry should be checked, `KeePass` does so unless it
+    /// was explicitly disabled for the entry
+    pub fn quality_check_enabled(&self) -> bool {
+        self.quality_check.unwrap_or(true)
+    }
+
+    /// Estimate the strength of the password in bits, from its length and the classes of
+    /// characters it uses. This is a rough upper bound that does not detect dictionary words or
+    /// patterns. Returns `None` if the entry has no or an empty password.
+    pub fn password_strength_bits(&self) -> Option<f64> {
+        self.with_password(|password| {
+            let has = |f: fn(&char) -> bool| password.chars().any(|c| f(&c));
+            let pool = [
+                (has(char::is_ascii_lowercase), 26),
+                (has(char::is_ascii_uppercase), 26),
+                (has(char::is_ascii_digit), 10),
+                (has(|c| c.is_ascii() && !c.is_ascii_alphanumeric()), 33),
+                (has(|c| !c.is_ascii()), 100),
+            ]
+            .iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, size)| size)
+            .sum::<u32>();
+            if pool == 0 {
+                return None;
+            }
+            Some(password.chars().count() as f64 * f64::from(pool).log2())
+        })
+        .flatten()
+    }
+
+    /// Get the URL to use when launching the entry: the override URL if one is set,
+    /// otherwise the value of the 'URL' field
+    pub fn effective_url(&self) -> Option<&str> {
+        self.override_url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .or_else(|| self.get_url())
+    }
+
+    /// Classify the override URL of the entry, so that commands and placeholders can be handled
+    /// differently from plain URLs. Returns `None` if no override URL is set.
+    pub fn override_url_kind(&self) -> Option<OverrideUrlKind> {
+        let url = self.override_url.as_deref().map(str::trim).filter(|url| !url.is_empty())?;
+        let kind = if url.get(..6).is_some_and(|scheme| scheme.eq_ignore_ascii_case("cmd://")) {
+            OverrideUrlKind::Command
+        } else if url.starts_with('{') {
+            OverrideUrlKind::Placeholder
+        } else {
+            OverrideUrlKind::Url
+        };
+        Some(kind)
+    }
+
+    /// Remove all fields but the ones in `STANDARD_FIELD_NAMES`, e.g. to sanitize an export.
+    /// Returns how many fields were removed.
+    pub fn clear_custom_fields(&mut self) -> usize {
+        let count = self.fields.len();
+        self.fields.retain(|name, _| STANDARD_FIELD_NAMES.contains(&name.as_str()));
+        count - self.fields.len()
+    }
+
     /// Adds the current version of the entry to the entry's history
     /// and updates the last modification timestamp.
     /// The history will only be updated if the entry has
@@ -408,15 +679,55 @@ impl serde::Serialize for Value {
     }
 }
 
+/// What an override URL of an entry launches, see `Entry::override_url_kind`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub enum OverrideUrlKind {
+    /// A URL to open in the browser
+    Url,
+    /// A `cmd://` URL running a command line
+    Command,
+    /// A placeholder such as `{BROWSER}` or `{FIREFOX} {URL}` that KeePass resolves when launching
+    Placeholder,
+}
+
+/// A reference from an entry to one of the binary attachments of the database
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct AttachmentRef {
+    /// File name of the attachment
+    pub name: String,
+
+    /// Reference into the attachments of the database. In KDBX4, this is the index into `header_attachments`.
+    pub identifier: String,
+}
+
 /// An `AutoType` setting associated with an Entry
 #[derive(Debug, Default, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
 pub struct AutoType {
     pub enabled: bool,
+    /// Whether KeePass should use two-channel auto-type obfuscation
+    pub data_transfer_obfuscation: bool,
     pub sequence: Option<String>,
     pub associations: Vec<AutoTypeAssociation>,
 }
 
+impl AutoType {
+    /// Get the first association whose window pattern matches a window title, see
+    /// `AutoTypeAssociation::matches_window`. Associations are kept in the order of the database,
+    /// so this is the one `KeePass` would pick as well.
+    pub fn association_for_window(&self, window_title: &str) -> Option<&AutoTypeAssociation> {
+        self.associations.iter().find(|a| a.matches_window(window_title))
+    }
+
+    /// Check whether auto-type applies to a window title: it has to be enabled, and if there are
+    /// associations, one of them has to match. Without associations, any window matches.
+    pub fn applies_to_window(&self, window_title: &str) -> bool {
+        self.enabled && (self.associations.is_empty() || self.association_for_window(window_title).is_some())
+    }
+}
+
 /// A window association associated with an `AutoType` setting
 #[derive(Debug, Default, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -425,6 +736,64 @@ pub struct AutoTypeAssociation {
     pub sequence: Option<String>,
 }
 
+impl AutoTypeAssociation {
+    /// Check whether this association applies to a window title.
+    ///
+    /// Like in `KeePass`, the comparison ignores case and `*` matches any sequence of characters.
+    /// A window pattern enclosed in `//` (e.g. `//^.* - Mozilla Firefox$//`) is a regular
+    /// expression, which is only supported with the `regex` feature and never matches otherwise.
+    pub fn matches_window(&self, window_title: &str) -> bool {
+        let Some(pattern) = self.window.as_deref() else {
+            return false;
+        };
+
+        if pattern.len() > 4 && pattern.starts_with("//") && pattern.ends_with("//") {
+            return Self::matches_window_regex(&pattern[2..pattern.len() - 2], window_title);
+        }
+
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        let title: Vec<char> = window_title.to_lowercase().chars().collect();
+        wildcard_match(&pattern, &title)
+    }
+
+    #[cfg(feature = "regex")]
+    fn matches_window_regex(pattern: &str, window_title: &str) -> bool {
+        regex::RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .is_ok_and(|re| re.is_match(window_title))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn matches_window_regex(_pattern: &str, _window_title: &str) -> bool {
+        false
+    }
+}
+
+/// Match `text` against `pattern`, where `*` in the pattern matches any sequence of characters
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` in the pattern, and the text position it was matched at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last `*` swallow one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 /// An entry's history
 #[derive(Debug, Default, Eq, PartialEq, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -447,6 +816,24 @@ impl History {
         &self.entries
     }
 
+    /// Iterate over the history newest first, with the time each version was made: its last
+    /// modification time, or its creation time if it has none. The entries are sorted by this
+    /// time, in case the stored order is off, e.g. in files written by other applications.
+    pub fn iter_chronological(&self) -> impl Iterator<Item = (&Entry, NaiveDateTime)> {
+        let mut entries: Vec<(&Entry, NaiveDateTime)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let time = entry.times.get_last_modification().or_else(|| entry.times.get_creation());
+                (entry, time.unwrap_or_else(Times::epoch))
+            })
+            .collect();
+        if !self.is_ordered() {
+            entries.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        }
+        entries.into_iter()
+    }
+
     // Determines if the entries of the history are
     // ordered by last modification time.
     pub(crate) fn is_ordered(&self) -> bool {
@@ -456,9 +843,11 @@ impl History {
                 last_modification_time = entry.times.get_last_modification();
             }
 
-            let entry_modification_time = entry.times.get_last_modification().unwrap();
+            let (Some(last), Some(entry_modification_time)) = (last_modification_time, entry.times.get_last_modification()) else {
+                return false;
+            };
             // FIXME should we also handle equal modification times??
-            if last_modification_time.unwrap() < entry_modification_time {
+            if last < entry_modification_time {
                 return false;
             }
             last_modification_time = Some(entry_modification_time);
@@ -512,10 +901,68 @@ impl History {
 
 #[cfg(test)]
 mod entry_tests {
-    use super::{Entry, Node, Value};
+    use super::{AutoType, AutoTypeAssociation, Color, Entry, History, Node, OverrideUrlKind, Value};
     use secstr::SecStr;
     use std::{thread, time};
 
+    #[test]
+    fn history_iter_chronological() {
+        let version = |title: &str, modified: &str| {
+            let mut entry = Entry::default();
+            entry.set_title(Some(title));
+            entry.times.set_last_modification(Some(modified.parse().unwrap()));
+            entry
+        };
+        let history = History {
+            entries: vec![
+                version("second", "2021-01-01T00:00:00"),
+                version("third", "2022-01-01T00:00:00"),
+                version("first", "2020-01-01T00:00:00"),
+            ],
+        };
+        assert!(!history.is_ordered());
+
+        let titles: Vec<_> = history.iter_chronological().map(|(e, _)| e.get_title().unwrap()).collect();
+        assert_eq!(titles, vec!["third", "second", "first"]);
+        let times: Vec<_> = history.iter_chronological().map(|(_, time)| time.to_string()).collect();
+        assert_eq!(times[0], "2022-01-01 00:00:00");
+    }
+
+    #[test]
+    fn colors_from_rgb() {
+        let mut entry = Entry::default();
+        entry.set_foreground_rgb(0, 5, 255);
+        entry.set_background_rgb(192, 255, 238);
+
+        let foreground = entry.get_foreground_color().unwrap();
+        assert_eq!((foreground.r, foreground.g, foreground.b), (0, 5, 255));
+        assert_eq!(entry.get_background_color().unwrap().to_string(), "#C0FFEE");
+
+        // colors are saved as hex strings, which need to parse back to the same color
+        assert_eq!(foreground.to_string(), "#0005FF");
+        assert_eq!(foreground.to_string().parse::<Color>().unwrap(), *foreground);
+
+        entry.set_foreground_color(None);
+        assert!(entry.get_foreground_color().is_none());
+    }
+
+    #[test]
+    fn notes_lines() {
+        let mut entry = Entry::default();
+        assert!(entry.notes_lines().is_empty());
+
+        entry.set_notes(Some("host: example.com\r\nport: 22\n\nuser: admin\r\n"));
+        assert_eq!(entry.notes_lines(), vec!["host: example.com", "port: 22", "", "user: admin"]);
+
+        let lines: Vec<String> = entry.notes_lines().iter().map(|line| line.to_string()).collect();
+        entry.set_notes_lines(&lines);
+        assert_eq!(entry.get_notes(), Some("host: example.com\nport: 22\n\nuser: admin"));
+        assert_eq!(entry.notes_lines(), lines);
+
+        entry.set_notes_lines::<&str>(&[]);
+        assert_eq!(entry.get_notes(), None);
+    }
+
     #[test]
     fn byte_values() {
         let mut entry = Entry::default();
@@ -538,6 +985,19 @@ mod entry_tests {
         assert!(!entry.fields["a-bytes"].is_empty());
     }
 
+    #[test]
+    fn field_protection() {
+        let mut entry = Entry::default();
+        entry.set_username(Some("user"));
+        entry.set_password(Some("secret"));
+        entry.fields.insert("a-bytes".to_string(), Value::Bytes(vec![1, 2, 3]));
+
+        assert_eq!(entry.is_field_protected("Password"), Some(true));
+        assert_eq!(entry.is_field_protected("UserName"), Some(false));
+        assert_eq!(entry.is_field_protected("a-bytes"), Some(false));
+        assert_eq!(entry.is_field_protected("missing"), None);
+    }
+
     #[test]
     fn update_history() {
         let mut entry = Entry::default();
@@ -598,6 +1058,30 @@ mod entry_tests {
         }
     }
 
+    #[test]
+    fn history_size_bytes() {
+        let mut entry = Entry::default();
+        assert_eq!(entry.history_size_bytes(), 0);
+
+        let mut history = History::default();
+        let mut old = Entry::default();
+        old.set_title(Some("first"));
+        history.add_entry(old.clone());
+        entry.history = Some(history);
+        assert_eq!(entry.history_size_bytes(), "Title".len() + "first".len());
+
+        old.fields
+            .insert("Password".to_string(), Value::Protected("secret".as_bytes().into()));
+        entry.history.as_mut().unwrap().add_entry(old);
+        let size = entry.history_size_bytes();
+        assert_eq!(size, 2 * ("Title".len() + "first".len()) + "Password".len() + "secret".len());
+
+        assert_eq!(entry.truncate_history(None, Some(size - 1)), 1);
+        assert_eq!(entry.history.as_ref().unwrap().entries.len(), 1);
+        assert_eq!(entry.truncate_history(Some(0), None), 1);
+        assert_eq!(entry.history_size_bytes(), 0);
+    }
+
     #[cfg(feature = "totp")]
     #[test]
     fn totp() {
@@ -613,6 +1097,216 @@ mod entry_tests {
         assert!(entry.get_otp().is_ok());
     }
 
+    #[test]
+    fn scoped_password_access() {
+        let mut entry = Entry::default();
+        assert_eq!(entry.with_password(|p| p.len()), None);
+        assert_eq!(entry.password_secstr(), None);
+
+        entry.set_password(Some("secret"));
+        assert_eq!(entry.with_password(|p| p.len()), Some(6));
+        assert_eq!(entry.with_password(|p| p == "secret"), Some(true));
+        assert_eq!(entry.password_secstr(), Some(SecStr::new(b"secret".to_vec())));
+
+        entry.fields.insert("Password".to_string(), Value::Unprotected("plain".to_string()));
+        assert_eq!(entry.with_password(str::to_uppercase), Some("PLAIN".to_string()));
+        assert_eq!(entry.password_secstr().unwrap().unsecure(), b"plain");
+    }
+
+    #[test]
+    fn autotype_window_glob() {
+        let assoc = AutoTypeAssociation {
+            window: Some("*GitHub*".to_string()),
+            sequence: None,
+        };
+        assert!(assoc.matches_window("Pull requests · GitHub - Mozilla Firefox"));
+        assert!(assoc.matches_window("github"));
+        assert!(!assoc.matches_window("GitLab - Mozilla Firefox"));
+
+        let assoc = AutoTypeAssociation {
+            window: Some("Login - *".to_string()),
+            sequence: None,
+        };
+        assert!(assoc.matches_window("login - example.com"));
+        assert!(!assoc.matches_window("Logout - example.com"));
+
+        let assoc = AutoTypeAssociation {
+            window: Some("Exact Title".to_string()),
+            sequence: None,
+        };
+        assert!(assoc.matches_window("exact title"));
+        assert!(!assoc.matches_window("Exact Title 2"));
+
+        assert!(!AutoTypeAssociation::default().matches_window("anything"));
+    }
+
+    #[test]
+    fn autotype_association_for_window() {
+        let association = |window: &str, sequence: &str| AutoTypeAssociation {
+            window: Some(window.to_string()),
+            sequence: Some(sequence.to_string()),
+        };
+        let autotype = AutoType {
+            associations: vec![
+                association("*GitLab*", "{USERNAME}"),
+                association("*GitHub*", "{USERNAME}{TAB}{PASSWORD}"),
+                association("*", "{PASSWORD}"),
+            ],
+            ..Default::default()
+        };
+
+        let found = |title| autotype.association_for_window(title).and_then(|a| a.sequence.as_deref());
+        assert_eq!(found("Sign in to GitHub - Mozilla Firefox"), Some("{USERNAME}{TAB}{PASSWORD}"));
+        assert_eq!(found("GitLab"), Some("{USERNAME}"));
+        assert_eq!(found("Terminal"), Some("{PASSWORD}"));
+        assert!(AutoType::default().association_for_window("Terminal").is_none());
+    }
+
+    #[test]
+    fn autotype_window_regex() {
+        let assoc = AutoTypeAssociation {
+            window: Some("//^(Sign in|Login) - .* - Mozilla Firefox$//".to_string()),
+            sequence: None,
+        };
+        #[cfg(feature = "regex")]
+        {
+            assert!(assoc.matches_window("Sign in - GitHub - Mozilla Firefox"));
+            assert!(assoc.matches_window("login - example - mozilla firefox"));
+            assert!(!assoc.matches_window("Sign up - GitHub - Mozilla Firefox"));
+        }
+        #[cfg(not(feature = "regex"))]
+        assert!(!assoc.matches_window("Sign in - GitHub - Mozilla Firefox"));
+    }
+
+    #[test]
+    fn eq_ignoring_history() {
+        let mut entry = Entry::default();
+        entry.set_title(Some("Title"));
+        let mut other = entry.clone();
+        let mut history = History::default();
+        history.add_entry(entry.clone());
+        entry.history = Some(history);
+        assert_ne!(entry, other);
+        assert!(entry.eq_ignoring_history(&other));
+
+        other.set_title(Some("Other title"));
+        assert!(!entry.eq_ignoring_history(&other));
+    }
+
+    #[test]
+    fn quality_check_enabled() {
+        let mut entry = Entry::default();
+        assert_eq!(entry.quality_check, None);
+        assert!(entry.quality_check_enabled());
+
+        entry.quality_check = Some(false);
+        assert!(!entry.quality_check_enabled());
+
+        entry.quality_check = Some(true);
+        assert!(entry.quality_check_enabled());
+    }
+
+    #[test]
+    fn effective_url() {
+        let mut entry = Entry::default();
+        assert_eq!(entry.effective_url(), None);
+
+        entry.set_url(Some("https://example.com"));
+        assert_eq!(entry.effective_url(), Some("https://example.com"));
+
+        entry.override_url = Some("cmd://firefox https://example.com/login".to_string());
+        assert_eq!(entry.effective_url(), Some("cmd://firefox https://example.com/login"));
+
+        entry.override_url = Some(String::new());
+        assert_eq!(entry.effective_url(), Some("https://example.com"));
+    }
+
+    #[test]
+    fn set_field_rejects_non_xml_characters() {
+        let mut entry = Entry::default();
+        assert!(entry.set_field("Custom\0Field", Value::Unprotected("value".to_string())).is_err());
+        assert!(entry.set_field("Bell\u{7}", Value::Unprotected("value".to_string())).is_err());
+        assert!(!entry.fields.contains_key("Custom\0Field"));
+
+        entry.set_field("a\tb", Value::Unprotected("tab".to_string())).unwrap();
+        entry.set_field("Line\nBreak", Value::Unprotected("line".to_string())).unwrap();
+        assert_eq!(entry.get("a\tb"), Some("tab"));
+        assert_eq!(entry.get("Line\nBreak"), Some("line"));
+
+        entry
+            .set_field("Custom <Field> & \"more\"", Value::Unprotected("value".to_string()))
+            .unwrap();
+        assert_eq!(entry.get("Custom <Field> & \"more\""), Some("value"));
+    }
+
+    #[test]
+    fn clear_custom_fields() {
+        let mut entry = Entry::default();
+        entry.set_title(Some("Title"));
+        entry.set_username(Some("jdoe"));
+        entry.set_password(Some("secret"));
+        entry.fields.insert("otp".to_string(), Value::Protected("otpauth://totp/x".into()));
+        entry.fields.insert("Recovery Code".to_string(), Value::Protected("1234".into()));
+        entry.fields.insert("Customer ID".to_string(), Value::Unprotected("42".to_string()));
+
+        assert_eq!(entry.clear_custom_fields(), 2);
+        let mut names: Vec<_> = entry.fields.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["Password", "Title", "UserName", "otp"]);
+        assert_eq!(entry.clear_custom_fields(), 0);
+    }
+
+    #[test]
+    fn override_url_kind() {
+        let mut entry = Entry::default();
+        assert_eq!(entry.override_url_kind(), None);
+
+        for (url, kind) in [
+            ("cmd://foo", OverrideUrlKind::Command),
+            ("CMD://notepad.exe \"{URL}\"", OverrideUrlKind::Command),
+            ("https://x", OverrideUrlKind::Url),
+            ("{BROWSER}", OverrideUrlKind::Placeholder),
+            ("{FIREFOX} {URL}", OverrideUrlKind::Placeholder),
+        ] {
+            entry.override_url = Some(url.to_string());
+            assert_eq!(entry.override_url_kind(), Some(kind), "{url}");
+        }
+
+        entry.override_url = Some(String::new());
+        assert_eq!(entry.override_url_kind(), None);
+    }
+
+    #[test]
+    fn common_history_base() {
+        let at = |secs| chrono::DateTime::from_timestamp(secs, 0).unwrap().naive_utc();
+        let version = |title: &str, secs| {
+            let mut entry = Entry::default();
+            entry.set_unprotected_field_pair("Title", Some(title));
+            entry.times.set_last_modification(Some(at(secs)));
+            entry
+        };
+
+        let mut shared = History::default();
+        shared.add_entry(version("v1", 1000));
+        shared.add_entry(version("v2", 2000));
+
+        let mut ours = Entry::default();
+        let mut theirs = Entry::default();
+        assert_eq!(ours.common_history_base(&theirs), None);
+
+        let mut history = shared.clone();
+        history.add_entry(version("ours", 3000));
+        ours.history = Some(history);
+        let mut history = shared.clone();
+        history.add_entry(version("theirs", 3500));
+        history.add_entry(version("theirs again", 4000));
+        theirs.history = Some(history);
+
+        assert_eq!(ours.common_history_base(&theirs), Some(at(2000)));
+        assert_eq!(theirs.common_history_base(&ours), Some(at(2000)));
+        assert_eq!(ours.common_history_base(&Entry::default()), None);
+    }
+
     #[cfg(feature = "serialization")]
     #[test]
     fn serialization() {
@@ -0,0 +1,221 @@
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::db::{
+    group_get_children, with_node, AutoType, Color, CustomData, Database, Entry, Group, IconId, NodePtr, Times, Value,
+    CREATION_TIME_TAG_NAME, EXPIRY_TIME_TAG_NAME, LAST_MODIFICATION_TIME_TAG_NAME, LOCATION_CHANGED_TAG_NAME,
+};
+
+/// Hashes the content of a database in a canonical form, see `Database::content_fingerprint`.
+///
+/// Every value is written with a length or presence prefix so that different sequences of values
+/// can never produce the same byte stream.
+pub(crate) struct Fingerprint<'a> {
+    db: &'a Database,
+    digest: Sha256,
+}
+
+impl<'a> Fingerprint<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Fingerprint { db, digest: Sha256::new() }
+    }
+
+    pub(crate) fn finish(mut self) -> [u8; 32] {
+        let db = self.db;
+        self.meta();
+        self.node(&db.root);
+        self.digest.finalize().into()
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.digest.update((bytes.len() as u64).to_le_bytes());
+        self.digest.update(bytes);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn flag(&mut self, flag: bool) {
+        self.digest.update([u8::from(flag)]);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.flag(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    fn opt_str(&mut self, s: Option<&str>) {
+        self.option(s, Self::str);
+    }
+
+    fn uuid(&mut self, uuid: Option<Uuid>) {
+        self.option(uuid, |f, uuid| f.digest.update(uuid.as_bytes()));
+    }
+
+    fn icon(&mut self, icon_id: Option<IconId>, custom_icon_uuid: Option<Uuid>) {
+        self.option(icon_id, |f, icon_id| f.digest.update((icon_id.0 as u64).to_le_bytes()));
+        self.uuid(custom_icon_uuid);
+    }
+
+    fn color(&mut self, color: Option<&Color>) {
+        self.option(color, |f, color| f.digest.update([color.r, color.g, color.b]));
+    }
+
+    /// Times are hashed in whole seconds, the precision they are saved with
+    fn time(&mut self, time: Option<NaiveDateTime>) {
+        self.option(time, |f, time| f.digest.update(time.and_utc().timestamp().to_le_bytes()));
+    }
+
+    fn tags(&mut self, tags: &[String]) {
+        self.digest.update((tags.len() as u64).to_le_bytes());
+        for tag in tags {
+            self.str(tag);
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Bytes(bytes) => {
+                self.digest.update([0]);
+                self.bytes(bytes);
+            }
+            Value::Unprotected(s) => {
+                self.digest.update([1]);
+                self.str(s);
+            }
+            Value::Protected(s) => {
+                self.digest.update([2]);
+                self.bytes(s.unsecure());
+            }
+        }
+    }
+
+    /// The times that describe the content, leaving out the last access time and the usage count
+    /// which change whenever a node is merely looked at
+    fn times(&mut self, times: &Times) {
+        self.flag(times.expires);
+        for tag in [
+            CREATION_TIME_TAG_NAME,
+            LAST_MODIFICATION_TIME_TAG_NAME,
+            EXPIRY_TIME_TAG_NAME,
+            LOCATION_CHANGED_TAG_NAME,
+        ] {
+            self.time(times.get(tag));
+        }
+    }
+
+    fn custom_data(&mut self, custom_data: &CustomData) {
+        let mut items: Vec<_> = custom_data.items.iter().collect();
+        items.sort_by_key(|(key, _)| *key);
+        self.digest.update((items.len() as u64).to_le_bytes());
+        for (key, item) in items {
+            self.str(key);
+            self.option(item.value.as_ref(), Self::value);
+            self.time(item.last_modification_time);
+        }
+    }
+
+    fn autotype(&mut self, autotype: Option<&AutoType>) {
+        self.option(autotype, |f, autotype| {
+            f.flag(autotype.enabled);
+            f.flag(autotype.data_transfer_obfuscation);
+            f.opt_str(autotype.sequence.as_deref());
+            f.digest.update((autotype.associations.len() as u64).to_le_bytes());
+            for association in &autotype.associations {
+                f.opt_str(association.window.as_deref());
+                f.opt_str(association.sequence.as_deref());
+            }
+        });
+    }
+
+    fn meta(&mut self) {
+        let meta = &self.db.meta;
+        self.opt_str(meta.database_name.as_deref());
+        self.opt_str(meta.database_description.as_deref());
+        self.opt_str(meta.default_username.as_deref());
+        self.color(meta.color.as_ref());
+        self.option(meta.recyclebin_enabled, Self::flag);
+        self.uuid(meta.recyclebin_uuid);
+        self.uuid(meta.entry_templates_group);
+        self.digest.update((meta.custom_icons.icons.len() as u64).to_le_bytes());
+        for icon in &meta.custom_icons.icons {
+            self.uuid(Some(icon.uuid));
+            self.bytes(&icon.data);
+        }
+        self.custom_data(&meta.custom_data);
+    }
+
+    fn node(&mut self, node: &NodePtr) {
+        if let Some(()) = with_node::<Entry, _, _>(node, |entry| {
+            self.digest.update([0]);
+            self.entry(entry);
+        }) {
+            return;
+        }
+        with_node::<Group, _, _>(node, |group| {
+            self.digest.update([1]);
+            self.group(group);
+        });
+        let children = group_get_children(node).unwrap_or_default();
+        self.digest.update((children.len() as u64).to_le_bytes());
+        for child in &children {
+            self.node(child);
+        }
+    }
+
+    /// The group itself, without its children. Whether the group is expanded and the last visible
+    /// entry are state of the user interface and left out.
+    fn group(&mut self, group: &Group) {
+        self.uuid(Some(group.uuid));
+        self.opt_str(group.name.as_deref());
+        self.opt_str(group.notes.as_deref());
+        self.icon(group.icon_id, group.custom_icon_uuid);
+        self.tags(&group.tags);
+        self.times(&group.times);
+        self.custom_data(&group.custom_data);
+        self.opt_str(group.default_autotype_sequence.as_deref());
+        self.opt_str(group.enable_autotype.as_deref());
+        self.opt_str(group.enable_searching.as_deref());
+    }
+
+    fn entry(&mut self, entry: &Entry) {
+        self.uuid(Some(entry.uuid));
+
+        let mut fields: Vec<_> = entry.fields.iter().collect();
+        fields.sort_by_key(|(key, _)| *key);
+        self.digest.update((fields.len() as u64).to_le_bytes());
+        for (key, value) in fields {
+            self.str(key);
+            self.value(value);
+        }
+
+        // attachments are hashed by content, as KDBX3 and KDBX4 reference them differently
+        self.digest.update((entry.attachments.len() as u64).to_le_bytes());
+        for attachment in &entry.attachments {
+            self.str(&attachment.name);
+            // content that cannot be decompressed is hashed like a missing attachment
+            let content = self.db.resolve_attachment(attachment).ok().flatten();
+            self.option(content.as_deref(), Self::bytes);
+        }
+
+        self.autotype(entry.autotype.as_ref());
+        self.tags(&entry.tags);
+        self.times(&entry.times);
+        self.custom_data(&entry.custom_data);
+        self.icon(entry.icon_id, entry.custom_icon_uuid);
+        self.color(entry.foreground_color.as_ref());
+        self.color(entry.background_color.as_ref());
+        self.opt_str(entry.override_url.as_deref());
+        self.option(entry.quality_check, Self::flag);
+
+        let history = entry.history.as_ref().map(|history| &history.entries[..]).unwrap_or_default();
+        self.digest.update((history.len() as u64).to_le_bytes());
+        for entry in history {
+            self.entry(entry);
+        }
+    }
+}
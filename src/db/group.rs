@@ -1,5 +1,6 @@
 use crate::{
-    db::{entry::Entry, node::*, rc_refcell_node, CustomData, IconId, Times},
+    db::{entry::Entry, node::*, rc_refcell_node, CustomData, CustomDataItem, IconId, Times, Value, GROUP_DEFAULT_USERNAME_KEY},
+    error::GetError,
     Result,
 };
 use uuid::Uuid;
@@ -30,6 +31,10 @@ pub enum MergeEventType {
 
     EntryUpdated,
     GroupCreated,
+
+    /// Removed by `Database::merge` because the other database deleted it
+    EntryDeleted,
+    GroupDeleted,
 }
 
 #[derive(Debug, Clone)]
@@ -92,6 +97,9 @@ pub struct Group {
     /// UUID for a custom group icon
     pub(crate) custom_icon_uuid: Option<Uuid>,
 
+    /// Tags of the group, available since KDBX 4.1
+    pub(crate) tags: Vec<String>,
+
     /// The list of child nodes (Groups or Entries)
     pub(crate) children: Vec<SerializableNodePtr>,
 
@@ -134,10 +142,12 @@ impl Default for Group {
             notes: None,
             icon_id: Some(IconId::FOLDER),
             custom_icon_uuid: None,
+            tags: Vec::new(),
             children: Vec::new(),
             times: Times::new(),
             custom_data: CustomData::default(),
-            is_expanded: false,
+            // like KeePass, new groups and groups without an IsExpanded tag are expanded
+            is_expanded: true,
             default_autotype_sequence: None,
             enable_autotype: None,
             enable_searching: None,
@@ -150,13 +160,27 @@ impl Default for Group {
 
 impl PartialEq for Group {
     fn eq(&self, other: &Self) -> bool {
+        self.compare(other, false)
+    }
+}
+
+impl Eq for Group {}
+
+impl Group {
+    /// Compare two groups like `==`, but ignore the history of all entries in them
+    pub fn eq_ignoring_history(&self, other: &Group) -> bool {
+        self.compare(other, true)
+    }
+
+    fn compare(&self, other: &Self, ignore_history: bool) -> bool {
         self.uuid == other.uuid
-            && self.compare_children(other)
+            && self.compare_children(other, ignore_history)
             && self.times == other.times
             && self.name == other.name
             && self.notes == other.notes
             && self.icon_id == other.icon_id
             && self.custom_icon_uuid == other.custom_icon_uuid
+            && self.tags == other.tags
             && self.is_expanded == other.is_expanded
             && self.default_autotype_sequence == other.default_autotype_sequence
             && self.enable_autotype == other.enable_autotype
@@ -167,8 +191,6 @@ impl PartialEq for Group {
     }
 }
 
-impl Eq for Group {}
-
 impl Node for Group {
     fn duplicate(&self) -> NodePtr {
         let mut new_group = self.clone();
@@ -250,7 +272,7 @@ impl Group {
         self.children.iter().map(|c| c.into()).collect()
     }
 
-    fn compare_children(&self, other: &Self) -> bool {
+    fn compare_children(&self, other: &Self, ignore_history: bool) -> bool {
         if self.children.len() != other.children.len() {
             return false;
         }
@@ -259,12 +281,12 @@ impl Group {
                 a.borrow().as_any().downcast_ref::<Group>(),
                 b.borrow().as_any().downcast_ref::<Group>(),
             ) {
-                a == b
+                a.compare(b, ignore_history)
             } else if let (Some(a), Some(b)) = (
                 a.borrow().as_any().downcast_ref::<Entry>(),
                 b.borrow().as_any().downcast_ref::<Entry>(),
             ) {
-                a == b
+                a.eq_ignoring_history(b) && (ignore_history || a.history == b.history)
             } else {
                 false
             }
@@ -275,6 +297,79 @@ impl Group {
         self.name = Some(name.to_string());
     }
 
+    /// Whether the group is expanded in the user interface
+    pub fn is_expanded(&self) -> bool {
+        self.is_expanded
+    }
+
+    pub fn set_expanded(&mut self, expanded: bool) {
+        self.is_expanded = expanded;
+    }
+
+    /// Whether searching is enabled in the group, or `None` if the group inherits the setting from
+    /// its parent, see `Database::is_searching_enabled`
+    pub fn searching_enabled(&self) -> Option<bool> {
+        parse_inheritable_flag(self.enable_searching.as_deref())
+    }
+
+    pub fn set_searching_enabled(&mut self, enabled: Option<bool>) {
+        self.enable_searching = Some(inheritable_flag_to_string(enabled));
+    }
+
+    /// Whether auto-type is enabled in the group, or `None` if the group inherits the setting from
+    /// its parent, see `Database::is_autotype_enabled`
+    pub fn autotype_enabled(&self) -> Option<bool> {
+        parse_inheritable_flag(self.enable_autotype.as_deref())
+    }
+
+    pub fn set_autotype_enabled(&mut self, enabled: Option<bool>) {
+        self.enable_autotype = Some(inheritable_flag_to_string(enabled));
+    }
+
+    /// Convenience method for getting tags
+    /// Returns a Vec of tags
+    pub fn get_tags(&self) -> &Vec<String> {
+        self.tags.as_ref()
+    }
+
+    pub fn get_tags_mut(&mut self) -> &mut Vec<String> {
+        self.tags.as_mut()
+    }
+
+    pub fn get_custom_data(&self) -> &CustomData {
+        &self.custom_data
+    }
+
+    /// Set a custom data item, see `CustomData::set`
+    pub fn set_custom_data(&mut self, key: &str, value: Value) {
+        self.custom_data.set(key, value);
+    }
+
+    pub fn remove_custom_data(&mut self, key: &str) -> Option<CustomDataItem> {
+        self.custom_data.remove(key)
+    }
+
+    /// The user name that new entries in the group get from `Database::create_new_entry_with_defaults`.
+    /// `KeePass` has no such setting for groups, so it is kept in the custom data under
+    /// `GROUP_DEFAULT_USERNAME_KEY`.
+    pub fn default_username(&self) -> Option<&str> {
+        match self.custom_data.get(GROUP_DEFAULT_USERNAME_KEY)? {
+            Value::Unprotected(username) => Some(username.as_str()),
+            Value::Protected(username) => std::str::from_utf8(username.unsecure()).ok(),
+            Value::Bytes(_) => None,
+        }
+        .filter(|username| !username.is_empty())
+    }
+
+    pub fn set_default_username(&mut self, username: Option<&str>) {
+        match username {
+            Some(username) => self.set_custom_data(GROUP_DEFAULT_USERNAME_KEY, Value::Unprotected(username.to_string())),
+            None => {
+                self.remove_custom_data(GROUP_DEFAULT_USERNAME_KEY);
+            }
+        }
+    }
+
     pub fn add_child(&mut self, child: NodePtr, index: usize) {
         child.borrow_mut().set_parent(Some(self.get_uuid()));
         if index < self.children.len() {
@@ -284,6 +379,50 @@ impl Group {
         }
     }
 
+    /// Like `add_child`, but refuse to add a group that contains this group, which would
+    /// create a cycle in the tree.
+    pub fn try_add_child(&mut self, child: NodePtr, index: usize) -> Result<()> {
+        if self.subtree_contains(&child) {
+            return Err("Cannot add a group into itself or one of its descendants".into());
+        }
+        self.add_child(child, index);
+        Ok(())
+    }
+
+    /// Whether `node` is this group or has it somewhere below it. This group is mutably borrowed
+    /// while a child is added, so its own node is recognized by address, all others by UUID.
+    fn subtree_contains(&self, node: &NodePtr) -> bool {
+        if std::ptr::addr_eq(node.as_ptr(), self) {
+            return true;
+        }
+        // a node that is mutably borrowed elsewhere is not this group
+        let Ok(node) = node.try_borrow() else {
+            return false;
+        };
+        if node.get_uuid() == self.get_uuid() {
+            return true;
+        }
+        node.as_any()
+            .downcast_ref::<Group>()
+            .is_some_and(|group| group.children.iter().any(|child| self.subtree_contains(child)))
+    }
+
+    /// Append many entries at once, giving all of them the same creation, modification,
+    /// access and location change time.
+    pub fn add_entries(&mut self, entries: Vec<Entry>) {
+        let now = Some(Times::now());
+        let uuid = self.get_uuid();
+        self.children.reserve(entries.len());
+        for mut entry in entries {
+            entry.times.set_creation(now);
+            entry.times.set_last_modification(now);
+            entry.times.set_last_access(now);
+            entry.times.set_location_changed(now);
+            entry.parent = Some(uuid);
+            self.children.push(rc_refcell_node(entry).into());
+        }
+    }
+
     /// Recursively get a Group or Entry reference by specifying a path relative to the current Group
     /// ```
     /// use keepass_ng::{
@@ -304,6 +443,27 @@ impl Group {
         self.get_internal(path, SearchField::Title)
     }
 
+    /// Like `get`, but also check that the node found is a `T`, i.e. an `Entry` or a `Group`
+    pub fn get_typed<T: Node>(&self, path: &[&str]) -> std::result::Result<NodePtr, GetError> {
+        let is_t = |node: &NodePtr| with_node::<T, _, _>(node, |_| ()).is_some();
+        let Some((name, parents)) = path.split_last() else {
+            let node = self.get(path).ok_or(GetError::NotFound)?;
+            return if is_t(&node) { Ok(node) } else { Err(GetError::WrongType) };
+        };
+
+        let siblings: Vec<NodePtr> = if parents.is_empty() {
+            self.children.iter().map(NodePtr::from).collect()
+        } else {
+            let parent = self.get(parents).ok_or(GetError::NotFound)?;
+            group_get_children(&parent).ok_or(GetError::NotFound)?
+        };
+        let found: Vec<NodePtr> = siblings.into_iter().filter(|node| SearchField::Title.matches(node, name)).collect();
+        if found.is_empty() {
+            return Err(GetError::NotFound);
+        }
+        found.into_iter().find(is_t).ok_or(GetError::WrongType)
+    }
+
     #[cfg(test)]
     pub(crate) fn get_by_uuid<T: AsRef<str>>(&self, path: &[T]) -> Option<NodePtr> {
         self.get_internal(path, SearchField::Uuid)
@@ -345,6 +505,13 @@ impl Group {
         response
     }
 
+    /// Whether an entry directly in this group has the title `title`
+    pub fn contains_title(&self, title: &str) -> bool {
+        self.children
+            .iter()
+            .any(|node| node_is_entry(node) && node.borrow().get_title() == Some(title))
+    }
+
     pub fn groups(&self) -> Vec<NodePtr> {
         let mut response: Vec<NodePtr> = vec![];
         for node in &self.children {
@@ -531,7 +698,8 @@ impl Group {
         Ok(())
     }
 
-    /// Merge this group with another group
+    /// Merge this group with another group. The deleted objects of the databases are not known
+    /// here, see `Database::merge` for a merge that does not bring back deleted entries.
     #[allow(clippy::too_many_lines)]
     pub fn merge(root: &NodePtr, other_group: &NodePtr) -> Result<MergeLog> {
         let mut log = MergeLog::default();
@@ -610,13 +778,18 @@ impl Group {
                 };
 
                 if destination_last_modification == source_last_modification {
-                    if !node_is_equals_to(&existing_entry, entry) {
+                    let same_state = with_node::<Entry, _, _>(&existing_entry, |existing| {
+                        with_node::<Entry, _, _>(entry, |entry| existing.eq_ignoring_history(entry))
+                    })
+                    .flatten()
+                    .unwrap_or(false);
+                    if !same_state {
                         // This should never happen.
                         // This means that an entry was updated without updating the last modification
                         // timestamp.
                         return Err("Entries have the same modification time but are not the same!".into());
                     }
-                    continue;
+                    // Only the histories differ, merge them below
                 }
 
                 let (merged_entry, entry_merge_log) = if destination_last_modification > source_last_modification {
@@ -646,7 +819,6 @@ impl Group {
             }
         }
 
-        // TODO handle deleted objects
         Ok(log)
     }
 
@@ -670,6 +842,20 @@ impl Group {
     }
 }
 
+/// KeePass stores the searching and auto-type settings of groups as `true`, `false` or `null`,
+/// the latter meaning that the setting is inherited from the parent group
+fn parse_inheritable_flag(value: Option<&str>) -> Option<bool> {
+    match value? {
+        v if v.eq_ignore_ascii_case("true") => Some(true),
+        v if v.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+fn inheritable_flag_to_string(enabled: Option<bool>) -> String {
+    enabled.map_or("null", |enabled| if enabled { "true" } else { "false" }).to_string()
+}
+
 #[cfg(test)]
 mod group_tests {
     use super::{Entry, Group, GroupRef, Node, Times};
@@ -832,11 +1018,6 @@ mod group_tests {
         assert!(with_node::<Group, _, _>(&source_group, |g| g.get_all_entries(&vec![]))
             .unwrap()
             .is_empty());
-        // FIXME we should not have to update the history here. We should
-        // have a better compare function in the merge function instead.
-        with_node_mut::<Entry, _, _>(&removed_entry, |entry| {
-            entry.update_history();
-        });
 
         let location = vec![
             GroupRef::new(destination_group_uuid, ""),
@@ -875,9 +1056,6 @@ mod group_tests {
         thread::sleep(time::Duration::from_secs(1));
         with_node_mut::<Entry, _, _>(&entry, |entry| {
             entry.times.set_location_changed(Some(Times::now()));
-            // FIXME we should not have to update the history here. We should
-            // have a better compare function in the merge function instead.
-            entry.update_history();
         });
         group_add_child(&source_sub_group, entry, 0).unwrap();
         with_node_mut::<Group, _, _>(&source_group, |g| {
@@ -943,6 +1121,39 @@ mod group_tests {
         assert_eq!(entry.borrow().get_title(), Some("entry1_updated"));
     }
 
+    #[test]
+    fn test_merge_history_only_difference() {
+        let destination_group = rc_refcell_node(Group::new("group1"));
+
+        let entry = rc_refcell_node(Entry::default());
+        entry_set_field_and_commit(&entry, "Title", "entry1").unwrap();
+        group_add_child(&destination_group, entry, 0).unwrap();
+
+        let source_group = destination_group.borrow().duplicate();
+        let entry = with_node::<Group, _, _>(&source_group, |g| g.entries()[0].clone()).unwrap();
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            let mut old_version = e.clone();
+            old_version.times.set_last_modification(Some(Times::epoch()));
+            e.history.as_mut().unwrap().entries.push(old_version);
+        });
+
+        assert_ne!(
+            with_node::<Group, _, _>(&destination_group, |g| g.clone()).unwrap(),
+            with_node::<Group, _, _>(&source_group, |g| g.clone()).unwrap()
+        );
+        with_node::<Group, _, _>(&destination_group, |d| {
+            with_node::<Group, _, _>(&source_group, |s| assert!(d.eq_ignoring_history(s)));
+        });
+
+        let merge_result = Group::merge(&destination_group, &source_group).unwrap();
+        assert_eq!(merge_result.warnings.len(), 0);
+
+        let entry = with_node::<Group, _, _>(&destination_group, |g| g.entries()[0].clone()).unwrap();
+        let merged_history = with_node::<Entry, _, _>(&entry, |e| e.history.clone().unwrap()).unwrap();
+        assert_eq!(merged_history.get_entries().len(), 2);
+        assert!(merged_history.is_ordered());
+    }
+
     #[test]
     fn test_update_with_conflicts() {
         let destination_group = rc_refcell_node(Group::new("group1"));
@@ -999,6 +1210,61 @@ mod group_tests {
         .unwrap();
     }
 
+    #[test]
+    fn add_child_cycle() {
+        let parent = rc_refcell_node(Group::new("parent"));
+        let child = rc_refcell_node(Group::new("child"));
+        let grandchild = rc_refcell_node(Group::new("grandchild"));
+        group_add_child(&parent, child.clone(), 0).unwrap();
+        group_add_child(&child, grandchild.clone(), 0).unwrap();
+
+        assert!(group_add_child(&grandchild, parent.clone(), 0).is_err());
+        assert!(group_add_child(&child, child.clone(), 0).is_err());
+        assert!(with_node_mut::<Group, _, _>(&grandchild, |g| g.try_add_child(parent.clone(), 0))
+            .unwrap()
+            .is_err());
+        assert_eq!(group_get_children(&grandchild).unwrap().len(), 0);
+        assert_eq!(NodeIterator::new(&parent).count(), 3);
+
+        // a node that is borrowed elsewhere is not mistaken for the group being added to
+        let sibling = rc_refcell_node(Group::new("sibling"));
+        {
+            let _borrowed = grandchild.borrow_mut();
+            assert!(group_add_child(&sibling, child.clone(), 0).is_ok());
+        }
+        assert_eq!(group_get_children(&sibling).unwrap().len(), 1);
+
+        let other = rc_refcell_node(Group::new("other"));
+        assert!(group_add_child(&grandchild, other, 0).is_ok());
+        assert!(group_add_child(&grandchild, rc_refcell_node(Entry::default()), 0).is_ok());
+        assert_eq!(NodeIterator::new(&parent).count(), 5);
+    }
+
+    #[test]
+    fn get_typed() {
+        use crate::error::GetError;
+
+        let db = Database::new(Default::default());
+        let general_group = rc_refcell_node(Group::new("General"));
+        let sample_entry = rc_refcell_node(Entry::default());
+        sample_entry.borrow_mut().set_title(Some("Sample Entry"));
+        group_add_child(&general_group, sample_entry, 0).unwrap();
+        group_add_child(&db.root, general_group, 0).unwrap();
+
+        with_node::<Group, _, _>(&db.root, |g| {
+            assert!(g.get_typed::<Group>(&["General"]).is_ok());
+            assert!(g.get_typed::<Entry>(&["General", "Sample Entry"]).is_ok());
+            assert!(g.get_typed::<Group>(&[]).is_ok());
+
+            assert_eq!(g.get_typed::<Group>(&["General", "Sample Entry"]).err(), Some(GetError::WrongType));
+            assert_eq!(g.get_typed::<Entry>(&["General"]).err(), Some(GetError::WrongType));
+            assert_eq!(g.get_typed::<Entry>(&[]).err(), Some(GetError::WrongType));
+            assert_eq!(g.get_typed::<Entry>(&["General", "Missing"]).err(), Some(GetError::NotFound));
+            assert_eq!(g.get_typed::<Entry>(&["Missing", "Sample Entry"]).err(), Some(GetError::NotFound));
+        })
+        .unwrap();
+    }
+
     #[test]
     fn get_by_uuid() {
         let db = Database::new(Default::default());
@@ -6,6 +6,20 @@ use std::convert::TryFrom;
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
 pub struct IconId(pub usize);
 
+/// The icon to show for a node, as resolved by `Database::effective_icon`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub enum EffectiveIcon {
+    /// One of the standard icons
+    Standard(IconId),
+
+    /// Image data of a custom icon
+    Custom(Vec<u8>),
+
+    /// The node has no icon of its own and shows the icon of the group `group`
+    Inherited { group: uuid::Uuid, icon: Box<EffectiveIcon> },
+}
+
 impl std::fmt::Display for IconId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         const EMOJIS: [&str; 69] = [
@@ -90,12 +104,18 @@ impl IconId {
     pub const DOLLAR: IconId = IconId(66);
     pub const CERTIFICATE: IconId = IconId(67);
     pub const MOBILE_PHONE: IconId = IconId(68);
+
+    /// Whether this is one of the standard `KeePass` icons. Databases can reference other
+    /// indices, which are kept as they are so that they survive a round trip.
+    pub fn is_standard(&self) -> bool {
+        self.0 <= Self::MOBILE_PHONE.0
+    }
 }
 
 impl TryFrom<usize> for IconId {
     type Error = crate::error::Error;
     fn try_from(value: usize) -> Result<Self, Self::Error> {
-        if value > 68 {
+        if !IconId(value).is_standard() {
             return Err(crate::error::Error::ParseIconIdError { icon_id: value });
         }
         Ok(Self(value))
@@ -1,7 +1,12 @@
+use std::borrow::Cow;
+
 use chrono::NaiveDateTime;
 use uuid::Uuid;
 
-use crate::db::{Color, CustomData};
+use crate::{
+    compression::{Compression, GZipCompression},
+    db::{Color, CustomData, CustomDataItem, Value},
+};
 
 /// Database metadata
 #[derive(Debug, Default, Eq, PartialEq, Clone)]
@@ -84,10 +89,14 @@ pub struct Meta {
     pub custom_data: CustomData,
 }
 
+/// Generator name written to databases that do not specify one
+pub(crate) const DEFAULT_GENERATOR: &str = concat!("keepass-ng ", env!("CARGO_PKG_VERSION"));
+
 impl Meta {
     /// Create a new Meta object
     pub fn new() -> Self {
         Self {
+            generator: Some(DEFAULT_GENERATOR.to_string()),
             recyclebin_enabled: Some(true),
             ..Meta::default()
         }
@@ -112,6 +121,20 @@ impl Meta {
         let time = chrono::Local::now().naive_local();
         self.recyclebin_changed = Some(time);
     }
+
+    /// Set a custom data item, see `CustomData::set`
+    pub fn set_custom_data(&mut self, key: &str, value: Value) {
+        self.custom_data.set(key, value);
+    }
+
+    /// Get the value of a custom data item
+    pub fn get_custom_data(&self, key: &str) -> Option<&Value> {
+        self.custom_data.get(key)
+    }
+
+    pub fn remove_custom_data(&mut self, key: &str) -> Option<CustomDataItem> {
+        self.custom_data.remove(key)
+    }
 }
 
 /// Database memory protection settings
@@ -146,6 +169,21 @@ impl Default for MemoryProtection {
     }
 }
 
+impl MemoryProtection {
+    /// Whether the value of the field `field_name` has to be stored protected. Only the standard
+    /// fields are covered by the settings, other fields carry their own protection flag.
+    pub fn protects_field(&self, field_name: &str) -> bool {
+        match field_name {
+            "Title" => self.protect_title,
+            "UserName" => self.protect_username,
+            "Password" => self.protect_password,
+            "URL" => self.protect_url,
+            "Notes" => self.protect_notes,
+            _ => false,
+        }
+    }
+}
+
 /// Collection of custom icons
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -172,10 +210,28 @@ pub struct BinaryAttachments {
 }
 
 /// Binary attachment in the metadata of a XML database
+///
+/// `content` holds the bytes as they are stored in the file, so if `compressed` is set, they
+/// must already be GZip-compressed. Earlier versions kept the decompressed bytes and compressed
+/// them when saving; code that builds compressed attachments itself has to compress the content
+/// now, or leave `compressed` unset. Use `content_decompressed` to read the content.
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
 pub struct BinaryAttachment {
     pub identifier: Option<String>,
     pub compressed: bool,
+    /// The raw bytes as stored in the file, not the attachment data: if `compressed` is set,
+    /// this is the GZip stream. Read the attachment data with `content_decompressed`.
     pub content: Vec<u8>,
 }
+
+impl BinaryAttachment {
+    /// Get the attachment content, decompressing it if necessary. Fails if the content is
+    /// marked as compressed but is not valid GZip data.
+    pub fn content_decompressed(&self) -> Result<Cow<'_, [u8]>, std::io::Error> {
+        if !self.compressed {
+            return Ok(Cow::Borrowed(&self.content));
+        }
+        GZipCompression.decompress(&self.content).map(Cow::Owned)
+    }
+}
@@ -1,40 +1,55 @@
 //! Types for representing data contained in a `KeePass` database
 
 pub(crate) mod entry;
+mod fingerprint;
 pub(crate) mod group;
 pub(crate) mod iconid;
 pub(crate) mod meta;
 pub(crate) mod node;
+pub(crate) mod snapshot;
 
 #[cfg(feature = "totp")]
 pub(crate) mod otp;
 
 pub use crate::db::{
-    entry::{AutoType, AutoTypeAssociation, Entry, History, Value},
-    group::Group,
+    entry::{AttachmentRef, AutoType, AutoTypeAssociation, Entry, History, OverrideUrlKind, Value},
+    group::{Group, MergeEvent, MergeEventType, MergeLog},
+    iconid::{EffectiveIcon, IconId},
     meta::{BinaryAttachment, BinaryAttachments, CustomIcons, Icon, MemoryProtection, Meta},
     node::*,
+    snapshot::{DatabaseSnapshot, EntrySnapshot, GroupSnapshot},
 };
 use chrono::NaiveDateTime;
-use std::{collections::HashMap, str::FromStr};
+use secstr::SecStr;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 use uuid::Uuid;
 
 #[cfg(feature = "totp")]
 pub use crate::db::otp::{TOTPAlgorithm, TOTP};
 
+#[cfg(feature = "threadsafe")]
+pub use crate::db::snapshot::SyncDatabase;
+
 use crate::{
-    config::DatabaseConfig,
-    db::iconid::IconId,
+    config::{DatabaseConfig, KdfConfig},
     error::{DatabaseIntegrityError, DatabaseOpenError, ParseColorError},
     format::{
-        kdb::parse_kdb,
-        kdbx3::{decrypt_kdbx3, parse_kdbx3},
-        kdbx4::{decrypt_kdbx4, parse_kdbx4},
-        DatabaseVersion,
+        kdb::{parse_kdb, read_kdf_config_kdb, validate_header_kdb},
+        kdb2::{check_key_kdb2, decrypt_kdb2, parse_kdb2, read_kdf_config_kdb2, validate_header_kdb2},
+        kdbx3::{check_key_kdbx3, decrypt_kdbx3, parse_kdbx3, read_kdf_config_kdbx3, validate_header_kdbx3},
+        kdbx4::{check_key_kdbx4, decrypt_kdbx4, parse_kdbx4, read_kdf_config_kdbx4, validate_header_kdbx4},
+        DatabaseVersion, KDBX4_CURRENT_MINOR_VERSION,
     },
     key::DatabaseKey,
 };
 
+#[cfg(feature = "save_kdbx4")]
+use crate::format::ParsedHeader;
+
 /// A decrypted `KeePass` database
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -53,6 +68,15 @@ pub struct Database {
 
     /// Metadata of the KeePass database
     pub meta: Meta,
+
+    /// Problems in the file that were worked around while reading the database, see `warnings`
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub(crate) warnings: Vec<String>,
+
+    /// Header of the file the database was read from, see `DumpOptions::reuse_seeds`
+    #[cfg(feature = "save_kdbx4")]
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub(crate) original_header: Option<ParsedHeader>,
 }
 
 impl PartialEq for Database {
@@ -76,46 +100,278 @@ impl Database {
         Database::parse(data.as_ref(), key)
     }
 
+    /// Like `open`, but also returns the non-fatal issues found while parsing, e.g. skipped unknown
+    /// header fields or multiple root groups that had to be merged. These are also kept in `warnings`.
+    pub fn open_with_warnings(source: &mut dyn std::io::Read, key: DatabaseKey) -> Result<(Database, Vec<String>), DatabaseOpenError> {
+        let db = Database::open(source, key)?;
+        let warnings = db.warnings.clone();
+        Ok((db, warnings))
+    }
+
+    /// The problems in the file that were worked around while reading the database, e.g. skipped
+    /// unknown header fields. Empty for a database that was created rather than read.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Parse a database from anything that can be borrowed as bytes, e.g. a `Vec<u8>` or a memory-mapped
+    /// file. Unlike `open`, the input is not copied into a buffer first.
+    pub fn parse_borrowed<D: AsRef<[u8]> + ?Sized>(data: &D, key: DatabaseKey) -> Result<Database, DatabaseOpenError> {
+        Database::parse(data.as_ref(), key)
+    }
+
+    /// Parse a database from bytes. Truncated or malformed data is reported as an error, mostly a
+    /// `DatabaseIntegrityError`, so this can be used on untrusted input.
     pub fn parse(data: &[u8], key: DatabaseKey) -> Result<Database, DatabaseOpenError> {
         let database_version = DatabaseVersion::parse(data)?;
 
         match database_version {
             DatabaseVersion::KDB(_) => parse_kdb(data, &key),
-            DatabaseVersion::KDB2(_) => Err(DatabaseOpenError::UnsupportedVersion),
+            DatabaseVersion::KDB2(_) => parse_kdb2(data, &key),
             DatabaseVersion::KDB3(_) => parse_kdbx3(data, &key),
             DatabaseVersion::KDB4(_) => parse_kdbx4(data, &key),
         }
     }
 
+    /// Convert a database opened from an older format to KDBX4 in memory, so that `save` writes
+    /// KDBX4. The key is derived with Argon2id and protected fields are encrypted with ChaCha20
+    /// from then on, with the costs of `DatabaseConfig::default`. Attachments move from
+    /// `meta.binaries` to `header_attachments` and the references of all entries are updated,
+    /// everything else is left untouched. Does nothing for KDBX4 databases. Fails without changing
+    /// anything if an attachment cannot be decompressed.
+    pub fn upgrade_to_kdbx4(&mut self) -> crate::Result<()> {
+        if matches!(self.config.version, DatabaseVersion::KDB4(_)) {
+            return Ok(());
+        }
+        let contents = self
+            .meta
+            .binaries
+            .binaries
+            .iter()
+            .map(|binary| binary.content_decompressed().map(Cow::into_owned))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // KDBX3 files use Salsa20 for protected fields, take the cipher along with the KDF from the
+        // KDBX4 defaults instead of keeping a setting of the old format
+        let default = DatabaseConfig::default_for_version(DatabaseVersion::KDB4(KDBX4_CURRENT_MINOR_VERSION));
+        self.config.version = default.version;
+        self.config.inner_cipher_config = default.inner_cipher_config;
+        self.config.kdf_config = match default.kdf_config {
+            KdfConfig::Argon2 {
+                iterations,
+                memory,
+                parallelism,
+                version,
+            }
+            | KdfConfig::Argon2id {
+                iterations,
+                memory,
+                parallelism,
+                version,
+            } => KdfConfig::Argon2id {
+                iterations,
+                memory,
+                parallelism,
+                version,
+            },
+            aes @ KdfConfig::Aes { .. } => aes,
+        };
+        // the seeds of the old file cannot be reused for another format
+        #[cfg(feature = "save_kdbx4")]
+        {
+            self.original_header = None;
+        }
+
+        let mut indices = HashMap::new();
+        for (binary, content) in std::mem::take(&mut self.meta.binaries.binaries).into_iter().zip(contents) {
+            if let Some(identifier) = binary.identifier {
+                indices.insert(identifier, self.header_attachments.len().to_string());
+            }
+            self.header_attachments.push(HeaderAttachment { flags: 0, content });
+        }
+        let update_refs = |entry: &mut Entry| {
+            for attachment in &mut entry.attachments {
+                if let Some(index) = indices.get(&attachment.identifier) {
+                    attachment.identifier.clone_from(index);
+                }
+            }
+        };
+        for node in NodeIterator::new(&self.root) {
+            with_node_mut::<Entry, _, _>(&node, |entry| {
+                update_refs(entry);
+                for version in entry.history.iter_mut().flat_map(|history| history.entries.iter_mut()) {
+                    update_refs(version);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Check the encryption settings of the database for deprecated or weak choices, see
+    /// `DatabaseConfig::security_assessment`
+    pub fn security_assessment(&self) -> crate::config::SecurityAssessment {
+        self.config.security_assessment()
+    }
+
+    /// A SHA-256 hash of the content of the database, for detecting changes, e.g. when syncing.
+    ///
+    /// Only the content is hashed, so the fingerprint does not depend on the format version, the
+    /// encryption settings or seeds of the file. Volatile data is left out as well: last access
+    /// times, usage counts, the state of the user interface and the timestamps in the metadata.
+    pub fn content_fingerprint(&self) -> [u8; 32] {
+        fingerprint::Fingerprint::new(self).finish()
+    }
+
+    /// Copy the database into plain values without interior mutability, e.g. to hand it to
+    /// another thread for reading. Fails if the root node is not a group.
+    pub fn snapshot(&self) -> crate::Result<DatabaseSnapshot> {
+        Ok(DatabaseSnapshot {
+            config: self.config.clone(),
+            meta: self.meta.clone(),
+            root: GroupSnapshot::new(&self.root).ok_or("The root node is not a group")?,
+        })
+    }
+
+    /// Check whether `key` opens the database read from `source`, without parsing its content.
+    ///
+    /// For KDBX4 only the header HMAC is verified, for KDBX3 the payload is decrypted to compare
+    /// its stream start bytes. The reader is rewound to where it was before the call.
+    pub fn check_key<R: std::io::Read + std::io::Seek>(source: &mut R, key: DatabaseKey) -> Result<bool, DatabaseOpenError> {
+        let start = source.stream_position()?;
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+        source.seek(std::io::SeekFrom::Start(start))?;
+
+        Self::check_key_data(&data, &key)
+    }
+
+    fn check_key_data(data: &[u8], key: &DatabaseKey) -> Result<bool, DatabaseOpenError> {
+        use crate::error::DatabaseKeyError;
+
+        match DatabaseVersion::parse(data)? {
+            DatabaseVersion::KDB(_) => match parse_kdb(data, key) {
+                Ok(_) => Ok(true),
+                Err(DatabaseOpenError::Key(DatabaseKeyError::IncorrectKey)) => Ok(false),
+                Err(err) => Err(err),
+            },
+            DatabaseVersion::KDB2(_) => check_key_kdb2(data, key),
+            DatabaseVersion::KDB3(_) => check_key_kdbx3(data, key),
+            DatabaseVersion::KDB4(_) => check_key_kdbx4(data, key),
+        }
+    }
+
+    /// Find out what a database read from `source` needs to be opened, before asking the user for
+    /// a key. The file format does not record which key components were used, so the only thing
+    /// that can be checked is whether an empty password opens it. This runs the key derivation
+    /// once, which takes as long as opening the database.
+    pub fn probe_key_requirements(source: &mut dyn std::io::Read) -> Result<KeyProbe, DatabaseOpenError> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+
+        let version = DatabaseVersion::parse(data.as_ref())?;
+        let kdf_config = match version {
+            DatabaseVersion::KDB(_) => read_kdf_config_kdb(data.as_ref())?,
+            DatabaseVersion::KDB2(_) => read_kdf_config_kdb2(data.as_ref())?,
+            DatabaseVersion::KDB3(_) => read_kdf_config_kdbx3(data.as_ref())?,
+            DatabaseVersion::KDB4(_) => read_kdf_config_kdbx4(data.as_ref())?,
+        };
+        let opens_with_empty_password = Self::check_key_data(&data, &DatabaseKey::new().with_password(""))?;
+
+        Ok(KeyProbe {
+            version,
+            kdf_config,
+            opens_with_empty_password,
+        })
+    }
+
     /// Save a database to a `std::io::Write`
     #[cfg(feature = "save_kdbx4")]
     pub fn save(&self, destination: &mut dyn std::io::Write, key: DatabaseKey) -> Result<(), crate::error::DatabaseSaveError> {
+        self.save_with_options(destination, key, &crate::config::DumpOptions::default())
+    }
+
+    /// Save a database to a `std::io::Write` with the given options
+    #[cfg(feature = "save_kdbx4")]
+    pub fn save_with_options(
+        &self,
+        destination: &mut dyn std::io::Write,
+        key: DatabaseKey,
+        options: &crate::config::DumpOptions,
+    ) -> Result<(), crate::error::DatabaseSaveError> {
+        use crate::error::DatabaseSaveError;
+        use crate::format::kdbx4::dump_kdbx4_with_options;
+
+        match self.config.version {
+            DatabaseVersion::KDB(_) => Err(DatabaseSaveError::UnsupportedVersion),
+            DatabaseVersion::KDB2(_) => Err(DatabaseSaveError::UnsupportedVersion),
+            DatabaseVersion::KDB3(_) => Err(DatabaseSaveError::UnsupportedVersion),
+            DatabaseVersion::KDB4(_) => dump_kdbx4_with_options(self, &key, options, destination),
+        }
+    }
+
+    /// Save a database to a `std::io::Write`, calling `progress` with the number of entries written
+    /// so far and the total number of entries after each entry. Entries in the history of other
+    /// entries are not counted.
+    #[cfg(feature = "save_kdbx4")]
+    pub fn save_with_progress(
+        &self,
+        destination: &mut dyn std::io::Write,
+        key: DatabaseKey,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<(), crate::error::DatabaseSaveError> {
         use crate::error::DatabaseSaveError;
-        use crate::format::kdbx4::dump_kdbx4;
+        use crate::format::kdbx4::dump_kdbx4_with_progress;
 
+        let options = crate::config::DumpOptions::default();
         match self.config.version {
             DatabaseVersion::KDB(_) => Err(DatabaseSaveError::UnsupportedVersion),
             DatabaseVersion::KDB2(_) => Err(DatabaseSaveError::UnsupportedVersion),
             DatabaseVersion::KDB3(_) => Err(DatabaseSaveError::UnsupportedVersion),
-            DatabaseVersion::KDB4(_) => dump_kdbx4(self, &key, destination),
+            DatabaseVersion::KDB4(_) => dump_kdbx4_with_progress(self, &key, &options, &mut progress, destination),
         }
     }
 
     /// Helper function to load a database into its internal XML chunks
     pub fn get_xml(source: &mut dyn std::io::Read, key: DatabaseKey) -> Result<Vec<u8>, DatabaseOpenError> {
+        Ok(Self::decrypt_xml(source, &key)?.1)
+    }
+
+    /// Like `get_xml`, additionally decrypting the protected values in the XML, which are written
+    /// as plain text marked with `ProtectInMemory="True"`, e.g. to debug how a database was saved.
+    ///
+    /// **Dangerous:** the output contains all passwords of the database in plain text. Never store
+    /// or share it, and do not use this for anything but debugging.
+    pub fn get_xml_decrypted(source: &mut dyn std::io::Read, key: DatabaseKey) -> Result<Vec<u8>, DatabaseOpenError> {
+        let (mut inner_cipher, xml) = Self::decrypt_xml(source, &key)?;
+        Ok(crate::xml_db::decrypt_protected_values(&xml, &mut *inner_cipher)?)
+    }
+
+    fn decrypt_xml(
+        source: &mut dyn std::io::Read,
+        key: &DatabaseKey,
+    ) -> Result<(Box<dyn crate::crypt::ciphers::Cipher>, Vec<u8>), DatabaseOpenError> {
         let mut data = Vec::new();
         source.read_to_end(&mut data)?;
 
         let database_version = DatabaseVersion::parse(data.as_ref())?;
 
-        let data = match database_version {
-            DatabaseVersion::KDB(_) => return Err(DatabaseOpenError::UnsupportedVersion),
-            DatabaseVersion::KDB2(_) => return Err(DatabaseOpenError::UnsupportedVersion),
-            DatabaseVersion::KDB3(_) => decrypt_kdbx3(data.as_ref(), &key)?.2,
-            DatabaseVersion::KDB4(_) => decrypt_kdbx4(data.as_ref(), &key)?.3,
-        };
+        match database_version {
+            DatabaseVersion::KDB(_) => Err(DatabaseOpenError::UnsupportedVersion),
+            DatabaseVersion::KDB2(_) => decrypt_kdb2(data.as_ref(), key).map(|(_, inner_cipher, xml)| (inner_cipher, xml)),
+            DatabaseVersion::KDB3(_) => decrypt_kdbx3(data.as_ref(), key).map(|(_, inner_cipher, xml)| (inner_cipher, xml)),
+            DatabaseVersion::KDB4(_) => decrypt_kdbx4(data.as_ref(), key).map(|(_, _, inner_cipher, xml)| (inner_cipher, xml)),
+        }
+    }
 
-        Ok(data)
+    /// Dump the internal XML of this database indented for human inspection. Protected values are
+    /// written with the plain inner stream, i.e. only Base64 encoded, so the output must be kept as
+    /// confidential as the database itself.
+    #[cfg(feature = "save_kdbx4")]
+    pub fn get_xml_pretty(&self) -> Result<Vec<u8>, crate::error::DatabaseSaveError> {
+        let mut inner_cipher = crate::config::InnerCipherConfig::Plain.get_cipher(&[]);
+        let mut xml = Vec::new();
+        crate::xml_db::dump::dump(self, &mut *inner_cipher, true, &mut xml)?;
+        Ok(xml)
     }
 
     /// Get the version of a database without decrypting it
@@ -125,6 +381,22 @@ impl Database {
         DatabaseVersion::parse(data.as_ref())
     }
 
+    /// Check that the outer header of a database is complete and well-formed, without needing
+    /// the key, e.g. to report a corrupt file before asking for a password
+    pub fn validate_header(source: &mut dyn std::io::Read) -> Result<DatabaseVersion, DatabaseOpenError> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+
+        let database_version = DatabaseVersion::parse(data.as_ref())?;
+        match database_version {
+            DatabaseVersion::KDB(_) => validate_header_kdb(data.as_ref())?,
+            DatabaseVersion::KDB2(_) => validate_header_kdb2(data.as_ref())?,
+            DatabaseVersion::KDB3(_) => validate_header_kdbx3(data.as_ref())?,
+            DatabaseVersion::KDB4(_) => validate_header_kdbx4(data.as_ref())?,
+        }
+        Ok(database_version)
+    }
+
     /// Create a new, empty database
     pub fn new(config: DatabaseConfig) -> Database {
         Self {
@@ -133,9 +405,17 @@ impl Database {
             root: rc_refcell_node(Group::new("Root")).into(),
             deleted_objects: DeletedObjects::default(),
             meta: Meta::new(),
+            warnings: Vec::new(),
+            #[cfg(feature = "save_kdbx4")]
+            original_header: None,
         }
     }
 
+    /// Create an empty database with the default configuration for `version`
+    pub fn new_with_version(version: DatabaseVersion) -> Database {
+        Self::new(DatabaseConfig::default_for_version(version))
+    }
+
     pub fn node_get_parents(&self, node: &NodePtr) -> Vec<Uuid> {
         let mut parents = Vec::new();
         let mut parent_uuid = node.borrow().get_parent();
@@ -147,6 +427,25 @@ impl Database {
         parents
     }
 
+    /// Set the name of the program recorded as the generator of the database file
+    pub fn set_generator(&mut self, name: &str) {
+        self.meta.generator = Some(name.to_string());
+    }
+
+    /// Remember the group that was last selected in the user interface
+    pub fn set_last_selected_group(&mut self, uuid: Uuid) -> crate::Result<()> {
+        search_node_by_uuid_with_specific_type::<Group>(&self.root, uuid).ok_or("Group not found")?;
+        self.meta.last_selected_group = Some(uuid);
+        Ok(())
+    }
+
+    /// Remember the group that was last at the top of the group list in the user interface
+    pub fn set_last_top_visible_group(&mut self, uuid: Uuid) -> crate::Result<()> {
+        search_node_by_uuid_with_specific_type::<Group>(&self.root, uuid).ok_or("Group not found")?;
+        self.meta.last_top_visible_group = Some(uuid);
+        Ok(())
+    }
+
     pub fn set_recycle_bin_enabled(&mut self, enabled: bool) {
         self.meta.set_recycle_bin_enabled(enabled);
     }
@@ -175,15 +474,108 @@ impl Database {
         if !self.recycle_bin_enabled() {
             return None;
         }
+        self.configured_recycle_bin_group()
+    }
+
+    /// Get the group that `meta` names as the recycle bin, even if the recycle bin is disabled.
+    /// `KeePass` keeps the UUID when the recycle bin is turned off, so this finds a leftover bin
+    /// that could be cleaned up.
+    pub fn configured_recycle_bin_group(&self) -> Option<NodePtr> {
         let uuid = self.meta.recyclebin_uuid?;
-        group_get_children(&self.root).and_then(|children| {
-            children
-                .into_iter()
-                .find(|child| child.borrow().get_uuid() == uuid && node_is_group(child))
+        if self.root.borrow().get_uuid() == uuid {
+            return None;
+        }
+        search_node_by_uuid_with_specific_type::<Group>(&self.root, uuid)
+    }
+
+    /// Change the UUID of the node `current` to `new`, updating the `parent` of its children, the
+    /// history entries of an entry and the references to it in `meta`. Fails if the node does not
+    /// exist or `new` is already taken.
+    pub fn set_node_uuid(&mut self, current: Uuid, new: Uuid) -> crate::Result<()> {
+        if current == new {
+            return Ok(());
+        }
+        if self.search_node_by_uuid(new).is_some() {
+            return Err(format!("Node {new} already exists").into());
+        }
+        let node = self.search_node_by_uuid(current).ok_or("Node not found")?;
+        node.borrow_mut().set_uuid(new);
+        with_node_mut::<Entry, _, _>(&node, |entry| {
+            for version in entry.history.iter_mut().flat_map(|history| history.entries.iter_mut()) {
+                version.uuid = new;
+            }
+        });
+        for child in group_get_children(&node).unwrap_or_default() {
+            child.borrow_mut().set_parent(Some(new));
+        }
+
+        let meta = &mut self.meta;
+        for reference in [
+            &mut meta.recyclebin_uuid,
+            &mut meta.entry_templates_group,
+            &mut meta.last_selected_group,
+            &mut meta.last_top_visible_group,
+        ] {
+            if *reference == Some(current) {
+                *reference = Some(new);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the group holding the entry templates, see `Meta::entry_templates_group`
+    pub fn template_group(&self) -> Option<NodePtr> {
+        let uuid = self.meta.entry_templates_group?;
+        search_node_by_uuid_with_specific_type::<Group>(&self.root, uuid)
+    }
+
+    /// Create a copy of the template entry `template_uuid` at the end of the group `target_group`.
+    /// The copy gets a new UUID and fresh timestamps, and does not take over the template's history.
+    pub fn create_entry_from_template(&mut self, template_uuid: Uuid, target_group: Uuid) -> crate::Result<NodePtr> {
+        let template = search_node_by_uuid(&self.root, template_uuid).ok_or("Template entry not found")?;
+        let target = search_node_by_uuid_with_specific_type::<Group>(&self.root, target_group).ok_or("Target group not found")?;
+
+        let entry = with_node::<Entry, _, _>(&template, |template| Entry {
+            uuid: Uuid::new_v4(),
+            times: Times::new(),
+            history: None,
+            parent: None,
+            weak_self: None,
+            ..template.clone()
         })
+        .ok_or("Template is not an entry")?;
+        let entry = rc_refcell_node(entry);
+        let count = group_get_children(&target).map_or(0, |c| c.len());
+        group_add_child(&target, entry.clone(), count)?;
+        Ok(entry)
+    }
+
+    /// Designate an existing group as the recycle bin, enabling the recycle bin if it was disabled
+    pub fn set_recycle_bin_group(&mut self, uuid: Uuid) -> crate::Result<()> {
+        if self.root.borrow().get_uuid() == uuid {
+            return Err("The root group cannot be the recycle bin".into());
+        }
+        if search_node_by_uuid_with_specific_type::<Group>(&self.root, uuid).is_none() {
+            return Err(format!("Group {uuid} not found").into());
+        }
+        self.meta.recyclebin_enabled = Some(true);
+        self.meta.recyclebin_uuid = Some(uuid);
+        self.meta.set_recycle_bin_changed();
+        Ok(())
     }
 
     pub fn create_recycle_bin(&mut self) -> crate::Result<NodePtr> {
+        self.create_recycle_bin_named(DEFAULT_RECYCLE_BIN_NAME)
+    }
+
+    /// Like `create_recycle_bin`, giving the recycle bin a name other than `DEFAULT_RECYCLE_BIN_NAME`,
+    /// e.g. a localized one
+    pub fn create_recycle_bin_named(&mut self, name: &str) -> crate::Result<NodePtr> {
+        self.create_recycle_bin_with_icon(name, IconId::RECYCLE_BIN)
+    }
+
+    /// Like `create_recycle_bin_named`, with an icon other than `IconId::RECYCLE_BIN`
+    pub fn create_recycle_bin_with_icon(&mut self, name: &str, icon_id: IconId) -> crate::Result<NodePtr> {
         use crate::error::Error;
         if !self.recycle_bin_enabled() {
             return Err(Error::RecycleBinDisabled);
@@ -191,329 +583,3671 @@ impl Database {
         if self.get_recycle_bin().is_some() {
             return Err(Error::RecycleBinAlreadyExists);
         }
-        let recycle_bin = rc_refcell_node(Group::new("Recycle Bin"));
-        recycle_bin.borrow_mut().set_icon_id(Some(IconId::RECYCLE_BIN));
+        let recycle_bin = rc_refcell_node(Group::new(name));
+        recycle_bin.borrow_mut().set_icon_id(Some(icon_id));
         self.meta.recyclebin_uuid = Some(recycle_bin.borrow().get_uuid());
         let count = group_get_children(&self.root).ok_or("")?.len();
         group_add_child(&self.root, recycle_bin.clone(), count)?;
+        self.meta.set_recycle_bin_changed();
         Ok(recycle_bin)
     }
 
     pub fn remove_node_by_uuid(&mut self, uuid: Uuid) -> crate::Result<NodePtr> {
+        // Check up front so that neither `deleted_objects` nor the recycle bin get touched
+        if self.root.borrow().get_uuid() == uuid {
+            return Err(crate::Error::CannotRemoveRoot);
+        }
         if !self.recycle_bin_enabled() {
             let node = group_remove_node_by_uuid(&self.root, uuid)?;
-            self.deleted_objects.add(uuid);
+            self.record_deleted(&node);
             return Ok(node);
         }
         let node_in_recycle_bin = self.node_is_in_recycle_bin(uuid);
-        let recycle_bin = self.get_recycle_bin().ok_or("").or_else(|_| self.create_recycle_bin())?;
-        let recycle_bin_uuid = recycle_bin.borrow().get_uuid();
+        let node_is_recycle_bin = self.get_recycle_bin().is_some_and(|bin| bin.borrow().get_uuid() == uuid);
         // This can remove the recycle bin itself, or node in the recycle bin, or node not in the recycle bin
-        let node = group_remove_node_by_uuid(&self.root, uuid)?;
-        self.deleted_objects.add(uuid);
-        if uuid != recycle_bin_uuid && !node_in_recycle_bin {
+        let node = if node_is_recycle_bin || node_in_recycle_bin {
+            let node = group_remove_node_by_uuid(&self.root, uuid)?;
+            self.record_deleted(&node);
+            node
+        } else {
+            self.search_node_by_uuid(uuid).ok_or("Node not found")?;
+            // Like KeePass, only create the recycle bin once something is actually moved into it.
+            // This happens before the node is detached, so that it is not lost if it fails.
+            let recycle_bin = match self.get_recycle_bin() {
+                Some(recycle_bin) => recycle_bin,
+                None => self.create_recycle_bin()?,
+            };
+            let node = group_remove_node_by_uuid(&self.root, uuid)?;
             group_add_child(&recycle_bin, node.clone(), 0)?;
-        }
+            node
+        };
         self.meta.set_recycle_bin_changed();
         Ok(node)
     }
 
-    pub fn search_node_by_uuid(&self, uuid: Uuid) -> Option<NodePtr> {
-        search_node_by_uuid(&self.root, uuid)
+    /// Add `node` and, for a group, everything below it to `deleted_objects`, so that a merge does
+    /// not bring back the children of a deleted group
+    fn record_deleted(&mut self, node: &NodePtr) {
+        for node in NodeIterator::new(node) {
+            let uuid = node.borrow().get_uuid();
+            self.deleted_objects.add(uuid);
+        }
     }
 
-    fn create_new_node<T: Node + Default>(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
-        let new_node = rc_refcell_node(T::default());
-        let parent = search_node_by_uuid_with_specific_type::<Group>(&self.root, parent)
-            .or_else(|| Some(self.root.clone().into()))
-            .ok_or("No parent node")?;
-        with_node_mut::<Group, _, _>(&parent, |parent| {
-            parent.add_child(new_node.clone(), index);
-        });
-        Ok(new_node)
+    /// Remove all groups that contain no entries, neither directly nor in any of their subgroups,
+    /// and return how many groups were removed. The root group is never removed, and neither is
+    /// the recycle bin if `keep_recycle_bin` is set. If the recycle bin is removed, `meta` no
+    /// longer refers to it, so that a new one is created when needed.
+    pub fn remove_empty_groups(&mut self, keep_recycle_bin: bool) -> usize {
+        let keep = self
+            .get_recycle_bin()
+            .filter(|_| keep_recycle_bin)
+            .map(|bin| bin.borrow().get_uuid());
+        let mut removed = Vec::new();
+        remove_empty_subgroups(&self.root, keep, &mut removed);
+        for uuid in &removed {
+            self.deleted_objects.add(*uuid);
+        }
+        if self.meta.recyclebin_uuid.is_some_and(|bin| removed.contains(&bin)) {
+            self.meta.recyclebin_uuid = None;
+            self.meta.set_recycle_bin_changed();
+        }
+        removed.len()
     }
 
-    pub fn create_new_entry(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
-        self.create_new_node::<Entry>(parent, index)
+    /// Set the password of all entries in `uuids` to `new_password`, recording the change in the
+    /// history of each entry. Returns how many entries were changed, entries that already had
+    /// this password are left alone. Nothing is changed if any of the entries cannot be found.
+    pub fn replace_passwords(&mut self, uuids: &[Uuid], new_password: &str) -> crate::Result<usize> {
+        let mut entries = Vec::with_capacity(uuids.len());
+        let mut missing = Vec::new();
+        for &uuid in uuids {
+            match search_node_by_uuid_with_specific_type::<Entry>(&self.root, uuid) {
+                Some(entry) => entries.push(entry),
+                None => missing.push(uuid.to_string()),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(format!("Entries not found: {}", missing.join(", ")).into());
+        }
+
+        let changed = entries
+            .iter()
+            .filter(|entry| {
+                with_node_mut::<Entry, _, _>(entry, |entry| {
+                    entry.set_password(Some(new_password));
+                    entry.update_history()
+                })
+                .unwrap_or(false)
+            })
+            .count();
+        Ok(changed)
     }
 
-    pub fn create_new_group(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
-        self.create_new_node::<Group>(parent, index)
+    /// Rename the field `old_name` to `new_name` on every entry that has it, keeping its value and
+    /// recording the change in the history of each entry. Entries that already have a `new_name`
+    /// field are left alone unless `force` is set, in which case that field is overwritten. Fails
+    /// without changing anything if `new_name` is empty or cannot be saved, see
+    /// `Entry::validate_field_name`, or if `force` would overwrite one of `STANDARD_FIELD_NAMES`.
+    /// Returns how many entries were changed.
+    pub fn rename_field(&mut self, old_name: &str, new_name: &str, force: bool) -> crate::Result<usize> {
+        if new_name.is_empty() {
+            return Err("The new field name is empty".into());
+        }
+        Entry::validate_field_name(new_name)?;
+        if force && STANDARD_FIELD_NAMES.contains(&new_name) {
+            return Err(format!("The standard field {new_name} cannot be overwritten").into());
+        }
+        if old_name == new_name {
+            return Ok(0);
+        }
+        let renamed = NodeIterator::new(&self.root)
+            .filter(|node| {
+                with_node_mut::<Entry, _, _>(node, |entry| {
+                    if !entry.fields.contains_key(old_name) || (!force && entry.fields.contains_key(new_name)) {
+                        return false;
+                    }
+                    if let Some(value) = entry.fields.remove(old_name) {
+                        entry.fields.insert(new_name.to_string(), value);
+                    }
+                    entry.update_history();
+                    true
+                })
+                .unwrap_or(false)
+            })
+            .count();
+        Ok(renamed)
     }
-}
 
-/// Timestamps for a Group or Entry
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct Times {
-    /// Does this node expire
-    pub(crate) expires: bool,
+    /// Remove all custom fields from every entry, see `Entry::clear_custom_fields`, committing the
+    /// history of the changed entries. The fields remain in the earlier versions in the history.
+    /// Returns how many entries were changed.
+    pub fn strip_custom_fields(&mut self) -> usize {
+        NodeIterator::new(&self.root)
+            .filter(|node| {
+                with_node_mut::<Entry, _, _>(node, |entry| {
+                    if entry.clear_custom_fields() == 0 {
+                        return false;
+                    }
+                    entry.update_history();
+                    true
+                })
+                .unwrap_or(false)
+            })
+            .count()
+    }
 
-    /// Number of usages
-    pub(crate) usage_count: usize,
+    /// Add `tag` to the entries with the given UUIDs that do not carry it yet, committing their
+    /// history. Returns how many entries were changed.
+    pub fn add_tag_to(&mut self, uuids: &[Uuid], tag: &str) -> usize {
+        self.modify_tags(uuids, |tags| {
+            if tags.iter().any(|t| t == tag) {
+                return false;
+            }
+            tags.push(tag.to_string());
+            true
+        })
+    }
 
-    /// Using chrono::NaiveDateTime which does not include timezone
-    /// or UTC offset because KeePass clients typically store timestamps
-    /// relative to the local time on the machine writing the data without
-    /// including accurate UTC offset or timezone information.
-    pub(crate) times: HashMap<String, NaiveDateTime>,
-}
+    /// Remove `tag` from the entries with the given UUIDs, committing their history. Returns how
+    /// many entries were changed.
+    pub fn remove_tag_from(&mut self, uuids: &[Uuid], tag: &str) -> usize {
+        self.modify_tags(uuids, |tags| {
+            let count = tags.len();
+            tags.retain(|t| t != tag);
+            tags.len() != count
+        })
+    }
 
-pub const EXPIRY_TIME_TAG_NAME: &str = "ExpiryTime";
-pub const LAST_MODIFICATION_TIME_TAG_NAME: &str = "LastModificationTime";
-pub const CREATION_TIME_TAG_NAME: &str = "CreationTime";
-pub const LAST_ACCESS_TIME_TAG_NAME: &str = "LastAccessTime";
-pub const LOCATION_CHANGED_TAG_NAME: &str = "LocationChanged";
+    fn modify_tags(&mut self, uuids: &[Uuid], mut modify: impl FnMut(&mut Vec<String>) -> bool) -> usize {
+        NodeIterator::new(&self.root)
+            .filter(|node| uuids.contains(&node.borrow().get_uuid()))
+            .filter(|node| {
+                with_node_mut::<Entry, _, _>(node, |entry| {
+                    if !modify(&mut entry.tags) {
+                        return false;
+                    }
+                    entry.update_history();
+                    true
+                })
+                .unwrap_or(false)
+            })
+            .count()
+    }
 
-impl Times {
-    fn get(&self, key: &str) -> Option<NaiveDateTime> {
-        self.times.get(key).copied()
+    /// Trim the history of every entry to `meta.history_max_items` and `meta.history_max_size`,
+    /// dropping the oldest history entries first. Returns how many history entries were removed.
+    pub fn enforce_history_limits(&mut self) -> usize {
+        let (max_items, max_size) = (self.meta.history_max_items, self.meta.history_max_size);
+        NodeIterator::new(&self.root)
+            .filter_map(|node| with_node_mut::<Entry, _, _>(&node, |entry| entry.truncate_history(max_items, max_size)))
+            .sum()
     }
 
-    fn set(&mut self, key: &str, time: Option<NaiveDateTime>) {
-        if let Some(time) = time {
-            self.times.insert(key.to_string(), time);
-        } else {
-            self.times.remove(key);
+    /// Expand or collapse all groups in the user interface, including the root group
+    pub fn set_all_groups_expanded(&mut self, expanded: bool) {
+        for node in NodeIterator::new(&self.root) {
+            with_node_mut::<Group, _, _>(&node, |group| group.set_expanded(expanded));
         }
     }
 
-    pub fn get_expires(&self) -> bool {
-        self.expires
+    /// Floor the timestamps of all groups and entries, including entry history, to whole seconds
+    /// so that they compare equal to what is read back after saving the database
+    pub fn truncate_subsecond_times(&mut self) {
+        for node in NodeIterator::new(&self.root) {
+            node.borrow_mut().get_times_mut().truncate_subsecond();
+            with_node_mut::<Entry, _, _>(&node, |entry| {
+                for old in entry.history.iter_mut().flat_map(|h| h.entries.iter_mut()) {
+                    old.times.truncate_subsecond();
+                }
+            });
+        }
     }
 
-    pub fn set_expires(&mut self, expires: bool) {
-        self.expires = expires;
+    /// Get the most recent last modification time of the group `group_uuid` and everything in it,
+    /// e.g. to sort groups by recent activity. Returns `None` if there is no such group.
+    pub fn group_latest_change(&self, group_uuid: Uuid) -> Option<NaiveDateTime> {
+        let group = search_node_by_uuid_with_specific_type::<Group>(&self.root, group_uuid)?;
+        NodeIterator::new(&group)
+            .filter_map(|node| node.borrow().get_times().get_last_modification())
+            .max()
     }
 
-    pub fn get_usage_count(&self) -> usize {
-        self.usage_count
+    /// Get all entries outside of the recycle bin whose field `field` is missing or empty,
+    /// e.g. entries without a password.
+    pub fn entries_missing_field(&self, field: &str) -> Vec<NodePtr> {
+        self.entries_outside_recycle_bin()
+            .into_iter()
+            .filter(|node| with_node::<Entry, _, _>(node, |e| e.fields.get(field).is_none_or(Value::is_empty)).unwrap_or(false))
+            .collect()
     }
 
-    pub fn set_usage_count(&mut self, usage_count: usize) {
-        self.usage_count = usage_count;
+    /// Get all entries outside of the recycle bin that are set to expire at or before `now`
+    pub fn expired_entries(&self, now: NaiveDateTime) -> Vec<NodePtr> {
+        self.entries_outside_recycle_bin()
+            .into_iter()
+            .filter(|node| {
+                let times = node.borrow().get_times().clone();
+                times.get_expires() && times.get_expiry_time().is_some_and(|expiry| expiry <= now)
+            })
+            .collect()
     }
 
-    /// Convenience method for getting the time that the entry expires.
-    /// This value is usually only meaningful/useful when expires == true
-    pub fn get_expiry_time(&self) -> Option<NaiveDateTime> {
-        self.get(EXPIRY_TIME_TAG_NAME)
+    /// Get all entries outside of the recycle bin whose password is weaker than `min_bits`, see
+    /// `Entry::password_strength_bits`. Entries without a password or with the quality check
+    /// disabled are skipped.
+    pub fn weak_passwords(&self, min_bits: f64) -> Vec<NodePtr> {
+        self.entries_outside_recycle_bin()
+            .into_iter()
+            .filter(|node| {
+                with_node::<Entry, _, _>(node, |e| {
+                    e.quality_check_enabled() && e.password_strength_bits().is_some_and(|bits| bits < min_bits)
+                })
+                .unwrap_or(false)
+            })
+            .collect()
     }
 
-    pub fn set_expiry_time(&mut self, time: Option<NaiveDateTime>) {
-        self.set(EXPIRY_TIME_TAG_NAME, time);
+    /// Get the entries outside of the recycle bin that share a password, grouped by password.
+    /// Empty passwords are not considered reused. The groups are in the order of their first
+    /// entry in the database.
+    pub fn reused_passwords(&self) -> Vec<Vec<NodePtr>> {
+        let mut by_password: Vec<(SecStr, Vec<NodePtr>)> = Vec::new();
+        for node in self.entries_outside_recycle_bin() {
+            let Some(password) = with_node::<Entry, _, _>(&node, Entry::password_secstr).flatten() else {
+                continue;
+            };
+            if password.unsecure().is_empty() {
+                continue;
+            }
+            match by_password.iter_mut().find(|(p, _)| *p == password) {
+                Some((_, nodes)) => nodes.push(node),
+                None => by_password.push((password, vec![node])),
+            }
+        }
+        by_password
+            .into_iter()
+            .map(|(_, nodes)| nodes)
+            .filter(|nodes| nodes.len() > 1)
+            .collect()
     }
 
-    pub fn get_last_modification(&self) -> Option<NaiveDateTime> {
-        self.get(LAST_MODIFICATION_TIME_TAG_NAME)
+    /// Combine the weak, reused and expired passwords of the database into one report, with
+    /// `AUDIT_WEAK_PASSWORD_BITS` as the threshold for weak passwords
+    pub fn audit_report(&self) -> AuditReport {
+        AuditReport {
+            entries_checked: self.entries_outside_recycle_bin().len(),
+            weak: node_uuids(self.weak_passwords(AUDIT_WEAK_PASSWORD_BITS)),
+            reused: self.reused_passwords().into_iter().map(node_uuids).collect(),
+            expired: node_uuids(self.expired_entries(Times::now())),
+        }
     }
 
-    pub fn set_last_modification(&mut self, time: Option<NaiveDateTime>) {
-        self.set(LAST_MODIFICATION_TIME_TAG_NAME, time);
+    /// Get all entries last modified after `since`, e.g. to only push the entries changed since the
+    /// last synchronization to another system
+    pub fn entries_modified_since(&self, since: NaiveDateTime) -> Vec<NodePtr> {
+        NodeIterator::new(&self.root)
+            .filter(node_is_entry)
+            .filter(|node| node.borrow().get_times().get_last_modification().is_some_and(|time| time > since))
+            .collect()
     }
 
-    pub fn get_creation(&self) -> Option<NaiveDateTime> {
-        self.get(CREATION_TIME_TAG_NAME)
+    /// Export the entries modified after `since` as JSON, together with the UUIDs of the objects
+    /// deleted after it:
+    /// `{"since": "...", "entries": [...], "deleted": ["..."]}`
+    #[cfg(feature = "serialization")]
+    pub fn export_modified_since_json(&self, since: NaiveDateTime) -> serde_json::Result<String> {
+        let delta = EntriesDelta {
+            since,
+            entries: self
+                .entries_modified_since(since)
+                .into_iter()
+                .map(SerializableNodePtr::from)
+                .collect(),
+            deleted: self
+                .deleted_objects
+                .objects
+                .iter()
+                .filter(|object| object.deletion_time > since)
+                .map(|object| object.uuid)
+                .collect(),
+        };
+        serde_json::to_string(&delta)
     }
 
-    pub fn set_creation(&mut self, time: Option<NaiveDateTime>) {
-        self.set(CREATION_TIME_TAG_NAME, time);
+    fn entries_outside_recycle_bin(&self) -> Vec<NodePtr> {
+        let recycled: HashSet<Uuid> = self
+            .get_recycle_bin()
+            .map(|bin| NodeIterator::new(&bin).map(|node| node.borrow().get_uuid()).collect())
+            .unwrap_or_default();
+        NodeIterator::new(&self.root)
+            .filter(node_is_entry)
+            .filter(|node| !recycled.contains(&node.borrow().get_uuid()))
+            .collect()
     }
 
-    pub fn get_last_access(&self) -> Option<NaiveDateTime> {
-        self.get(LAST_ACCESS_TIME_TAG_NAME)
+    pub fn search_node_by_uuid(&self, uuid: Uuid) -> Option<NodePtr> {
+        search_node_by_uuid(&self.root, uuid)
     }
 
-    pub fn set_last_access(&mut self, time: Option<NaiveDateTime>) {
-        self.set(LAST_ACCESS_TIME_TAG_NAME, time);
+    /// Get up to `limit` entries outside of the recycle bin, most recently accessed first.
+    /// Entries without a last access time come last.
+    pub fn recently_accessed(&self, limit: usize) -> Vec<NodePtr> {
+        let mut entries = self.entries_outside_recycle_bin();
+        entries.sort_by_key(|node| std::cmp::Reverse(node.borrow().get_times().get_last_access()));
+        entries.truncate(limit);
+        entries
     }
 
-    pub fn get_location_changed(&self) -> Option<NaiveDateTime> {
-        self.get(LOCATION_CHANGED_TAG_NAME)
+    /// Get the content of an attachment referenced by an entry, or `None` if the database does
+    /// not have it
+    ///
+    /// KDBX3 databases keep their attachments in `meta.binaries`, referenced by ID, while KDBX4
+    /// stores them in the inner header, referenced by position. Binaries without an identifier can
+    /// therefore only be resolved in KDBX4 databases. Fails if the content of the binary cannot be
+    /// decompressed, see `BinaryAttachment::content_decompressed`.
+    pub fn resolve_attachment(&self, attachment: &AttachmentRef) -> Result<Option<Cow<'_, [u8]>>, std::io::Error> {
+        self.attachment_content(&attachment.identifier)
     }
 
-    pub fn set_location_changed(&mut self, time: Option<NaiveDateTime>) {
-        self.set(LOCATION_CHANGED_TAG_NAME, time);
+    fn attachment_content(&self, identifier: &str) -> Result<Option<Cow<'_, [u8]>>, std::io::Error> {
+        match self.config.version {
+            DatabaseVersion::KDB4(_) => {
+                let index = identifier.parse::<usize>().ok();
+                Ok(index
+                    .and_then(|index| self.header_attachments.get(index))
+                    .map(|a| Cow::Borrowed(a.content.as_slice())))
+            }
+            _ => self
+                .meta
+                .binaries
+                .binaries
+                .iter()
+                .find(|b| b.identifier.as_deref() == Some(identifier))
+                .map(BinaryAttachment::content_decompressed)
+                .transpose(),
+        }
     }
 
-    // Returns the current time, without the nanoseconds since
-    // the last leap second.
-    pub fn now() -> NaiveDateTime {
-        let now = chrono::Utc::now().timestamp();
-        chrono::DateTime::from_timestamp(now, 0).unwrap().naive_utc()
+    /// Point the attachment references of the entries in `root`, a copy of the tree of `other`, to
+    /// the attachments of this database, adding the attachments of `other` that are not here yet
+    fn import_attachments(&mut self, other: &Database, root: &NodePtr) -> crate::Result<()> {
+        let mut imported: HashMap<String, Option<String>> = HashMap::new();
+        for node in NodeIterator::new(root) {
+            with_node_mut::<Entry, _, _>(&node, |entry| {
+                let history = entry.history.iter_mut().flat_map(|h| h.entries.iter_mut());
+                for attachment in history.flat_map(|e| e.attachments.iter_mut()).chain(entry.attachments.iter_mut()) {
+                    let identifier = match imported.get(&attachment.identifier) {
+                        Some(identifier) => identifier.clone(),
+                        None => {
+                            let identifier = self.import_attachment(other, &attachment.identifier)?;
+                            imported.insert(attachment.identifier.clone(), identifier.clone());
+                            identifier
+                        }
+                    };
+                    // a reference that `other` cannot resolve is left as it is
+                    if let Some(identifier) = identifier {
+                        attachment.identifier = identifier;
+                    }
+                }
+                Ok::<_, crate::Error>(())
+            })
+            .transpose()?;
+        }
+        Ok(())
     }
 
-    pub fn epoch() -> NaiveDateTime {
-        chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc()
+    /// Add the attachment `identifier` of `other` to the attachments of this database, unless an
+    /// identical one is already there, and return its identifier in this database. Returns `None`
+    /// if `other` does not have the attachment.
+    fn import_attachment(&mut self, other: &Database, identifier: &str) -> crate::Result<Option<String>> {
+        let Some(content) = other.attachment_content(identifier)? else {
+            return Ok(None);
+        };
+        if let DatabaseVersion::KDB4(_) = self.config.version {
+            // keep the memory protection flag of attachments that come from an inner header as well
+            let flags = match other.config.version {
+                DatabaseVersion::KDB4(_) => identifier
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| other.header_attachments.get(index))
+                    .map_or(0, |a| a.flags),
+                _ => 0,
+            };
+            let attachment = HeaderAttachment {
+                flags,
+                content: content.into_owned(),
+            };
+            let index = match self.header_attachments.iter().position(|a| *a == attachment) {
+                Some(index) => index,
+                None => {
+                    self.header_attachments.push(attachment);
+                    self.header_attachments.len() - 1
+                }
+            };
+            return Ok(Some(index.to_string()));
+        }
+
+        let binaries = &mut self.meta.binaries.binaries;
+        let existing = binaries
+            .iter()
+            .find(|b| b.identifier.is_some() && b.content_decompressed().is_ok_and(|c| c == content));
+        if let Some(existing) = existing {
+            return Ok(existing.identifier.clone());
+        }
+        let identifier = binaries
+            .iter()
+            .filter_map(|b| b.identifier.as_deref()?.parse::<usize>().ok())
+            .max()
+            .map_or(0, |max| max + 1)
+            .to_string();
+        binaries.push(BinaryAttachment {
+            identifier: Some(identifier.clone()),
+            compressed: false,
+            content: content.into_owned(),
+        });
+        Ok(Some(identifier))
     }
 
-    pub fn new() -> Times {
-        let mut response = Times::default();
-        let now = Some(Times::now());
-        response.set_creation(now);
-        response.set_last_modification(now);
-        response.set_last_access(now);
-        response.set_location_changed(now);
-        response.set_expiry_time(now);
-        response.set_expires(false);
-        response
+    /// Merge `other` into this database with `Group::merge`, taking the deleted objects of both
+    /// databases into account: entries deleted in this database are not copied back from `other`,
+    /// and nodes deleted in `other` are removed here, unless they were modified after they were
+    /// deleted. The deleted objects of `other` are added to the ones of this database. Attachments
+    /// of `other` that the merged entries refer to are added to the attachments of this database.
+    /// The nodes removed here are logged as `EntryDeleted` or `GroupDeleted`.
+    pub fn merge(&mut self, other: &Database) -> crate::Result<MergeLog> {
+        let theirs = other.root.borrow().duplicate();
+        self.import_attachments(other, &theirs)?;
+        remove_deleted_nodes(&theirs, &self.deleted_objects)?;
+        let mut log = Group::merge(&self.root, &theirs)?;
+        log.events.extend(remove_deleted_nodes(&self.root, &other.deleted_objects)?);
+        self.deleted_objects.merge(&other.deleted_objects);
+        Ok(log)
     }
-}
 
-/// Collection of custom data fields for an entry or metadata
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct CustomData {
-    pub items: HashMap<String, CustomDataItem>,
-}
+    /// Merge only the group at the title `path` of `other` into the group at the same path of this
+    /// database, see `Group::merge`. Fails if either database has no group at `path`.
+    pub fn merge_group_by_path(&mut self, other: &Database, path: &[&str]) -> crate::Result<MergeLog> {
+        let find_group = |db: &Database| {
+            with_node::<Group, _, _>(&db.root, |root| root.get_typed::<Group>(path).ok())
+                .flatten()
+                .ok_or_else(|| format!("Group {path:?} not found"))
+        };
+        let destination = find_group(self)?;
+        let source = find_group(other)?;
+        Group::merge(&destination, &source)
+    }
 
-/// Custom data field for an entry or metadata for internal use
+    /// Read the database again from `source`, e.g. after the file was changed by a sync, and merge
+    /// the changes into this database with `Database::merge` instead of replacing it. Entries are
+    /// updated in place, so `NodePtr`s held by the application stay valid, except for entries that
+    /// were moved to another group or deleted. The metadata is taken from the file, apart from the
+    /// attachments in `meta.binaries`, which are merged.
+    pub fn reload(&mut self, source: &mut dyn std::io::Read, key: DatabaseKey) -> crate::Result<MergeLog> {
+        let other = Database::open(source, key)?;
+        let log = self.merge(&other)?;
+        let binaries = std::mem::take(&mut self.meta.binaries);
+        self.meta = Meta { binaries, ..other.meta };
+        // saving with `DumpOptions::reuse_seeds` compares against the file as it is now
+        #[cfg(feature = "save_kdbx4")]
+        {
+            self.original_header = other.original_header;
+        }
+        Ok(log)
+    }
+
+    /// Find out what `merge` with `other` would do, by merging into a copy of this database.
+    /// Besides the would-be merge log, this lists the entries that were edited on both sides
+    /// since their last common version, whose changes on one side will be moved to the history
+    /// when merging.
+    pub fn merge_preview(&self, other: &Database) -> crate::Result<MergePreview> {
+        let mut copy = Database {
+            root: self.root.borrow().duplicate().into(),
+            ..self.clone()
+        };
+        let root: NodePtr = copy.root.clone().into();
+
+        let conflicts = NodeIterator::new(&other.root)
+            .filter_map(|theirs| {
+                let uuid = theirs.borrow().get_uuid();
+                let ours = search_node_by_uuid_with_specific_type::<Entry>(&root, uuid)?;
+                with_node::<Entry, _, _>(&ours, |ours| {
+                    with_node::<Entry, _, _>(&theirs, |theirs| entries_diverged(ours, theirs))
+                })
+                .flatten()
+                .filter(|&diverged| diverged)
+                .map(|_| uuid)
+            })
+            .collect();
+
+        let log = copy.merge(other)?;
+        Ok(MergePreview { log, conflicts })
+    }
+
+    /// Get all entries carrying any or all of the given tags, depending on `mode`
+    pub fn entries_matching_tags(&self, tags: &[&str], mode: TagMatch) -> Vec<NodePtr> {
+        NodeIterator::new(&self.root)
+            .filter(|node| {
+                with_node::<Entry, _, _>(node, |entry| {
+                    let has_tag = |tag: &&str| entry.get_tags().iter().any(|t| t == tag);
+                    match mode {
+                        TagMatch::Any => tags.iter().any(has_tag),
+                        TagMatch::All => tags.iter().all(has_tag),
+                    }
+                })
+                .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Get all entries that reference the attachment at `index` of `header_attachments`, either
+    /// themselves or in one of their history entries
+    pub fn entries_using_attachment(&self, index: usize) -> Vec<NodePtr> {
+        let identifier = index.to_string();
+        NodeIterator::new(&self.root)
+            .filter(|node| {
+                with_node::<Entry, _, _>(node, |entry| {
+                    let history = entry.history.iter().flat_map(|h| h.entries.iter());
+                    std::iter::once(entry)
+                        .chain(history)
+                        .any(|e| e.attachments.iter().any(|a| a.identifier == identifier))
+                })
+                .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Remove the entries of `header_attachments` that no entry references, neither itself nor in
+    /// its history, and update the references to the remaining attachments to their new position.
+    /// Returns how many attachments were removed.
+    pub fn prune_unused_attachments(&mut self) -> usize {
+        let mut used = vec![false; self.header_attachments.len()];
+        for node in NodeIterator::new(&self.root) {
+            with_node::<Entry, _, _>(&node, |entry| {
+                let history = entry.history.iter().flat_map(|h| h.entries.iter());
+                for attachment in std::iter::once(entry).chain(history).flat_map(|e| e.attachments.iter()) {
+                    if let Some(used) = attachment.identifier.parse::<usize>().ok().and_then(|i| used.get_mut(i)) {
+                        *used = true;
+                    }
+                }
+            });
+        }
+
+        let mut new_indices = Vec::with_capacity(used.len());
+        let mut next = 0;
+        for &is_used in &used {
+            new_indices.push(next);
+            next += usize::from(is_used);
+        }
+        for node in NodeIterator::new(&self.root) {
+            with_node_mut::<Entry, _, _>(&node, |entry| {
+                let history = entry.history.iter_mut().flat_map(|h| h.entries.iter_mut());
+                for attachment in history.flat_map(|e| e.attachments.iter_mut()).chain(entry.attachments.iter_mut()) {
+                    if let Some(index) = attachment.identifier.parse::<usize>().ok().and_then(|i| new_indices.get(i)) {
+                        attachment.identifier = index.to_string();
+                    }
+                }
+            });
+        }
+
+        let removed = used.iter().filter(|&&is_used| !is_used).count();
+        let mut used = used.into_iter();
+        self.header_attachments.retain(|_| used.next().unwrap_or(true));
+        removed
+    }
+
+    /// Iterate over all groups in the database, including the root group
+    pub fn groups_iter(&self) -> impl Iterator<Item = NodePtr> {
+        NodeIterator::new(&self.root).filter(node_is_group)
+    }
+
+    /// Check that the `parent` of every node is the group that actually contains it, which can break
+    /// after misusing `set_parent`. Returns the UUIDs of the nodes with a wrong parent, including the
+    /// root group if it has a parent at all.
+    pub fn orphan_check(&self) -> Vec<Uuid> {
+        let mut inconsistent = Vec::new();
+        if self.root.borrow().get_parent().is_some() {
+            inconsistent.push(self.root.borrow().get_uuid());
+        }
+        for group in self.groups_iter() {
+            let group_uuid = group.borrow().get_uuid();
+            for child in group_get_children(&group).unwrap_or_default() {
+                let child = child.borrow();
+                if child.get_parent() != Some(group_uuid) {
+                    inconsistent.push(child.get_uuid());
+                }
+            }
+        }
+        inconsistent
+    }
+
+    /// Resolve the auto-type sequence of the entry `entry` for a window title, or `None` if auto-type
+    /// is disabled for the entry, see `is_autotype_enabled`, or does not apply to the window, see
+    /// `AutoType::applies_to_window`. An entry without auto-type settings applies to any window.
+    /// The sequence is the one of the matching association, falling back to the default sequence
+    /// of the entry, then to the default sequence of the closest group above it and finally to
+    /// `DEFAULT_AUTOTYPE_SEQUENCE`.
+    pub fn autotype_sequence_for_window(&self, entry: &NodePtr, window_title: &str) -> Option<String> {
+        let autotype = with_node::<Entry, _, _>(entry, |e| e.autotype.clone())?.unwrap_or_else(|| AutoType {
+            enabled: true,
+            ..Default::default()
+        });
+        if !self.is_autotype_enabled(entry) || !autotype.applies_to_window(window_title) {
+            return None;
+        }
+        let non_empty = |sequence: &Option<String>| sequence.clone().filter(|s| !s.is_empty());
+        if let Some(sequence) = autotype
+            .association_for_window(window_title)
+            .and_then(|a| non_empty(&a.sequence))
+            .or_else(|| non_empty(&autotype.sequence))
+        {
+            return Some(sequence);
+        }
+
+        let mut parent = entry.borrow().get_parent();
+        while let Some(group_uuid) = parent {
+            let Some(group) = search_node_by_uuid_with_specific_type::<Group>(&self.root, group_uuid) else {
+                break;
+            };
+            if let Some(sequence) = with_node::<Group, _, _>(&group, |g| non_empty(&g.default_autotype_sequence)).flatten() {
+                return Some(sequence);
+            }
+            parent = group.borrow().get_parent();
+        }
+
+        Some(DEFAULT_AUTOTYPE_SEQUENCE.to_string())
+    }
+
+    /// Whether searching includes a node. Groups that do not set whether searching is enabled
+    /// inherit the setting from their parent, up to the root group. Searching is enabled if no
+    /// group on the way sets it.
+    pub fn is_searching_enabled(&self, node: &NodePtr) -> bool {
+        self.inherited_group_flag(node, Group::searching_enabled)
+    }
+
+    /// Whether auto-type applies to a node, inheriting the setting of the groups above it like
+    /// `is_searching_enabled`. For an entry, its own auto-type settings have to be enabled as well.
+    pub fn is_autotype_enabled(&self, node: &NodePtr) -> bool {
+        let entry_enabled = with_node::<Entry, _, _>(node, |entry| entry.autotype.as_ref().is_none_or(|autotype| autotype.enabled));
+        entry_enabled.unwrap_or(true) && self.inherited_group_flag(node, Group::autotype_enabled)
+    }
+
+    fn inherited_group_flag(&self, node: &NodePtr, flag: fn(&Group) -> Option<bool>) -> bool {
+        let mut current = if node_is_group(node) {
+            Some(node.borrow().get_uuid())
+        } else {
+            node.borrow().get_parent()
+        };
+        while let Some(group_uuid) = current {
+            let Some(group) = search_node_by_uuid_with_specific_type::<Group>(&self.root, group_uuid) else {
+                break;
+            };
+            if let Some(enabled) = with_node::<Group, _, _>(&group, flag).flatten() {
+                return enabled;
+            }
+            current = group.borrow().get_parent();
+        }
+        true
+    }
+
+    /// Get all groups that explicitly disable searching, e.g. to list them in the settings
+    pub fn searching_disabled_groups(&self) -> Vec<NodePtr> {
+        NodeIterator::new(&self.root)
+            .filter(|node| with_node::<Group, _, _>(node, |group| group.searching_enabled() == Some(false)).unwrap_or(false))
+            .collect()
+    }
+
+    /// Resolve the icon to show for a node: its custom icon if the metadata has it, else its standard
+    /// icon, else the icon of the closest group above it that has one. Without any icon up to the root,
+    /// the default key or folder icon is used.
+    pub fn effective_icon(&self, node: &NodePtr) -> EffectiveIcon {
+        if let Some(icon) = self.own_icon(node) {
+            return icon;
+        }
+
+        let mut parent = node.borrow().get_parent();
+        while let Some(group_uuid) = parent {
+            let Some(group) = search_node_by_uuid_with_specific_type::<Group>(&self.root, group_uuid) else {
+                break;
+            };
+            if let Some(icon) = self.own_icon(&group) {
+                return EffectiveIcon::Inherited {
+                    group: group_uuid,
+                    icon: Box::new(icon),
+                };
+            }
+            parent = group.borrow().get_parent();
+        }
+
+        EffectiveIcon::Standard(if node_is_group(node) { IconId::FOLDER } else { IconId::KEY })
+    }
+
+    fn own_icon(&self, node: &NodePtr) -> Option<EffectiveIcon> {
+        let node = node.borrow();
+        node.get_custom_icon_uuid()
+            .and_then(|uuid| self.meta.custom_icons.icons.iter().find(|icon| icon.uuid == uuid))
+            .map(|icon| EffectiveIcon::Custom(icon.data.clone()))
+            .or_else(|| node.get_icon_id().map(EffectiveIcon::Standard))
+    }
+
+    /// Get the value of the field `field_name` of an entry with its field references resolved, see
+    /// `resolve_references`. Returns `None` if the entry does not have the field.
+    pub fn resolve_field(&self, entry: &NodePtr, field_name: &str) -> Option<String> {
+        let value = with_node::<Entry, _, _>(entry, |entry| entry.get(field_name).map(str::to_string)).flatten()?;
+        Some(self.resolve_references(&value))
+    }
+
+    /// Replace the field references in `text` with the fields they point to. A reference is written
+    /// `{REF:<wanted>@<search>:<text>}` like in `KeePass`, e.g. `{REF:P@T:Bank}` for the password of
+    /// the first entry whose title contains `Bank`. The fields are given by their codes `T` (title),
+    /// `U` (user name), `P` (password), `A` (URL), `N` (notes) and `I` (UUID), and entries can also be
+    /// searched by the text of their other fields with `O`. Like in `KeePass`, the search ignores
+    /// case, and UUIDs have to match as a whole. References that do not resolve are left unchanged.
+    pub fn resolve_references(&self, text: &str) -> String {
+        self.resolve_references_at_depth(text, 0)
+    }
+
+    fn resolve_references_at_depth(&self, text: &str, depth: usize) -> String {
+        let mut resolved = String::new();
+        let mut rest = text;
+        // ASCII case conversion keeps byte offsets, so the position applies to `rest` as well
+        while let Some(start) = rest.to_ascii_uppercase().find("{REF:") {
+            resolved.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let reference = &rest[..=end];
+            match self.resolve_reference(&reference[5..end], depth) {
+                Some(value) => resolved.push_str(&value),
+                None => resolved.push_str(reference),
+            }
+            rest = &rest[end + 1..];
+        }
+        resolved.push_str(rest);
+        resolved
+    }
+
+    fn resolve_reference(&self, reference: &str, depth: usize) -> Option<String> {
+        let mut chars = reference.chars();
+        let (wanted, separator, search) = (chars.next()?, chars.next()?, chars.next()?);
+        if separator != '@' {
+            return None;
+        }
+        let search_text = chars.as_str().strip_prefix(':').filter(|text| !text.is_empty())?;
+        let (wanted, search) = (wanted.to_ascii_uppercase(), search.to_ascii_uppercase());
+
+        let target = NodeIterator::new(&self.root)
+            .filter(node_is_entry)
+            .find(|node| with_node::<Entry, _, _>(node, |entry| reference_matches(entry, search, search_text)).unwrap_or(false))?;
+        let value = with_node::<Entry, _, _>(&target, |entry| match wanted {
+            'I' => Some(entry.uuid.simple().to_string().to_uppercase()),
+            _ => reference_field_name(wanted).map(|name| entry.get(name).unwrap_or_default().to_string()),
+        })
+        .flatten()?;
+
+        // the referenced field can contain references itself, stop following them at some depth
+        // in case they form a cycle
+        if depth < MAX_REFERENCE_DEPTH {
+            Some(self.resolve_references_at_depth(&value, depth + 1))
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Get a group or entry by its title path as a single string, e.g. `General/Sample Entry`, see
+    /// `Group::get`. Titles containing a slash are written with a backslash, e.g. `Work/A\/B` for
+    /// the entry `A/B` in the group `Work`, and `\\` stands for a backslash. A leading slash is
+    /// ignored.
+    pub fn get_by_path_str(&self, path: &str) -> Option<NodePtr> {
+        let path = split_path_str(path.strip_prefix('/').unwrap_or(path));
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        with_node::<Group, _, _>(&self.root, |root| root.get(&path)).flatten()
+    }
+
+    /// Find fields that look like they were mangled by a wrong character encoding, e.g. when
+    /// importing. Returns the UUID of the entry and the name of the field for each field that
+    /// contains the Unicode replacement character or looks like UTF-8 that was decoded as Latin-1
+    /// (or Windows-1252) and encoded again, like `cafÃ©` for `café`. This is a heuristic.
+    pub fn find_encoding_anomalies(&self) -> Vec<(Uuid, String)> {
+        let mut anomalies = Vec::new();
+        for node in NodeIterator::new(&self.root) {
+            with_node::<Entry, _, _>(&node, |entry| {
+                let mut names: Vec<&String> = entry
+                    .fields
+                    .keys()
+                    .filter(|name| {
+                        entry
+                            .get(name)
+                            .is_some_and(|value| value.contains(char::REPLACEMENT_CHARACTER) || undo_double_encoding(value).is_some())
+                    })
+                    .collect();
+                names.sort();
+                anomalies.extend(names.into_iter().map(|name| (entry.uuid, name.clone())));
+            });
+        }
+        anomalies
+    }
+
+    /// Repair the given fields, e.g. the ones from `find_encoding_anomalies` that the user
+    /// confirmed, by decoding them as UTF-8 once more. Text like `Ã©` can also be meant literally,
+    /// so only the fields passed in are touched. Fields with replacement characters cannot be
+    /// repaired and are left alone. The repair is recorded in the history of each entry. Returns
+    /// the number of fields that were repaired.
+    pub fn repair_encoding(&mut self, fields: &[(Uuid, String)]) -> usize {
+        NodeIterator::new(&self.root)
+            .filter_map(|node| {
+                with_node_mut::<Entry, _, _>(&node, |entry| {
+                    let mut repaired = 0;
+                    let uuid = entry.uuid;
+                    let values = entry
+                        .fields
+                        .iter_mut()
+                        .filter(|(name, _)| fields.iter().any(|field| field.0 == uuid && field.1 == **name))
+                        .map(|(_, value)| value);
+                    for value in values {
+                        match value {
+                            Value::Unprotected(s) => {
+                                if let Some(fixed) = undo_double_encoding(s) {
+                                    *s = fixed;
+                                    repaired += 1;
+                                }
+                            }
+                            Value::Protected(s) => {
+                                let fixed = std::str::from_utf8(s.unsecure()).ok().and_then(undo_double_encoding);
+                                if let Some(fixed) = fixed {
+                                    *s = SecStr::from(fixed);
+                                    repaired += 1;
+                                }
+                            }
+                            Value::Bytes(_) => {}
+                        }
+                    }
+                    if repaired > 0 {
+                        entry.update_history();
+                    }
+                    repaired
+                })
+            })
+            .sum()
+    }
+
+    /// Undo the escaping that versions of this crate up to 0.9.0 added on top of the escaping of
+    /// the XML writer when saving. Those versions wrote `<` as `&amp;lt;`, which is now read as the
+    /// literal text `&lt;`. Only call this for databases last saved by such a version, as a value
+    /// that is meant to contain `&lt;` is changed as well. Field names and values, tags, the
+    /// override URL and the auto-type sequences and windows are repaired, and the repair is
+    /// recorded in the history of each entry. Returns the number of values that were repaired.
+    pub fn repair_xml_escaping(&mut self) -> usize {
+        NodeIterator::new(&self.root)
+            .filter_map(|node| {
+                with_node_mut::<Entry, _, _>(&node, |entry| {
+                    let repair = |s: &mut String| unescape_xml_entities(s).map(|fixed| *s = fixed).is_some();
+                    let mut repaired = 0;
+                    for (mut name, mut value) in std::mem::take(&mut entry.fields) {
+                        repaired += usize::from(repair(&mut name));
+                        match &mut value {
+                            Value::Unprotected(s) => repaired += usize::from(repair(s)),
+                            Value::Protected(s) => {
+                                if let Some(fixed) = std::str::from_utf8(s.unsecure()).ok().and_then(unescape_xml_entities) {
+                                    *s = SecStr::from(fixed);
+                                    repaired += 1;
+                                }
+                            }
+                            Value::Bytes(_) => {}
+                        }
+                        entry.fields.insert(name, value);
+                    }
+                    let autotype = entry.autotype.iter_mut().flat_map(|autotype| {
+                        let associations = autotype.associations.iter_mut().flat_map(|a| [&mut a.window, &mut a.sequence]);
+                        std::iter::once(&mut autotype.sequence).chain(associations)
+                    });
+                    // the tags were escaped as a whole, and the `;` of the entities split them when reading
+                    if let Some(tags) = unescape_xml_entities(&entry.tags.join(";")) {
+                        entry.tags = tags.split([';', ',']).map(str::to_string).collect();
+                        repaired += 1;
+                    }
+                    repaired += entry
+                        .override_url
+                        .iter_mut()
+                        .chain(autotype.flatten())
+                        .map(repair)
+                        .filter(|&fixed| fixed)
+                        .count();
+                    if repaired > 0 {
+                        entry.update_history();
+                    }
+                    repaired
+                })
+            })
+            .sum()
+    }
+
+    /// Get all entries in document order, each paired with the path of the group containing it,
+    /// for exporting to formats without nesting. Group titles are joined with `/` and the root
+    /// group is left out, so entries directly in the root have an empty path. Slashes and
+    /// backslashes in titles are escaped like `get_by_path_str` expects them.
+    pub fn flatten(&self) -> Vec<(String, NodePtr)> {
+        let mut entries = Vec::new();
+        flatten_group(&self.root, "", false, &mut entries);
+        entries
+    }
+
+    /// Like `flatten`, but the entry title is appended to the group path, e.g. `Internet/Email/Gmail`,
+    /// so that `get_by_path_str` finds the entry again
+    pub fn flatten_with_title_prefix(&self) -> Vec<(String, NodePtr)> {
+        let mut entries = Vec::new();
+        flatten_group(&self.root, "", true, &mut entries);
+        entries
+    }
+
+    /// Write a simple HTML listing of all entries, nested by group, with their title, user name and
+    /// URL, and their password if `include_passwords` is set. All content is HTML-escaped.
+    pub fn export_html<W: std::io::Write>(&self, w: &mut W, include_passwords: bool) -> crate::Result<()> {
+        let title = escape_html(self.meta.database_name.as_deref().unwrap_or_default());
+        writeln!(w, "<!DOCTYPE html>")?;
+        writeln!(w, "<html>")?;
+        writeln!(w, "<head><meta charset=\"utf-8\"><title>{title}</title></head>")?;
+        writeln!(w, "<body>")?;
+        writeln!(w, "<h1>{title}</h1>")?;
+        export_html_group(&self.root, 2, include_passwords, w)?;
+        writeln!(w, "</body>")?;
+        writeln!(w, "</html>")?;
+        Ok(())
+    }
+
+    fn create_new_node<T: Node + Default>(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
+        let new_node = rc_refcell_node(T::default());
+        let parent = search_node_by_uuid_with_specific_type::<Group>(&self.root, parent)
+            .or_else(|| Some(self.root.clone().into()))
+            .ok_or("No parent node")?;
+        with_node_mut::<Group, _, _>(&parent, |parent| {
+            parent.add_child(new_node.clone(), index);
+        });
+        Ok(new_node)
+    }
+
+    /// Insert many entries into the group `parent` at once, see `Group::add_entries`
+    pub fn bulk_insert(&self, parent: Uuid, entries: Vec<Entry>) -> crate::Result<()> {
+        let parent = search_node_by_uuid_with_specific_type::<Group>(&self.root, parent).ok_or("Parent group not found")?;
+        with_node_mut::<Group, _, _>(&parent, |parent| parent.add_entries(entries)).ok_or("Parent is not a group")?;
+        Ok(())
+    }
+
+    pub fn create_new_entry(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
+        self.create_new_node::<Entry>(parent, index)
+    }
+
+    /// Like `create_new_entry`, but fill in the user name from `default_username_for`
+    pub fn create_new_entry_with_defaults(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
+        let entry = self.create_new_entry(parent, index)?;
+        let parent = entry.borrow().get_parent();
+        if let Some(username) = parent.and_then(|parent| self.default_username_for(parent)) {
+            with_node_mut::<Entry, _, _>(&entry, |entry| entry.set_username(Some(&username)));
+        }
+        Ok(entry)
+    }
+
+    /// The user name for new entries in the group `group`: the default user name of the group or
+    /// of the closest group above it that has one, see `Group::default_username`, otherwise the
+    /// default user name of the database
+    pub fn default_username_for(&self, group: Uuid) -> Option<String> {
+        let mut current = Some(group);
+        while let Some(group_uuid) = current {
+            let Some(group) = search_node_by_uuid_with_specific_type::<Group>(&self.root, group_uuid) else {
+                break;
+            };
+            if let Some(username) = with_node::<Group, _, _>(&group, |g| g.default_username().map(str::to_string)).flatten() {
+                return Some(username);
+            }
+            current = group.borrow().get_parent();
+        }
+        self.meta.default_username.clone().filter(|username| !username.is_empty())
+    }
+
+    /// Like `create_new_entry`, but also set the title of the new entry to `title`. Fails without
+    /// creating an entry if the parent group already contains an entry with this title, see
+    /// `Group::contains_title`.
+    pub fn create_new_entry_unique(&self, parent: Uuid, index: usize, title: &str) -> crate::Result<NodePtr> {
+        let group = search_node_by_uuid_with_specific_type::<Group>(&self.root, parent).unwrap_or_else(|| self.root.clone().into());
+        if with_node::<Group, _, _>(&group, |group| group.contains_title(title)).unwrap_or(false) {
+            return Err(format!("An entry titled {title:?} already exists in the group").into());
+        }
+        let group_uuid = group.borrow().get_uuid();
+        let entry = self.create_new_entry(group_uuid, index)?;
+        entry.borrow_mut().set_title(Some(title));
+        Ok(entry)
+    }
+
+    pub fn create_new_group(&self, parent: Uuid, index: usize) -> crate::Result<NodePtr> {
+        self.create_new_node::<Group>(parent, index)
+    }
+}
+
+/// Remove the empty subgroups of `group` depth-first, returns whether `group` is empty afterwards
+fn remove_empty_subgroups(group: &NodePtr, keep: Option<Uuid>, removed: &mut Vec<Uuid>) -> bool {
+    let mut empty = Vec::new();
+    for child in group_get_children(group).unwrap_or_default() {
+        let uuid = child.borrow().get_uuid();
+        if node_is_group(&child) && remove_empty_subgroups(&child, keep, removed) && keep != Some(uuid) {
+            empty.push(uuid);
+        }
+    }
+    with_node_mut::<Group, _, _>(group, |group| {
+        group.children.retain(|c| !empty.contains(&c.borrow().get_uuid()));
+        removed.extend(empty);
+        group.children.is_empty()
+    })
+    .unwrap_or(false)
+}
+
+/// The UUIDs of `nodes`, in the same order
+fn node_uuids(nodes: Vec<NodePtr>) -> Vec<Uuid> {
+    nodes.iter().map(|node| node.borrow().get_uuid()).collect()
+}
+
+/// Remove the nodes below `root` that `deleted` lists, unless they were modified after they were
+/// deleted. A group is only removed along with everything in it, so it stays if anything in it
+/// is still there. Returns a merge event for every removed node.
+fn remove_deleted_nodes(root: &NodePtr, deleted: &DeletedObjects) -> crate::Result<Vec<MergeEvent>> {
+    let deletion_times: HashMap<Uuid, NaiveDateTime> = deleted.objects.iter().map(|o| (o.uuid, o.deletion_time)).collect();
+    let is_deleted = |node: NodePtr| {
+        let node = node.borrow();
+        deletion_times
+            .get(&node.get_uuid())
+            .is_some_and(|&deleted| node.get_times().get_last_modification().is_none_or(|modified| modified <= deleted))
+    };
+    let root_uuid = root.borrow().get_uuid();
+    let removed: Vec<MergeEvent> = NodeIterator::new(root)
+        .filter(|node| node.borrow().get_uuid() != root_uuid && NodeIterator::new(node).all(is_deleted))
+        .map(|node| MergeEvent {
+            node_uuid: node.borrow().get_uuid(),
+            event_type: if node_is_group(&node) {
+                MergeEventType::GroupDeleted
+            } else {
+                MergeEventType::EntryDeleted
+            },
+        })
+        .collect();
+    for event in &removed {
+        // the descendants of a removed group are gone with it
+        if search_node_by_uuid(root, event.node_uuid).is_some() {
+            group_remove_node_by_uuid(root, event.node_uuid)?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Escape a title for a path that `split_path_str` splits again
+fn escape_path_part(title: &str) -> String {
+    title.replace('\\', "\\\\").replace('/', "\\/")
+}
+
+/// Split a path on `/`, where `\/` is a literal slash and `\\` a literal backslash
+fn split_path_str(path: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ ('/' | '\\')) => parts.last_mut().unwrap().push(escaped),
+                Some(other) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(other);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            },
+            '/' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+/// How deep `Database::resolve_references` follows references in referenced fields
+const MAX_REFERENCE_DEPTH: usize = 10;
+
+/// The field a code of a field reference stands for, except for `I` (UUID) and `O` (other fields)
+fn reference_field_name(code: char) -> Option<&'static str> {
+    match code {
+        'T' => Some("Title"),
+        'U' => Some("UserName"),
+        'P' => Some("Password"),
+        'A' => Some("URL"),
+        'N' => Some("Notes"),
+        _ => None,
+    }
+}
+
+/// Whether `entry` is found by the search of a field reference, see `Database::resolve_references`
+fn reference_matches(entry: &Entry, search: char, text: &str) -> bool {
+    let text = text.to_lowercase();
+    let contains = |name: &str| entry.get(name).is_some_and(|value| value.to_lowercase().contains(&text));
+    match search {
+        'I' => Uuid::parse_str(&text).is_ok_and(|uuid| uuid == entry.uuid),
+        'O' => entry
+            .fields
+            .keys()
+            .filter(|name| !["Title", "UserName", "Password", "URL", "Notes"].contains(&name.as_str()))
+            .any(|name| contains(name)),
+        _ => reference_field_name(search).is_some_and(contains),
+    }
+}
+
+fn flatten_group(group: &NodePtr, path: &str, with_title: bool, entries: &mut Vec<(String, NodePtr)>) {
+    let join = |title: Option<&str>| match path {
+        "" => escape_path_part(title.unwrap_or_default()),
+        _ => format!("{path}/{}", escape_path_part(title.unwrap_or_default())),
+    };
+    for child in group_get_children(group).unwrap_or_default() {
+        if node_is_group(&child) {
+            let child_path = join(child.borrow().get_title());
+            flatten_group(&child, &child_path, with_title, entries);
+        } else if with_title {
+            let key = join(child.borrow().get_title());
+            entries.push((key, child));
+        } else {
+            entries.push((path.to_string(), child));
+        }
+    }
+}
+
+fn export_html_group<W: std::io::Write>(group: &NodePtr, level: usize, include_passwords: bool, w: &mut W) -> std::io::Result<()> {
+    let children = group_get_children(group).unwrap_or_default();
+    let (groups, entries): (Vec<_>, Vec<_>) = children.into_iter().partition(node_is_group);
+
+    writeln!(w, "<div class=\"group\">")?;
+    let title = escape_html(group.borrow().get_title().unwrap_or_default());
+    let level = level.min(6);
+    writeln!(w, "<h{level}>{title}</h{level}>")?;
+    if !entries.is_empty() {
+        writeln!(w, "<table>")?;
+        let password_header = if include_passwords { "<th>Password</th>" } else { "" };
+        writeln!(w, "<tr><th>Title</th><th>User Name</th><th>URL</th>{password_header}</tr>")?;
+        for entry in &entries {
+            let row = with_node::<Entry, _, _>(entry, |e| {
+                let cell = |value: Option<&str>| format!("<td>{}</td>", escape_html(value.unwrap_or_default()));
+                let password = if include_passwords { cell(e.get_password()) } else { String::new() };
+                format!(
+                    "<tr>{}{}{}{password}</tr>",
+                    cell(e.get_title()),
+                    cell(e.get_username()),
+                    cell(e.get_url())
+                )
+            });
+            if let Some(row) = row {
+                writeln!(w, "{row}")?;
+            }
+        }
+        writeln!(w, "</table>")?;
+    }
+    for child in &groups {
+        export_html_group(child, level + 1, include_passwords, w)?;
+    }
+    writeln!(w, "</div>")
+}
+
+/// The characters Windows-1252 has at the bytes 0x80 to 0x9F. The bytes it leaves undefined map
+/// to the control characters of the same value, as in Latin-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{2C6}', '\u{2030}', '\u{160}',
+    '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}', '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}',
+    '\u{2014}', '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// If `s` is UTF-8 that was decoded as Latin-1 or Windows-1252, return the original text. Plain
+/// ASCII, text that does not decode as UTF-8 again and text that does not give back `s` exactly
+/// when encoded again with one of the two code pages is left alone.
+fn undo_double_encoding(s: &str) -> Option<String> {
+    if s.is_ascii() {
+        return None;
+    }
+    let bytes = s
+        .chars()
+        .map(|c| match u8::try_from(c) {
+            Ok(byte) => Some(byte),
+            Err(_) => WINDOWS_1252_HIGH.iter().position(|&high| high == c).map(|i| 0x80 + i as u8),
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    let original = String::from_utf8(bytes).ok()?;
+    let latin_1: String = original.bytes().map(char::from).collect();
+    let windows_1252: String = original
+        .bytes()
+        .map(|byte| match byte {
+            0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        })
+        .collect();
+    (latin_1 == s || windows_1252 == s).then_some(original)
+}
+
+/// Replace the predefined XML entities and the character references in `&#x...;` form, which
+/// earlier versions of the XML dumper wrote, in a single pass. Returns `None` if there are none.
+fn unescape_xml_entities(s: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    let mut changed = false;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').map(|end| &rest[..=end]);
+        let c = entity.and_then(|entity| match entity {
+            "&lt;" => Some('<'),
+            "&gt;" => Some('>'),
+            "&amp;" => Some('&'),
+            "&quot;" => Some('"'),
+            "&apos;" => Some('\''),
+            _ => {
+                let hex = entity.strip_prefix("&#x")?.strip_suffix(';')?;
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            }
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                unescaped.push(c);
+                rest = &rest[entity.len()..];
+                changed = true;
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    changed.then_some(unescaped)
+}
+
+fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Timestamps for a Group or Entry
 #[derive(Debug, Default, PartialEq, Eq, Clone)]
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct CustomDataItem {
-    pub value: Option<Value>,
-    pub last_modification_time: Option<NaiveDateTime>,
+pub struct Times {
+    /// Does this node expire
+    pub(crate) expires: bool,
+
+    /// Number of usages
+    pub(crate) usage_count: usize,
+
+    /// Using chrono::NaiveDateTime which does not include timezone
+    /// or UTC offset because KeePass clients typically store timestamps
+    /// relative to the local time on the machine writing the data without
+    /// including accurate UTC offset or timezone information.
+    pub(crate) times: HashMap<String, NaiveDateTime>,
 }
 
-/// Custom data field for an entry or metadata from XML data
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct CustomDataItemDenormalized {
-    pub key: String,
-    pub custom_data_item: CustomDataItem,
-}
+pub const EXPIRY_TIME_TAG_NAME: &str = "ExpiryTime";
+pub const LAST_MODIFICATION_TIME_TAG_NAME: &str = "LastModificationTime";
+pub const CREATION_TIME_TAG_NAME: &str = "CreationTime";
+pub const LAST_ACCESS_TIME_TAG_NAME: &str = "LastAccessTime";
+pub const LOCATION_CHANGED_TAG_NAME: &str = "LocationChanged";
+
+impl Times {
+    fn get(&self, key: &str) -> Option<NaiveDateTime> {
+        self.times.get(key).copied()
+    }
+
+    fn set(&mut self, key: &str, time: Option<NaiveDateTime>) {
+        if let Some(time) = time {
+            self.times.insert(key.to_string(), time);
+        } else {
+            self.times.remove(key);
+        }
+    }
+
+    pub fn get_expires(&self) -> bool {
+        self.expires
+    }
+
+    pub fn set_expires(&mut self, expires: bool) {
+        self.expires = expires;
+    }
+
+    pub fn get_usage_count(&self) -> usize {
+        self.usage_count
+    }
+
+    pub fn set_usage_count(&mut self, usage_count: usize) {
+        self.usage_count = usage_count;
+    }
+
+    /// Convenience method for getting the time that the entry expires.
+    /// This value is usually only meaningful/useful when expires == true
+    pub fn get_expiry_time(&self) -> Option<NaiveDateTime> {
+        self.get(EXPIRY_TIME_TAG_NAME)
+    }
+
+    pub fn set_expiry_time(&mut self, time: Option<NaiveDateTime>) {
+        self.set(EXPIRY_TIME_TAG_NAME, time);
+    }
+
+    pub fn get_last_modification(&self) -> Option<NaiveDateTime> {
+        self.get(LAST_MODIFICATION_TIME_TAG_NAME)
+    }
+
+    pub fn set_last_modification(&mut self, time: Option<NaiveDateTime>) {
+        self.set(LAST_MODIFICATION_TIME_TAG_NAME, time);
+    }
+
+    pub fn get_creation(&self) -> Option<NaiveDateTime> {
+        self.get(CREATION_TIME_TAG_NAME)
+    }
+
+    pub fn set_creation(&mut self, time: Option<NaiveDateTime>) {
+        self.set(CREATION_TIME_TAG_NAME, time);
+    }
+
+    pub fn get_last_access(&self) -> Option<NaiveDateTime> {
+        self.get(LAST_ACCESS_TIME_TAG_NAME)
+    }
+
+    pub fn set_last_access(&mut self, time: Option<NaiveDateTime>) {
+        self.set(LAST_ACCESS_TIME_TAG_NAME, time);
+    }
+
+    pub fn get_location_changed(&self) -> Option<NaiveDateTime> {
+        self.get(LOCATION_CHANGED_TAG_NAME)
+    }
+
+    pub fn set_location_changed(&mut self, time: Option<NaiveDateTime>) {
+        self.set(LOCATION_CHANGED_TAG_NAME, time);
+    }
+
+    // Returns the current time, without the nanoseconds since
+    // the last leap second.
+    pub fn now() -> NaiveDateTime {
+        let now = chrono::Utc::now().timestamp();
+        chrono::DateTime::from_timestamp(now, 0).unwrap().naive_utc()
+    }
+
+    pub fn epoch() -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(0, 0).unwrap().naive_utc()
+    }
+
+    pub fn new() -> Times {
+        let mut response = Times::default();
+        let now = Some(Times::now());
+        response.set_creation(now);
+        response.set_last_modification(now);
+        response.set_last_access(now);
+        response.set_location_changed(now);
+        response.set_expiry_time(now);
+        response.set_expires(false);
+        response
+    }
+
+    /// Check that the timestamps are consistent: the creation must not be after the last
+    /// modification, and an expiring node must not expire before it was created
+    pub fn validate(&self) -> crate::Result<()> {
+        let creation = self.get_creation();
+        if let (Some(creation), Some(modification)) = (creation, self.get_last_modification()) {
+            if creation > modification {
+                return Err(format!("Created at {creation}, after the last modification at {modification}").into());
+            }
+        }
+        if let (true, Some(creation), Some(expiry)) = (self.expires, creation, self.get_expiry_time()) {
+            if expiry < creation {
+                return Err(format!("Expires at {expiry}, before the creation at {creation}").into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the sub-second part of all timestamps, since the database format only stores seconds
+    pub(crate) fn truncate_subsecond(&mut self) {
+        use chrono::Timelike;
+        for time in self.times.values_mut() {
+            *time = time.with_nanosecond(0).unwrap_or(*time);
+        }
+    }
+}
+
+/// Collection of custom data fields for an entry or metadata
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct CustomData {
+    pub items: HashMap<String, CustomDataItem>,
+}
+
+impl CustomData {
+    /// Set an item, stamping it with the current time so that merging picks the newest value
+    pub fn set(&mut self, key: &str, value: Value) {
+        let item = CustomDataItem {
+            value: Some(value),
+            last_modification_time: Some(Times::now()),
+        };
+        self.items.insert(key.to_string(), item);
+    }
+
+    /// Get the value of an item
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.items.get(key).and_then(|item| item.value.as_ref())
+    }
+
+    /// Remove an item, returning it if it existed
+    pub fn remove(&mut self, key: &str) -> Option<CustomDataItem> {
+        self.items.remove(key)
+    }
+}
+
+/// Custom data field for an entry or metadata for internal use
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct CustomDataItem {
+    pub value: Option<Value>,
+    pub last_modification_time: Option<NaiveDateTime>,
+}
+
+/// Custom data field for an entry or metadata from XML data
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct CustomDataItemDenormalized {
+    pub key: String,
+    pub custom_data_item: CustomDataItem,
+}
+
+/// Whether two versions of an entry differ and neither is a version the other already has in
+/// its history, i.e. both were edited since their last common version
+fn entries_diverged(ours: &Entry, theirs: &Entry) -> bool {
+    let knows = |entry: &Entry, version: &Entry| {
+        let modified = version.times.get_last_modification();
+        entry
+            .history
+            .iter()
+            .flat_map(|h| &h.entries)
+            .any(|old| old.times.get_last_modification() == modified && old.eq_ignoring_history(version))
+    };
+    !ours.eq_ignoring_history(theirs) && !knows(ours, theirs) && !knows(theirs, ours)
+}
+
+/// The result of `Database::merge_preview`
+#[derive(Debug, Default, Clone)]
+pub struct MergePreview {
+    /// What merging would change
+    pub log: MergeLog,
+    /// The UUIDs of the entries that were edited in both databases
+    pub conflicts: Vec<Uuid>,
+}
+
+/// What `Database::probe_key_requirements` found out about opening a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct KeyProbe {
+    /// Version of the database file
+    pub version: DatabaseVersion,
+    /// The key derivation settings, e.g. to tell the user that opening takes a while
+    pub kdf_config: KdfConfig,
+    /// Whether the database opens with an empty password and no key file
+    pub opens_with_empty_password: bool,
+}
+
+impl KeyProbe {
+    /// Whether the user has to provide a key. This is a password, a key file or both, which one
+    /// cannot be told from the file.
+    pub fn key_required(&self) -> bool {
+        !self.opens_with_empty_password
+    }
+}
+
+/// The fields that `Entry::clear_custom_fields` keeps: the standard fields of `KeePass` and the
+/// `otp` field holding a TOTP
+pub const STANDARD_FIELD_NAMES: [&str; 6] = ["Title", "UserName", "Password", "URL", "Notes", "otp"];
+
+/// The key of the custom data item of a group holding its default user name, see
+/// `Group::default_username`
+pub const GROUP_DEFAULT_USERNAME_KEY: &str = "keepass-ng.DefaultUserName";
+
+/// The name `Database::create_recycle_bin` gives the recycle bin
+pub const DEFAULT_RECYCLE_BIN_NAME: &str = "Recycle Bin";
+
+/// The auto-type sequence `KeePass` uses when neither an entry nor its groups define one
+pub const DEFAULT_AUTOTYPE_SEQUENCE: &str = "{USERNAME}{TAB}{PASSWORD}{ENTER}";
+
+/// Passwords with fewer bits than this are reported as weak by `Database::audit_report`
+pub const AUDIT_WEAK_PASSWORD_BITS: f64 = 64.0;
+
+/// The result of `Database::audit_report`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct AuditReport {
+    /// Number of entries outside of the recycle bin that were checked
+    pub entries_checked: usize,
+    /// The UUIDs of the entries with a weak password
+    pub weak: Vec<Uuid>,
+    /// The UUIDs of entries sharing a password, one list per password
+    pub reused: Vec<Vec<Uuid>>,
+    /// The UUIDs of the expired entries
+    pub expired: Vec<Uuid>,
+}
+
+impl AuditReport {
+    /// Number of entries whose password is used by at least one other entry
+    pub fn reused_count(&self) -> usize {
+        self.reused.iter().map(Vec::len).sum()
+    }
+
+    /// Whether no problems were found
+    pub fn is_clean(&self) -> bool {
+        self.weak.is_empty() && self.reused.is_empty() && self.expired.is_empty()
+    }
+}
+
+/// The changes exported by `Database::export_modified_since_json`
+#[cfg(feature = "serialization")]
+#[derive(serde::Serialize)]
+struct EntriesDelta {
+    since: NaiveDateTime,
+    entries: Vec<SerializableNodePtr>,
+    deleted: Vec<Uuid>,
+}
+
+/// How `Database::entries_matching_tags` combines multiple tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagMatch {
+    /// Entries carrying at least one of the tags
+    Any,
+    /// Entries carrying every one of the tags
+    All,
+}
+
+/// Binary attachments stored in a database inner header
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct HeaderAttachment {
+    pub flags: u8,
+    pub content: Vec<u8>,
+}
+
+/// Elements that have been previously deleted
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct DeletedObjects {
+    pub objects: Vec<DeletedObject>,
+}
+
+impl DeletedObjects {
+    pub fn add(&mut self, uuid: Uuid) {
+        let deletion_time = Times::now();
+        if let Some(item) = self.objects.iter_mut().find(|item| item.uuid == uuid) {
+            item.deletion_time = deletion_time;
+        } else {
+            self.objects.push(DeletedObject { uuid, deletion_time });
+        }
+    }
+
+    /// Add the objects of `other`, keeping the later deletion time of objects in both
+    pub fn merge(&mut self, other: &DeletedObjects) {
+        for object in &other.objects {
+            match self.objects.iter_mut().find(|item| item.uuid == object.uuid) {
+                Some(item) => item.deletion_time = item.deletion_time.max(object.deletion_time),
+                None => self.objects.push(object.clone()),
+            }
+        }
+    }
+}
+
+/// A reference to a deleted element
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct DeletedObject {
+    pub uuid: Uuid,
+    pub deletion_time: NaiveDateTime,
+}
+
+/// A color value for the Database, or Entry
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[cfg(feature = "serialization")]
+impl serde::Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        if !str.starts_with('#') || str.len() != 7 {
+            return Err(ParseColorError(str.to_string()));
+        }
+
+        let var = u64::from_str_radix(str.trim_start_matches('#'), 16).map_err(|_e| ParseColorError(str.to_string()))?;
+
+        let r = ((var >> 16) & 0xff) as u8;
+        let g = ((var >> 8) & 0xff) as u8;
+        let b = (var & 0xff) as u8;
+
+        Ok(Self { r, g, b })
+    }
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+#[cfg(test)]
+mod database_tests {
+    use super::node_uuids;
+    #[cfg(feature = "save_kdbx4")]
+    use crate::{config::DatabaseConfig, db::Entry};
+    use crate::{
+        db::{Database, DatabaseKey},
+        Result,
+    };
+    use std::fs::File;
+
+    /// The time `secs` seconds after the Unix epoch
+    fn at(secs: i64) -> chrono::NaiveDateTime {
+        chrono::DateTime::from_timestamp(secs, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn test_xml() -> Result<()> {
+        let key = DatabaseKey::new().with_password("demopass");
+        let mut f = File::open("tests/resources/test_db_with_password.kdbx")?;
+        let xml = Database::get_xml(&mut f, key)?;
+
+        assert!(xml.len() > 100);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_upgrade_to_kdbx4() -> Result<()> {
+        use crate::{
+            config::{DatabaseVersion, InnerCipherConfig, KdfConfig},
+            db::{with_node, Entry, Value},
+        };
+
+        fn attachments(db: &Database) -> Vec<(uuid::Uuid, String, Vec<u8>)> {
+            crate::db::NodeIterator::new(&db.root)
+                .filter_map(|node| with_node::<Entry, _, _>(&node, |e| (e.uuid, e.attachments.clone())))
+                .flat_map(|(uuid, refs)| {
+                    refs.into_iter()
+                        .map(move |r| (uuid, r.name.clone(), db.resolve_attachment(&r).unwrap().unwrap().into_owned()))
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        }
+
+        let key = DatabaseKey::new().with_password("samplepassword");
+        let mut db = Database::open(
+            &mut File::open("tests/resources/test_db_kdb3_with_file_larger_1mb.kdbx")?,
+            key.clone(),
+        )?;
+        assert!(matches!(db.config.version, DatabaseVersion::KDB3(_)));
+        db.meta
+            .set_custom_data("custom-key", Value::Unprotected("custom-value".to_string()));
+        let before = attachments(&db);
+        assert!(!before.is_empty());
+
+        db.upgrade_to_kdbx4()?;
+        assert!(db.meta.binaries.binaries.is_empty());
+        assert_eq!(attachments(&db), before);
+
+        let mut data = Vec::new();
+        db.save(&mut data, key.clone())?;
+        let reopened = Database::open(&mut data.as_slice(), key)?;
+        assert!(matches!(reopened.config.version, DatabaseVersion::KDB4(_)));
+        assert!(matches!(reopened.config.kdf_config, KdfConfig::Argon2id { .. }));
+        assert_eq!(reopened.config.inner_cipher_config, InnerCipherConfig::ChaCha20);
+        assert_eq!(attachments(&reopened), before);
+        assert_eq!(
+            reopened.meta.get_custom_data("custom-key"),
+            Some(&Value::Unprotected("custom-value".to_string()))
+        );
+        assert!(crate::db::node_is_equals_to(&db.root, &reopened.root));
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_invalid_version_header_size() {
+        assert!(Database::parse(&[], DatabaseKey::new().with_password("testing")).is_err());
+        assert!(Database::parse(&[0, 0, 0, 0, 0, 0, 0, 0], DatabaseKey::new().with_password("testing")).is_err());
+        assert!(Database::parse(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], DatabaseKey::new().with_password("testing")).is_err());
+    }
+
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_save() -> Result<()> {
+        use crate::{
+            db::Group,
+            db::{group_add_child, rc_refcell_node},
+        };
+
+        let db = Database::new(DatabaseConfig::default());
+
+        group_add_child(&db.root, rc_refcell_node(Entry::default()), 0).unwrap();
+        group_add_child(&db.root, rc_refcell_node(Entry::default()), 1).unwrap();
+        group_add_child(&db.root, rc_refcell_node(Entry::default()), 2).unwrap();
+
+        let group = rc_refcell_node(Group::new("my group"));
+        group_add_child(&group, rc_refcell_node(Entry::default()), 0).unwrap();
+        group_add_child(&group, rc_refcell_node(Entry::default()), 1).unwrap();
+        group_add_child(&db.root, group, 3).unwrap();
+
+        let mut buffer = Vec::new();
+        let key = DatabaseKey::new().with_password("testing");
+
+        db.save(&mut buffer, key.clone())?;
+
+        let db_loaded = Database::open(&mut buffer.as_slice(), key)?;
+
+        assert_eq!(db, db_loaded);
+        Ok(())
+    }
+
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_save_empty() -> Result<()> {
+        use crate::db::group_get_children;
+
+        let db = Database::new(DatabaseConfig::default());
+
+        let mut buffer = Vec::new();
+        let key = DatabaseKey::new().with_password("testing");
+        db.save(&mut buffer, key.clone())?;
+
+        let db_loaded = Database::open(&mut buffer.as_slice(), key)?;
+        assert_eq!(group_get_children(&db_loaded.root).unwrap().len(), 0);
+        assert_eq!(db_loaded.root.borrow().get_uuid(), db.root.borrow().get_uuid());
+        assert_eq!(db_loaded.meta, db.meta);
+        assert!(db_loaded.recycle_bin_enabled());
+        assert!(db_loaded.get_recycle_bin().is_none());
+        assert_eq!(db, db_loaded);
+        Ok(())
+    }
+
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_save_reuse_seeds() -> Result<()> {
+        use crate::{
+            config::DumpOptions,
+            db::{group_get_children, with_node_mut, Node},
+        };
+
+        let db = Database::new(DatabaseConfig::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |entry| entry.set_title(Some("Reused")));
+
+        let key = DatabaseKey::new().with_password("testing");
+        let mut original = Vec::new();
+        db.save(&mut original, key.clone())?;
+
+        let db = Database::open(&mut original.as_slice(), key.clone())?;
+        let options = DumpOptions {
+            reuse_seeds: true,
+            ..Default::default()
+        };
+
+        let mut first = Vec::new();
+        db.save_with_options(&mut first, key.clone(), &options)?;
+        let mut second = Vec::new();
+        db.save_with_options(&mut second, key.clone(), &options)?;
+        assert_eq!(first, second);
+        assert_eq!(first, original);
+
+        // without the option, or with a different key, fresh seeds are used
+        let mut fresh = Vec::new();
+        db.save(&mut fresh, key.clone())?;
+        assert_ne!(fresh, first);
+
+        let other_key = DatabaseKey::new().with_password("other");
+        let mut rekeyed = Vec::new();
+        db.save_with_options(&mut rekeyed, other_key.clone(), &options)?;
+        assert_ne!(rekeyed, first);
+        assert_eq!(Database::open(&mut rekeyed.as_slice(), other_key)?, db);
+
+        // changed content must not reuse the IVs either
+        let entry = group_get_children(&db.root).unwrap()[0].clone();
+        with_node_mut::<Entry, _, _>(&entry, |entry| entry.set_title(Some("Changed")));
+        let mut changed = Vec::new();
+        db.save_with_options(&mut changed, key.clone(), &options)?;
+        assert_ne!(changed[..first.len().min(changed.len())], first[..first.len().min(changed.len())]);
+        assert_eq!(Database::open(&mut changed.as_slice(), key)?, db);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_node_into_new_recycle_bin() -> Result<()> {
+        use crate::db::group_get_children;
+
+        let mut db = Database::new(Default::default());
+        assert!(db.recycle_bin_enabled());
+        assert!(db.meta.recycle_bin_changed().is_none());
+
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        let entry_uuid = entry.borrow().get_uuid();
+        assert!(db.get_recycle_bin().is_none());
+
+        // removing an unknown node must not create the recycle bin
+        assert!(db.remove_node_by_uuid(uuid::Uuid::new_v4()).is_err());
+        assert!(db.get_recycle_bin().is_none());
+        assert!(db.meta.recycle_bin_changed().is_none());
+
+        db.remove_node_by_uuid(entry_uuid)?;
+        let recycle_bin = db.get_recycle_bin().unwrap();
+        assert!(db.meta.recycle_bin_changed().is_some());
+        assert!(db.node_is_in_recycle_bin(entry_uuid));
+        assert_eq!(group_get_children(&recycle_bin).unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_recycle_bin_group() -> Result<()> {
+        let mut db = Database::new(Default::default());
+        db.meta.set_recycle_bin_enabled(false);
+        let root_uuid = db.root.borrow().get_uuid();
+        let parent = db.create_new_group(root_uuid, 0)?;
+        let parent_uuid = parent.borrow().get_uuid();
+        let trash = db.create_new_group(parent_uuid, 0)?;
+        let trash_uuid = trash.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 1)?;
+        let entry_uuid = entry.borrow().get_uuid();
+
+        assert!(db.set_recycle_bin_group(uuid::Uuid::new_v4()).is_err());
+        assert!(db.set_recycle_bin_group(entry_uuid).is_err());
+        assert!(db.set_recycle_bin_group(root_uuid).is_err());
+        assert!(db.meta.recyclebin_uuid.is_none());
+
+        db.set_recycle_bin_group(trash_uuid)?;
+        assert!(db.recycle_bin_enabled());
+        assert!(db.meta.recycle_bin_changed().is_some());
+        assert_eq!(db.get_recycle_bin().unwrap().borrow().get_uuid(), trash_uuid);
+
+        db.remove_node_by_uuid(entry_uuid)?;
+        assert!(db.node_is_in_recycle_bin(entry_uuid));
+        assert!(matches!(db.create_recycle_bin(), Err(crate::Error::RecycleBinAlreadyExists)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_recycle_bin_named() -> Result<()> {
+        use crate::db::IconId;
+
+        let mut db = Database::new(Default::default());
+        let bin = db.create_recycle_bin_named("Papierkorb")?;
+        assert_eq!(bin.borrow().get_title(), Some("Papierkorb"));
+        assert_eq!(bin.borrow().get_icon_id(), Some(IconId::RECYCLE_BIN));
+        assert!(db.node_is_recycle_bin(&bin));
+        assert!(matches!(
+            db.create_recycle_bin_named("Corbeille"),
+            Err(crate::Error::RecycleBinAlreadyExists)
+        ));
+
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry_uuid = db.create_new_entry(root_uuid, 0)?.borrow().get_uuid();
+        db.remove_node_by_uuid(entry_uuid)?;
+        assert!(db.node_is_in_recycle_bin(entry_uuid));
+
+        let mut db = Database::new(Default::default());
+        let bin = db.create_recycle_bin_with_icon("Corbeille", IconId::FILING_CABINET)?;
+        assert_eq!(bin.borrow().get_icon_id(), Some(IconId::FILING_CABINET));
+        assert!(db.node_is_recycle_bin(&bin));
+
+        let bin = Database::new(Default::default()).create_recycle_bin()?;
+        assert_eq!(bin.borrow().get_title(), Some(crate::db::DEFAULT_RECYCLE_BIN_NAME));
+        Ok(())
+    }
+
+    #[test]
+    fn test_orphan_check() -> Result<()> {
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        let entry_uuid = entry.borrow().get_uuid();
+        let other = db.create_new_entry(root_uuid, 1)?;
+        assert!(db.orphan_check().is_empty());
+
+        entry.borrow_mut().set_parent(Some(uuid::Uuid::new_v4()));
+        other.borrow_mut().set_parent(Some(group_uuid));
+        let other_uuid = other.borrow().get_uuid();
+        assert_eq!(db.orphan_check(), vec![other_uuid, entry_uuid]);
+
+        entry.borrow_mut().set_parent(Some(group_uuid));
+        other.borrow_mut().set_parent(Some(root_uuid));
+        db.root.borrow_mut().set_parent(Some(group_uuid));
+        assert_eq!(db.orphan_check(), vec![root_uuid]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_data_set() {
+        use crate::db::{Entry, Group, Times, Value};
+
+        let before = Times::now();
+        let mut db = Database::new(Default::default());
+        db.meta.set_custom_data("meta-key", Value::Unprotected("meta".to_string()));
+        let item = &db.meta.custom_data.items["meta-key"];
+        assert_eq!(item.value, Some(Value::Unprotected("meta".to_string())));
+        assert!(item.last_modification_time.unwrap() >= before);
+
+        let mut entry = Entry::default();
+        entry.set_custom_data("entry-key", Value::Unprotected("entry".to_string()));
+        assert!(entry.get_custom_data().items["entry-key"].last_modification_time.unwrap() >= before);
+        assert!(entry.remove_custom_data("entry-key").is_some());
+        assert!(entry.remove_custom_data("entry-key").is_none());
+
+        let mut group = Group::new("group");
+        group.set_custom_data("group-key", Value::Unprotected("group".to_string()));
+        assert!(group.get_custom_data().items["group-key"].last_modification_time.unwrap() >= before);
+        assert!(db.meta.remove_custom_data("meta-key").is_some());
+        assert!(db.meta.custom_data.items.is_empty());
+    }
+
+    #[test]
+    fn test_meta_custom_data() {
+        use crate::db::{Times, Value};
+
+        let before = Times::now();
+        let mut db = Database::new(Default::default());
+        assert!(db.meta.get_custom_data("KPXC_DECRYPTION_TIME_PREFERENCE").is_none());
+
+        db.meta
+            .set_custom_data("KPXC_DECRYPTION_TIME_PREFERENCE", Value::Unprotected("1000".to_string()));
+        assert_eq!(
+            db.meta.get_custom_data("KPXC_DECRYPTION_TIME_PREFERENCE"),
+            Some(&Value::Unprotected("1000".to_string()))
+        );
+        let item = &db.meta.custom_data.items["KPXC_DECRYPTION_TIME_PREFERENCE"];
+        assert!(item.last_modification_time.is_some_and(|time| time >= before));
+    }
+
+    #[test]
+    fn test_bulk_insert() -> Result<()> {
+        use crate::db::{group_get_children, with_node, Entry, Node};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let entries = (0..500)
+            .map(|i| {
+                let mut entry = Entry::default();
+                entry.set_title(Some(&format!("Entry_{i}")));
+                entry
+            })
+            .collect();
+        db.bulk_insert(root_uuid, entries)?;
+        assert!(db.bulk_insert(uuid::Uuid::new_v4(), vec![Entry::default()]).is_err());
+
+        let children = group_get_children(&db.root).unwrap();
+        assert_eq!(children.len(), 500);
+        let creation_time = |node| with_node::<Entry, _, _>(node, |e| e.get_times().get_creation()).unwrap();
+        let first = creation_time(&children[0]);
+        assert!(first.is_some());
+        for (i, child) in children.iter().enumerate() {
+            assert_eq!(creation_time(child), first);
+            assert_eq!(child.borrow().get_parent(), Some(root_uuid));
+            assert_eq!(child.borrow().get_title(), Some(format!("Entry_{i}").as_str()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_missing_field() -> Result<()> {
+        use crate::db::{with_node_mut, Entry};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+
+        let complete = db.create_new_entry(root_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&complete, |e| {
+            e.set_password(Some("secret"));
+            e.set_url(Some("https://example.com"));
+        });
+        let empty_password = db.create_new_entry(root_uuid, 1)?;
+        with_node_mut::<Entry, _, _>(&empty_password, |e| {
+            e.set_password(Some(""));
+            e.set_url(Some("https://example.com"));
+        });
+        let missing_url = db.create_new_entry(root_uuid, 2)?;
+        with_node_mut::<Entry, _, _>(&missing_url, |e| e.set_password(Some("secret")));
+        let recycled = db.create_new_entry(root_uuid, 3)?;
+        let recycled_uuid = recycled.borrow().get_uuid();
+        db.remove_node_by_uuid(recycled_uuid)?;
+
+        assert_eq!(
+            node_uuids(db.entries_missing_field("Password")),
+            vec![empty_password.borrow().get_uuid()]
+        );
+        assert_eq!(node_uuids(db.entries_missing_field("URL")), vec![missing_url.borrow().get_uuid()]);
+        assert_eq!(db.entries_missing_field("Title").len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recently_accessed() -> Result<()> {
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+
+        let mut entries = Vec::new();
+        for (parent, access) in [
+            (root_uuid, Some(2000)),
+            (group_uuid, Some(5000)),
+            (root_uuid, None),
+            (group_uuid, Some(3000)),
+        ] {
+            let entry = db.create_new_entry(parent, 0)?;
+            entry.borrow_mut().get_times_mut().set_last_access(access.map(at));
+            entries.push(entry.borrow().get_uuid());
+        }
+        let recycled = db.create_new_entry(root_uuid, 0)?;
+        recycled.borrow_mut().get_times_mut().set_last_access(Some(at(9000)));
+        let recycled_uuid = recycled.borrow().get_uuid();
+        db.remove_node_by_uuid(recycled_uuid)?;
+
+        assert_eq!(
+            node_uuids(db.recently_accessed(10)),
+            vec![entries[1], entries[3], entries[0], entries[2]]
+        );
+        assert_eq!(node_uuids(db.recently_accessed(2)), vec![entries[1], entries[3]]);
+        assert!(db.recently_accessed(0).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_with_version() {
+        use crate::config::{DatabaseVersion, InnerCipherConfig, KdfConfig};
+
+        let db = Database::new_with_version(DatabaseVersion::KDB3(1));
+        assert_eq!(db.config.version, DatabaseVersion::KDB3(1));
+        assert_eq!(db.config.inner_cipher_config, InnerCipherConfig::Salsa20);
+        assert!(matches!(db.config.kdf_config, KdfConfig::Aes { .. }));
+
+        let db = Database::new_with_version(DatabaseVersion::KDB4(1));
+        assert_eq!(db.config.version, DatabaseVersion::KDB4(1));
+        assert_eq!(db.config.inner_cipher_config, InnerCipherConfig::ChaCha20);
+    }
+
+    #[test]
+    fn test_remove_group_records_descendants() -> Result<()> {
+        let mut db = Database::new(Default::default());
+        db.set_recycle_bin_enabled(false);
+        let root_uuid = db.root.borrow().get_uuid();
+        let kept = db.create_new_entry(root_uuid, 0)?.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 1)?.borrow().get_uuid();
+        let entry = db.create_new_entry(group, 0)?.borrow().get_uuid();
+        let subgroup = db.create_new_group(group, 1)?.borrow().get_uuid();
+        let nested_entry = db.create_new_entry(subgroup, 0)?.borrow().get_uuid();
+
+        db.remove_node_by_uuid(group)?;
+        let deleted: Vec<_> = db.deleted_objects.objects.iter().map(|o| o.uuid).collect();
+        assert_eq!(deleted.len(), 4);
+        for uuid in [group, entry, subgroup, nested_entry] {
+            assert!(deleted.contains(&uuid));
+        }
+        assert!(!deleted.contains(&kept));
+        Ok(())
+    }
+
+    #[test]
+    fn test_recycling_does_not_record_deleted() -> Result<()> {
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?.borrow().get_uuid();
+        let entry = db.create_new_entry(group, 0)?.borrow().get_uuid();
+
+        db.remove_node_by_uuid(group)?;
+        assert!(db.node_is_in_recycle_bin(entry));
+        assert!(db.deleted_objects.objects.is_empty());
+
+        // removing it from the recycle bin deletes it for good
+        db.remove_node_by_uuid(group)?;
+        let deleted: Vec<_> = db.deleted_objects.objects.iter().map(|o| o.uuid).collect();
+        assert_eq!(deleted, vec![group, entry]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_honors_deleted_objects() -> Result<()> {
+        use crate::db::{with_node_mut, Entry, Node};
+
+        let mut ours = Database::new(Default::default());
+        ours.set_recycle_bin_enabled(false);
+        let root_uuid = ours.root.borrow().get_uuid();
+        let deleted_here = ours.create_new_entry(root_uuid, 0)?.borrow().get_uuid();
+        let edited_here = ours.create_new_entry(root_uuid, 1)?;
+        let group = ours.create_new_group(root_uuid, 2)?.borrow().get_uuid();
+        let in_group = ours.create_new_entry(group, 0)?.borrow().get_uuid();
+
+        let mut theirs = ours.clone();
+        theirs.root = ours.root.borrow().duplicate().into();
+        let edited_here_uuid = edited_here.borrow().get_uuid();
+        theirs.remove_node_by_uuid(group)?;
+        theirs.remove_node_by_uuid(edited_here_uuid)?;
+        ours.remove_node_by_uuid(deleted_here)?;
+        // modified after it was deleted in the other database, so it stays
+        with_node_mut::<Entry, _, _>(&edited_here, |e| {
+            e.set_title(Some("Edited"));
+            e.times.set_last_modification(Some("2100-01-01T00:00:00".parse().unwrap()));
+        });
+
+        ours.merge(&theirs)?;
+        assert!(ours.search_node_by_uuid(deleted_here).is_none());
+        assert!(ours.search_node_by_uuid(group).is_none());
+        assert!(ours.search_node_by_uuid(in_group).is_none());
+        assert!(ours.search_node_by_uuid(edited_here_uuid).is_some());
+
+        let deleted: Vec<_> = ours.deleted_objects.objects.iter().map(|o| o.uuid).collect();
+        for uuid in [deleted_here, group, in_group] {
+            assert!(deleted.contains(&uuid));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_attachment_without_identifier() -> Result<()> {
+        use crate::{
+            config::DatabaseVersion,
+            db::{AttachmentRef, BinaryAttachment, HeaderAttachment},
+        };
+
+        let reference = AttachmentRef {
+            name: "file.bin".to_string(),
+            identifier: "0".to_string(),
+        };
+        let unnamed = BinaryAttachment {
+            identifier: None,
+            compressed: false,
+            content: b"from meta".to_vec(),
+        };
+
+        let mut db = Database::new(Default::default());
+        db.meta.binaries.binaries.push(unnamed.clone());
+        db.header_attachments.push(HeaderAttachment {
+            flags: 0,
+            content: b"from header".to_vec(),
+        });
+        assert_eq!(db.resolve_attachment(&reference)?.as_deref(), Some(&b"from header"[..]));
+
+        let mut db = Database::new_with_version(DatabaseVersion::KDB3(1));
+        db.meta.binaries.binaries.push(unnamed);
+        assert_eq!(db.resolve_attachment(&reference)?, None);
+
+        // a binary that does not decompress is an error, not a missing attachment
+        db.meta.binaries.binaries.push(BinaryAttachment {
+            identifier: Some("0".to_string()),
+            compressed: true,
+            content: b"not gzip".to_vec(),
+        });
+        assert!(db.resolve_attachment(&reference).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_using_attachment() -> Result<()> {
+        use crate::db::{with_node_mut, AttachmentRef, Entry};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+
+        let mut entries = Vec::new();
+        for index in ["1", "0", "1"] {
+            let entry = db.create_new_entry(root_uuid, entries.len())?;
+            with_node_mut::<Entry, _, _>(&entry, |e| {
+                e.attachments.push(AttachmentRef {
+                    name: format!("attachment {index}"),
+                    identifier: index.to_string(),
+                })
+            });
+            entries.push(entry.borrow().get_uuid());
+        }
+
+        assert_eq!(node_uuids(db.entries_using_attachment(1)), vec![entries[0], entries[2]]);
+        assert_eq!(node_uuids(db.entries_using_attachment(0)), vec![entries[1]]);
+        assert!(db.entries_using_attachment(2).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune_unused_attachments() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, AttachmentRef, Entry, HeaderAttachment};
+
+        let mut db = Database::new(Default::default());
+        for content in ["orphaned", "referenced"] {
+            db.header_attachments.push(HeaderAttachment {
+                flags: 0,
+                content: content.as_bytes().to_vec(),
+            });
+        }
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.attachments.push(AttachmentRef {
+                name: "file.txt".to_string(),
+                identifier: "1".to_string(),
+            })
+        });
+
+        assert_eq!(db.prune_unused_attachments(), 1);
+        assert_eq!(db.header_attachments.len(), 1);
+        let attachment = with_node::<Entry, _, _>(&entry, |e| e.attachments[0].clone()).unwrap();
+        assert_eq!(attachment.identifier, "0");
+        assert_eq!(db.resolve_attachment(&attachment)?.as_deref(), Some(&b"referenced"[..]));
+
+        assert_eq!(db.prune_unused_attachments(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<()> {
+        use crate::db::{rc_refcell_node, with_node_mut, Entry, Group, Node};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        with_node_mut::<Group, _, _>(&group, |g| g.set_title(Some("Internet")));
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.set_title(Some("Mail"));
+            e.set_username(Some("jdoe"));
+            e.set_password(Some("secret"));
+        });
+
+        let snapshot = db.snapshot()?;
+        with_node_mut::<Entry, _, _>(&entry, |e| e.set_title(Some("Changed")));
+
+        let snapshot = std::thread::spawn(move || snapshot).join().unwrap();
+        assert_eq!(snapshot.root.groups.len(), 1);
+        assert!(snapshot.root.entries.is_empty());
+        let group = &snapshot.root.groups[0];
+        assert_eq!(group.get_title(), Some("Internet"));
+        let entry = &group.entries[0];
+        assert_eq!(entry.get_title(), Some("Mail"));
+        assert_eq!(entry.get_username(), Some("jdoe"));
+        assert_eq!(entry.get_password(), Some("secret"));
+
+        let mut db = db;
+        db.root = rc_refcell_node(Entry::default()).into();
+        assert!(db.snapshot().is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "threadsafe")]
+    #[test]
+    fn test_sync_database() -> Result<()> {
+        use crate::db::{with_node_mut, Entry, Node, SyncDatabase};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| e.set_title(Some("first")));
+
+        let shared = SyncDatabase::new(&db)?;
+        let reader = shared.clone();
+        let title = std::thread::spawn(move || reader.read().root.entries[0].get_title().map(str::to_string));
+        assert_eq!(title.join().unwrap().as_deref(), Some("first"));
+
+        with_node_mut::<Entry, _, _>(&entry, |e| e.set_title(Some("second")));
+        assert_eq!(shared.read().root.entries[0].get_title(), Some("first"));
+        shared.update(&db)?;
+        let reader = shared.clone();
+        let title = std::thread::spawn(move || reader.read().root.entries[0].get_title().map(str::to_string));
+        assert_eq!(title.join().unwrap().as_deref(), Some("second"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_field() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, Value};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+
+        let mut entries = Vec::new();
+        for fields in [vec!["Login"], vec!["Login", "UserName"], vec!["Title"]] {
+            let entry = db.create_new_entry(root_uuid, entries.len())?;
+            with_node_mut::<Entry, _, _>(&entry, |e| {
+                for field in fields {
+                    e.fields.insert(field.to_string(), Value::Unprotected(format!("{field} value")));
+                }
+            });
+            entries.push(entry);
+        }
+        with_node_mut::<Entry, _, _>(&entries[0], |e| {
+            e.fields.insert("Login".to_string(), Value::Protected("secret".as_bytes().into()));
+        });
+
+        assert_eq!(db.rename_field("Login", "UserName", false)?, 1);
+        with_node::<Entry, _, _>(&entries[0], |e| {
+            assert!(!e.fields.contains_key("Login"));
+            assert_eq!(e.fields.get("UserName"), Some(&Value::Protected("secret".as_bytes().into())));
+            assert_eq!(e.get_history().as_ref().unwrap().get_entries().len(), 1);
+        });
+        with_node::<Entry, _, _>(&entries[1], |e| {
+            assert_eq!(e.get("Login"), Some("Login value"));
+            assert_eq!(e.get("UserName"), Some("UserName value"));
+        });
+
+        // standard fields are never overwritten, and invalid names are rejected up front
+        let before = db.root.borrow().duplicate();
+        assert!(db.rename_field("Login", "UserName", true).is_err());
+        assert!(db.rename_field("Login", "", false).is_err());
+        assert!(db.rename_field("Login", "Bad\0Name", true).is_err());
+        assert!(crate::db::node_is_equals_to(&db.root, &before));
+        with_node::<Entry, _, _>(&entries[1], |e| {
+            assert_eq!(e.get("Login"), Some("Login value"));
+            assert_eq!(e.get("UserName"), Some("UserName value"));
+        });
+
+        with_node_mut::<Entry, _, _>(&entries[1], |e| {
+            e.fields
+                .insert("Account".to_string(), Value::Unprotected("Account value".to_string()));
+        });
+        assert_eq!(db.rename_field("Login", "Account", true)?, 1);
+        with_node::<Entry, _, _>(&entries[1], |e| {
+            assert!(!e.fields.contains_key("Login"));
+            assert_eq!(e.get("Account"), Some("Login value"));
+        });
+        assert_eq!(db.rename_field("Login", "Account", true)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_custom_fields() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, Value};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let custom = db.create_new_entry(root_uuid, 0)?;
+        let standard = db.create_new_entry(root_uuid, 1)?;
+        with_node_mut::<Entry, _, _>(&custom, |e| {
+            e.set_username(Some("jdoe"));
+            e.fields.insert("PIN".to_string(), Value::Protected("1234".as_bytes().into()));
+            e.fields.insert("Account".to_string(), Value::Unprotected("42".to_string()));
+            e.update_history();
+        });
+        with_node_mut::<Entry, _, _>(&standard, |e| e.set_url(Some("https://example.com")));
+
+        assert_eq!(db.strip_custom_fields(), 1);
+        with_node::<Entry, _, _>(&custom, |e| {
+            assert_eq!(e.fields.len(), 1);
+            assert_eq!(e.get_username(), Some("jdoe"));
+            assert_eq!(e.get_history().as_ref().unwrap().get_entries().len(), 2);
+        });
+        with_node::<Entry, _, _>(&standard, |e| assert_eq!(e.get_url(), Some("https://example.com")));
+        assert_eq!(db.strip_custom_fields(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries_matching_tags() -> Result<()> {
+        use crate::db::{with_node_mut, Entry, TagMatch};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+
+        let mut entries = Vec::new();
+        for tags in [vec!["work", "mail"], vec!["work"], vec!["mail", "private"], vec![]] {
+            let entry = db.create_new_entry(root_uuid, entries.len())?;
+            with_node_mut::<Entry, _, _>(&entry, |e| e.get_tags_mut().extend(tags.iter().map(|t| t.to_string())));
+            entries.push(entry.borrow().get_uuid());
+        }
+
+        assert_eq!(
+            node_uuids(db.entries_matching_tags(&["work", "private"], TagMatch::Any)),
+            vec![entries[0], entries[1], entries[2]]
+        );
+        assert_eq!(
+            node_uuids(db.entries_matching_tags(&["work", "mail"], TagMatch::All)),
+            vec![entries[0]]
+        );
+        assert_eq!(
+            node_uuids(db.entries_matching_tags(&["mail"], TagMatch::All)),
+            vec![entries[0], entries[2]]
+        );
+        assert!(db.entries_matching_tags(&["work", "private"], TagMatch::All).is_empty());
+        assert!(db.entries_matching_tags(&[], TagMatch::Any).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_selected_group() -> Result<()> {
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group_uuid = db.create_new_group(root_uuid, 0)?.borrow().get_uuid();
+        let entry_uuid = db.create_new_entry(root_uuid, 1)?.borrow().get_uuid();
+
+        db.set_last_selected_group(group_uuid)?;
+        db.set_last_top_visible_group(root_uuid)?;
+        assert_eq!(db.meta.last_selected_group, Some(group_uuid));
+        assert_eq!(db.meta.last_top_visible_group, Some(root_uuid));
+
+        assert!(db.set_last_selected_group(uuid::Uuid::new_v4()).is_err());
+        assert!(db.set_last_top_visible_group(entry_uuid).is_err());
+        assert_eq!(db.meta.last_selected_group, Some(group_uuid));
+        assert_eq!(db.meta.last_top_visible_group, Some(root_uuid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten() -> Result<()> {
+        use crate::db::{group_add_child, rc_refcell_node, Entry, Group, Node};
+
+        let db = Database::new(Default::default());
+        let entry = |title: &str| {
+            let mut entry = Entry::default();
+            entry.set_title(Some(title));
+            rc_refcell_node(entry)
+        };
+        let internet = rc_refcell_node(Group::new("Internet"));
+        let email = rc_refcell_node(Group::new("Email"));
+        group_add_child(&db.root, entry("Top"), 0)?;
+        group_add_child(&db.root, internet.clone(), 1)?;
+        group_add_child(&db.root, entry("Bottom"), 2)?;
+        group_add_child(&internet, email.clone(), 0)?;
+        group_add_child(&internet, entry("Forum"), 1)?;
+        group_add_child(&email, entry("Gmail"), 0)?;
+        group_add_child(&email, entry("Outlook"), 1)?;
+
+        let flat = db.flatten();
+        let titles: Vec<_> = flat
+            .iter()
+            .map(|(path, node)| (path.as_str(), node.borrow().get_title().unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                ("", "Top".to_string()),
+                ("Internet/Email", "Gmail".to_string()),
+                ("Internet/Email", "Outlook".to_string()),
+                ("Internet", "Forum".to_string()),
+                ("", "Bottom".to_string()),
+            ]
+        );
+
+        let keys: Vec<_> = db.flatten_with_title_prefix().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(
+            keys,
+            vec!["Top", "Internet/Email/Gmail", "Internet/Email/Outlook", "Internet/Forum", "Bottom"]
+        );
+
+        let dir = rc_refcell_node(Group::new(r"C:\Users"));
+        group_add_child(&internet, dir.clone(), 2)?;
+        group_add_child(&dir, entry("A/B"), 0)?;
+        let (key, node) = db.flatten_with_title_prefix().into_iter().nth(4).unwrap();
+        assert_eq!(key, r"Internet/C:\\Users/A\/B");
+        assert!(std::rc::Rc::ptr_eq(&db.get_by_path_str(&key).unwrap(), &node));
+        assert_eq!(db.flatten()[4].0, r"Internet/C:\\Users");
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_empty_groups() -> Result<()> {
+        use crate::db::{group_get_children, search_node_by_uuid};
+
+        let mut db = Database::new(Default::default());
+        db.set_recycle_bin_enabled(true);
+        let root_uuid = db.root.borrow().get_uuid();
+        let recycle_bin = db.create_recycle_bin()?.borrow().get_uuid();
+
+        // root
+        //  ├ empty
+        //  │  └ empty child
+        //  │     └ empty grandchild
+        //  ├ full
+        //  │  ├ empty
+        //  │  └ entry
+        //  └ recycle bin
+        let empty = db.create_new_group(root_uuid, 0)?.borrow().get_uuid();
+        let empty_child = db.create_new_group(empty, 0)?.borrow().get_uuid();
+        let empty_grandchild = db.create_new_group(empty_child, 0)?.borrow().get_uuid();
+        let full = db.create_new_group(root_uuid, 1)?.borrow().get_uuid();
+        let empty_in_full = db.create_new_group(full, 0)?.borrow().get_uuid();
+        db.create_new_entry(full, 1)?;
+
+        assert_eq!(db.remove_empty_groups(true), 4);
+        for uuid in [empty, empty_child, empty_grandchild, empty_in_full] {
+            assert!(search_node_by_uuid(&db.root, uuid).is_none());
+            assert!(db.deleted_objects.objects.iter().any(|o| o.uuid == uuid));
+        }
+        assert!(search_node_by_uuid(&db.root, full).is_some());
+        assert_eq!(group_get_children(&db.root).unwrap().len(), 2);
+        assert!(db.get_recycle_bin().is_some());
+
+        assert_eq!(db.remove_empty_groups(true), 0);
+        db.meta.recyclebin_changed = None;
+        assert_eq!(db.remove_empty_groups(false), 1);
+        assert!(search_node_by_uuid(&db.root, recycle_bin).is_none());
+        assert_eq!(db.root.borrow().get_uuid(), root_uuid);
+        assert!(db.meta.recyclebin_uuid.is_none());
+        assert!(db.meta.recyclebin_changed.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_effective_icon() -> Result<()> {
+        use crate::db::{with_node_mut, EffectiveIcon, Entry, Icon, IconId};
+        use uuid::Uuid;
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+
+        assert_eq!(db.effective_icon(&entry), EffectiveIcon::Standard(IconId::KEY));
+
+        entry.borrow_mut().set_icon_id(None);
+        let inherited = EffectiveIcon::Inherited {
+            group: group_uuid,
+            icon: Box::new(EffectiveIcon::Standard(IconId::FOLDER)),
+        };
+        assert_eq!(db.effective_icon(&entry), inherited);
+
+        let icon_uuid = Uuid::new_v4();
+        db.meta.custom_icons.icons.push(Icon {
+            uuid: icon_uuid,
+            data: vec![1, 2, 3],
+        });
+        with_node_mut::<Entry, _, _>(&entry, |entry| entry.custom_icon_uuid = Some(icon_uuid));
+        assert_eq!(db.effective_icon(&entry), EffectiveIcon::Custom(vec![1, 2, 3]));
+
+        // a custom icon missing from the metadata is skipped
+        with_node_mut::<Entry, _, _>(&entry, |entry| entry.custom_icon_uuid = Some(Uuid::new_v4()));
+        assert_eq!(db.effective_icon(&entry), inherited);
+
+        for node in [&db.root, &group] {
+            node.borrow_mut().set_icon_id(None);
+        }
+        assert_eq!(db.effective_icon(&entry), EffectiveIcon::Standard(IconId::KEY));
+        assert_eq!(db.effective_icon(&group), EffectiveIcon::Standard(IconId::FOLDER));
+        Ok(())
+    }
+
+    #[test]
+    fn test_inherited_searching_and_autotype() -> Result<()> {
+        use crate::db::{with_node_mut, Group};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let enabled = db.create_new_group(root_uuid, 0)?;
+        let disabled = db.create_new_group(root_uuid, 1)?;
+        let disabled_uuid = disabled.borrow().get_uuid();
+        let nested = db.create_new_group(disabled_uuid, 0)?;
+        let entry = db.create_new_entry(disabled_uuid, 1)?;
+
+        with_node_mut::<Group, _, _>(&db.root, |root| root.set_searching_enabled(Some(true)));
+        with_node_mut::<Group, _, _>(&disabled, |group| group.set_searching_enabled(Some(false)));
+        with_node_mut::<Group, _, _>(&nested, |group| group.set_searching_enabled(None));
+
+        assert!(db.is_searching_enabled(&db.root));
+        assert!(db.is_searching_enabled(&enabled));
+        assert!(!db.is_searching_enabled(&disabled));
+        assert!(!db.is_searching_enabled(&nested));
+        assert!(!db.is_searching_enabled(&entry));
+
+        let disabled_groups = db.searching_disabled_groups();
+        assert_eq!(disabled_groups.len(), 1);
+        assert!(std::rc::Rc::ptr_eq(&disabled_groups[0], &disabled));
+
+        // a setting on the root applies to the whole database unless overridden, and resolving
+        // the auto-type sequence agrees with it
+        let sequence = |db: &Database| db.autotype_sequence_for_window(&entry, "Terminal");
+        assert!(db.is_autotype_enabled(&entry));
+        assert!(sequence(&db).is_some());
+        with_node_mut::<Group, _, _>(&db.root, |root| root.set_autotype_enabled(Some(false)));
+        assert!(!db.is_autotype_enabled(&entry));
+        assert!(sequence(&db).is_none());
+        assert!(!db.is_autotype_enabled(&nested));
+        with_node_mut::<Group, _, _>(&disabled, |group| group.set_autotype_enabled(Some(true)));
+        assert!(db.is_autotype_enabled(&entry));
+        assert!(sequence(&db).is_some());
+        assert!(!db.is_autotype_enabled(&enabled));
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_latest_change() -> Result<()> {
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        db.root.borrow_mut().get_times_mut().set_last_modification(Some(at(1000)));
+
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        group.borrow_mut().get_times_mut().set_last_modification(Some(at(2000)));
+        let subgroup = db.create_new_group(group_uuid, 0)?;
+        subgroup.borrow_mut().get_times_mut().set_last_modification(Some(at(1500)));
+        let subgroup_uuid = subgroup.borrow().get_uuid();
+        for (parent, secs) in [(group_uuid, 3000), (group_uuid, 2500), (subgroup_uuid, 5000)] {
+            let entry = db.create_new_entry(parent, 0)?;
+            entry.borrow_mut().get_times_mut().set_last_modification(Some(at(secs)));
+        }
+        let newest_outside = db.create_new_entry(root_uuid, 1)?;
+        newest_outside.borrow_mut().get_times_mut().set_last_modification(Some(at(9000)));
+
+        assert_eq!(db.group_latest_change(subgroup_uuid), Some(at(5000)));
+        assert_eq!(db.group_latest_change(group_uuid), Some(at(5000)));
+        assert_eq!(db.group_latest_change(root_uuid), Some(at(9000)));
+        assert_eq!(db.group_latest_change(newest_outside.borrow().get_uuid()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_passwords() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let mut uuids = Vec::new();
+        for _ in 0..3 {
+            let entry = db.create_new_entry(root_uuid, 0)?;
+            with_node_mut::<Entry, _, _>(&entry, |e| {
+                e.set_password(Some("leaked"));
+                e.update_history();
+            });
+            uuids.push(entry.borrow().get_uuid());
+        }
+        let history_len = |db: &Database, uuid| {
+            let entry = db.search_node_by_uuid(uuid).unwrap();
+            with_node::<Entry, _, _>(&entry, |e| e.get_history().as_ref().unwrap().get_entries().len()).unwrap()
+        };
+        let password = |db: &Database, uuid| {
+            let entry = db.search_node_by_uuid(uuid).unwrap();
+            with_node::<Entry, _, _>(&entry, |e| e.get_password().unwrap().to_string()).unwrap()
+        };
+
+        assert!(db.replace_passwords(&[uuids[0], uuid::Uuid::new_v4()], "new").is_err());
+        assert_eq!(password(&db, uuids[0]), "leaked");
+
+        assert_eq!(db.replace_passwords(&uuids[..2], "new")?, 2);
+        assert_eq!(history_len(&db, uuids[0]), 2);
+        assert_eq!(history_len(&db, uuids[1]), 2);
+        assert_eq!(history_len(&db, uuids[2]), 1);
+        assert_eq!(password(&db, uuids[0]), "new");
+        assert_eq!(password(&db, uuids[1]), "new");
+        assert_eq!(password(&db, uuids[2]), "leaked");
+
+        assert_eq!(db.replace_passwords(&uuids[..2], "new")?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_groups_iter() -> Result<()> {
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        for _ in 0..2 {
+            let group = db.create_new_group(root_uuid, 0)?.borrow().get_uuid();
+            for _ in 0..3 {
+                let subgroup = db.create_new_group(group, 0)?.borrow().get_uuid();
+                db.create_new_entry(subgroup, 0)?;
+            }
+            db.create_new_entry(group, 0)?;
+        }
+
+        assert_eq!(db.groups_iter().count(), 1 + 2 + 2 * 3);
+        assert_eq!(db.groups_iter().next().unwrap().borrow().get_uuid(), root_uuid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_subsecond_times() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, History};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let precise = chrono::DateTime::from_timestamp(1_700_000_000, 999_000_000).unwrap().naive_utc();
+        let floored = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc();
+
+        db.root.borrow_mut().get_times_mut().set_creation(Some(precise));
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.times.set_last_modification(Some(precise));
+            let mut history = History::default();
+            history.add_entry(e.clone());
+            e.history = Some(history);
+        });
+
+        db.truncate_subsecond_times();
+        assert_eq!(db.root.borrow().get_times().get_creation(), Some(floored));
+        assert_eq!(entry.borrow().get_times().get_last_modification(), Some(floored));
+        let old_modification = with_node::<Entry, _, _>(&entry, |e| e.history.as_ref().unwrap().entries[0].times.get_last_modification());
+        assert_eq!(old_modification.unwrap(), Some(floored));
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_root_node() {
+        use crate::error::Error;
+
+        for enabled in [true, false] {
+            let mut db = Database::new(Default::default());
+            db.set_recycle_bin_enabled(enabled);
+            let root_uuid = db.root.borrow().get_uuid();
+            let meta = db.meta.clone();
+
+            assert!(matches!(db.remove_node_by_uuid(root_uuid), Err(Error::CannotRemoveRoot)));
+            assert!(db.deleted_objects.objects.is_empty());
+            assert!(db.get_recycle_bin().is_none());
+            assert_eq!(db.meta, meta);
+            assert_eq!(db.root.borrow().get_uuid(), root_uuid);
+        }
+    }
+
+    #[test]
+    fn test_merge_group_by_path() -> Result<()> {
+        use crate::db::{group_get_children, with_node, with_node_mut, Entry, Group, Node};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        for title in ["Sibling", "Shared"] {
+            let group = db.create_new_group(root_uuid, 0)?;
+            group.borrow_mut().set_title(Some(title));
+            let group_uuid = group.borrow().get_uuid();
+            let sub = db.create_new_group(group_uuid, 0)?;
+            sub.borrow_mut().set_title(Some("Sub"));
+        }
+
+        let mut other = Database::new(Default::default());
+        other.root = db.root.borrow().duplicate().into();
+
+        let get = |db: &Database, path: &[&str]| with_node::<Group, _, _>(&db.root, |g| g.get(path)).flatten().unwrap();
+        for path in [["Shared", "Sub"], ["Sibling", "Sub"]] {
+            let parent = get(&other, &path).borrow().get_uuid();
+            let entry = other.create_new_entry(parent, 0)?;
+            with_node_mut::<Entry, _, _>(&entry, |e| e.set_title(Some(path[0])));
+        }
+
+        let log = db.merge_group_by_path(&other, &["Shared", "Sub"])?;
+        assert_eq!(log.events.len(), 1);
+        assert_eq!(group_get_children(&get(&db, &["Shared", "Sub"])).unwrap().len(), 1);
+        assert!(group_get_children(&get(&db, &["Sibling", "Sub"])).unwrap().is_empty());
+
+        assert!(db.merge_group_by_path(&other, &["Missing"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_html() -> Result<()> {
+        use crate::db::{with_node_mut, Entry, Node};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        group.borrow_mut().set_title(Some("Mail & Web"));
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.set_title(Some("Webmail"));
+            e.set_username(Some("<script>alert(1)</script>"));
+            e.set_password(Some("secret"));
+        });
+
+        let mut html = Vec::new();
+        db.export_html(&mut html, false)?;
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains("<td>Webmail</td>"));
+        assert!(html.contains("Mail &amp; Web"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("secret"));
+
+        let mut html = Vec::new();
+        db.export_html(&mut html, true)?;
+        assert!(String::from_utf8(html).unwrap().contains("<td>secret</td>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_entry_from_template() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, Node};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        assert!(db.template_group().is_none());
+
+        let templates = db.create_new_group(root_uuid, 0)?;
+        let templates_uuid = templates.borrow().get_uuid();
+        db.meta.entry_templates_group = Some(templates_uuid);
+        assert_eq!(db.template_group().unwrap().borrow().get_uuid(), templates_uuid);
+
+        let template = db.create_new_entry(templates_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&template, |e| {
+            e.set_title(Some("Server"));
+            e.set_username(Some("admin"));
+            e.tags.push("ssh".to_string());
+            e.update_history();
+        });
+        let template_uuid = template.borrow().get_uuid();
+
+        let entry = db.create_entry_from_template(template_uuid, root_uuid)?;
+        assert_ne!(entry.borrow().get_uuid(), template_uuid);
+        assert_eq!(entry.borrow().get_parent(), Some(root_uuid));
+        with_node::<Entry, _, _>(&entry, |e| {
+            assert_eq!(e.get_title(), Some("Server"));
+            assert_eq!(e.get_username(), Some("admin"));
+            assert_eq!(e.get_tags(), &vec!["ssh".to_string()]);
+            assert!(e.history.is_none());
+        });
+
+        let err = |result: Result<_>| result.unwrap_err().to_string();
+        assert_eq!(
+            err(db.create_entry_from_template(uuid::Uuid::new_v4(), root_uuid)),
+            "String error: Template entry not found"
+        );
+        assert_eq!(
+            err(db.create_entry_from_template(root_uuid, root_uuid)),
+            "String error: Template is not an entry"
+        );
+        assert_eq!(
+            err(db.create_entry_from_template(template_uuid, template_uuid)),
+            "String error: Target group not found"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_node_uuid() -> Result<()> {
+        use crate::db::{group_get_children, with_node, with_node_mut, Entry, Node};
+        use uuid::Uuid;
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let old_uuid = group.borrow().get_uuid();
+        db.create_new_entry(old_uuid, 0)?;
+        db.create_new_group(old_uuid, 1)?;
+        db.meta.last_selected_group = Some(old_uuid);
+
+        assert!(db.set_node_uuid(old_uuid, root_uuid).is_err());
+        assert!(db.set_node_uuid(Uuid::new_v4(), Uuid::new_v4()).is_err());
+
+        let new_uuid = Uuid::new_v4();
+        db.set_node_uuid(old_uuid, new_uuid)?;
+        assert!(db.search_node_by_uuid(old_uuid).is_none());
+        assert_eq!(group.borrow().get_uuid(), new_uuid);
+        let children = group_get_children(&group).unwrap();
+        assert_eq!(children.len(), 2);
+        assert!(children.iter().all(|c| c.borrow().get_parent() == Some(new_uuid)));
+        assert_eq!(db.meta.last_selected_group, Some(new_uuid));
+
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        let old_entry_uuid = entry.borrow().get_uuid();
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.set_title(Some("first"));
+            e.update_history();
+            e.set_title(Some("second"));
+            e.update_history();
+        });
+        let new_entry_uuid = Uuid::new_v4();
+        db.set_node_uuid(old_entry_uuid, new_entry_uuid)?;
+        with_node::<Entry, _, _>(&entry, |e| {
+            let history = e.history.as_ref().unwrap();
+            assert_eq!(history.entries.len(), 2);
+            assert!(history.entries.iter().all(|version| version.uuid == new_entry_uuid));
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_and_remove_tags() -> Result<()> {
+        use crate::db::{with_node, Entry};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let first = db.create_new_entry(root_uuid, 0)?;
+        let second = db.create_new_entry(root_uuid, 1)?;
+        let uuids = [first.borrow().get_uuid(), second.borrow().get_uuid(), root_uuid];
+
+        assert_eq!(db.add_tag_to(&uuids, "work"), 2);
+        assert_eq!(db.add_tag_to(&uuids, "work"), 0);
+        assert_eq!(db.remove_tag_from(&uuids[..1], "work"), 1);
+        assert_eq!(db.remove_tag_from(&uuids[..1], "work"), 0);
+
+        let tags = |node| with_node::<Entry, _, _>(node, |e| e.get_tags().clone()).unwrap();
+        assert!(tags(&first).is_empty());
+        assert_eq!(tags(&second), vec!["work".to_string()]);
+        let history_len = |node| with_node::<Entry, _, _>(node, |e| e.history.as_ref().map_or(0, |h| h.entries.len())).unwrap();
+        assert_eq!(history_len(&first), 2);
+        assert_eq!(history_len(&second), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_preview() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, History, MergeLog, Node};
+
+        fn edit(entry: &crate::db::NodePtr, title: &str, modified: &str) {
+            with_node_mut::<Entry, _, _>(entry, |e| {
+                e.set_title(Some(title));
+                e.times.set_last_modification(Some(modified.parse().unwrap()));
+                let mut history = e.history.take().unwrap_or_default();
+                history.add_entry(e.clone());
+                e.history = Some(history);
+            });
+        }
+
+        let mut db = Database::new(Default::default());
+        db.set_recycle_bin_enabled(false);
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        let untouched = db.create_new_entry(root_uuid, 1)?;
+        let deleted_here = db.create_new_entry(root_uuid, 2)?;
+        edit(&entry, "base", "2020-01-01T00:00:00");
+        edit(&untouched, "untouched", "2020-01-01T00:00:00");
+        edit(&deleted_here, "deleted here", "2020-01-01T00:00:00");
+        let uuid = entry.borrow().get_uuid();
+
+        let mut other = Database::new(Default::default());
+        other.set_recycle_bin_enabled(false);
+        other.root = db.root.borrow().duplicate().into();
+        let theirs = other.search_node_by_uuid(uuid).unwrap();
+
+        // only one side edited, merging fast-forwards
+        edit(&theirs, "theirs", "2020-01-03T00:00:00");
+        let preview = db.merge_preview(&other)?;
+        assert!(preview.conflicts.is_empty());
+        assert_eq!(preview.log.events.len(), 1);
+
+        // both sides edited
+        edit(&entry, "ours", "2020-01-02T00:00:00");
+        let preview = db.merge_preview(&other)?;
+        assert_eq!(preview.conflicts, vec![uuid]);
+        assert_eq!(preview.log.events.len(), 1);
 
-/// Binary attachments stored in a database inner header
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct HeaderAttachment {
-    pub flags: u8,
-    pub content: Vec<u8>,
-}
+        // the preview left this database alone
+        assert_eq!(entry.borrow().get_title(), Some("ours"));
+        let history = with_node::<Entry, _, _>(&entry, |e| e.history.clone())
+            .flatten()
+            .map(|h: History| h.entries.len());
+        assert_eq!(history, Some(2));
 
-/// Elements that have been previously deleted
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct DeletedObjects {
-    pub objects: Vec<DeletedObject>,
-}
+        // deletions on either side are taken into account like by `merge`
+        let untouched_uuid = untouched.borrow().get_uuid();
+        let deleted_here_uuid = deleted_here.borrow().get_uuid();
+        other.remove_node_by_uuid(untouched_uuid)?;
+        db.remove_node_by_uuid(deleted_here_uuid)?;
+        let events = |log: &MergeLog| {
+            log.events
+                .iter()
+                .map(|event| (event.node_uuid, format!("{:?}", event.event_type)))
+                .collect::<Vec<_>>()
+        };
+        let preview = db.merge_preview(&other)?;
+        assert_eq!(
+            events(&preview.log),
+            vec![(uuid, "EntryUpdated".to_string()), (untouched_uuid, "EntryDeleted".to_string())]
+        );
+        assert!(db.search_node_by_uuid(untouched_uuid).is_some());
+        assert_eq!(events(&db.merge(&other)?), events(&preview.log));
+        assert!(db.search_node_by_uuid(untouched_uuid).is_none());
+        assert!(db.search_node_by_uuid(deleted_here_uuid).is_none());
+        Ok(())
+    }
 
-impl DeletedObjects {
-    pub fn add(&mut self, uuid: Uuid) {
-        let deletion_time = Times::now();
-        if let Some(item) = self.objects.iter_mut().find(|item| item.uuid == uuid) {
-            item.deletion_time = deletion_time;
-        } else {
-            self.objects.push(DeletedObject { uuid, deletion_time });
-        }
+    #[test]
+    fn test_validate_times() {
+        use crate::db::Times;
+
+        let mut times = Times::new();
+        assert!(times.validate().is_ok());
+
+        let earlier = Times::now() - chrono::Duration::days(1);
+        times.set_last_modification(Some(earlier));
+        assert!(times.validate().is_err());
+
+        let mut times = Times::new();
+        times.set_expiry_time(Some(earlier));
+        assert!(times.validate().is_ok());
+        times.set_expires(true);
+        assert!(times.validate().is_err());
     }
-}
 
-/// A reference to a deleted element
-#[derive(Debug, Default, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-pub struct DeletedObject {
-    pub uuid: Uuid,
-    pub deletion_time: NaiveDateTime,
-}
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_save_validates_times() -> Result<()> {
+        use crate::{config::DumpOptions, db::with_node_mut, error::DatabaseSaveError};
 
-/// A color value for the Database, or Entry
-#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
-pub struct Color {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-}
+        let db = Database::new(DatabaseConfig::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        let earlier = crate::db::Times::now() - chrono::Duration::days(1);
+        entry.borrow_mut().get_times_mut().set_last_modification(Some(earlier));
 
-#[cfg(feature = "serialization")]
-impl serde::Serialize for Color {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(&self.to_string())
+        let key = DatabaseKey::new().with_password("testing");
+        let options = DumpOptions {
+            validate_times: true,
+            ..Default::default()
+        };
+        let result = db.save_with_options(&mut Vec::new(), key.clone(), &options);
+        assert!(matches!(result, Err(DatabaseSaveError::InvalidTimes { uuid, .. }) if uuid == entry.borrow().get_uuid()));
+        db.save(&mut Vec::new(), key.clone())?;
+
+        with_node_mut::<Entry, _, _>(&entry, |e| e.set_all_times_now());
+        db.save_with_options(&mut Vec::new(), key, &options)?;
+        Ok(())
     }
-}
 
-impl FromStr for Color {
-    type Err = ParseColorError;
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_save_validates_field_names() -> Result<()> {
+        use crate::{
+            db::{with_node_mut, Value},
+            error::DatabaseSaveError,
+        };
 
-    fn from_str(str: &str) -> Result<Self, Self::Err> {
-        if !str.starts_with('#') || str.len() != 7 {
-            return Err(ParseColorError(str.to_string()));
+        let db = Database::new(DatabaseConfig::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.fields
+                .insert("Custom\0Field".to_string(), Value::Unprotected("value".to_string()));
+        });
+
+        let key = DatabaseKey::new().with_password("testing");
+        let result = db.save(&mut Vec::new(), key.clone());
+        assert!(matches!(result, Err(DatabaseSaveError::InvalidFieldName { uuid, .. }) if uuid == entry.borrow().get_uuid()));
+
+        with_node_mut::<Entry, _, _>(&entry, |e| e.fields.remove("Custom\0Field"));
+        db.save(&mut Vec::new(), key)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_fingerprint() -> Result<()> {
+        use crate::{
+            config::{DatabaseConfig, OuterCipherConfig},
+            db::{with_node_mut, Entry, Node},
+        };
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.set_title(Some("Title"));
+            e.set_password(Some("secret"));
+        });
+        let uuid = entry.borrow().get_uuid();
+
+        // the same content with other encryption settings
+        let mut other = Database::new(DatabaseConfig {
+            outer_cipher_config: OuterCipherConfig::ChaCha20,
+            ..Default::default()
+        });
+        other.meta = db.meta.clone();
+        other.root = db.root.borrow().duplicate().into();
+        assert_eq!(db.content_fingerprint(), other.content_fingerprint());
+
+        // looking at an entry does not change the content
+        let theirs = other.search_node_by_uuid(uuid).unwrap();
+        with_node_mut::<Entry, _, _>(&theirs, |e| {
+            e.times.set_last_access(Some("2000-01-01T00:00:00".parse().unwrap()));
+            e.times.set_usage_count(7);
+        });
+        assert_eq!(db.content_fingerprint(), other.content_fingerprint());
+
+        with_node_mut::<Entry, _, _>(&theirs, |e| e.set_password(Some("changed")));
+        assert_ne!(db.content_fingerprint(), other.content_fingerprint());
+
+        #[cfg(feature = "save_kdbx4")]
+        {
+            let key = DatabaseKey::new().with_password("test");
+            let mut data = Vec::new();
+            db.save(&mut data, key.clone())?;
+            let reopened = Database::open(&mut data.as_slice(), key)?;
+            assert_eq!(db.content_fingerprint(), reopened.content_fingerprint());
         }
 
-        let var = u64::from_str_radix(str.trim_start_matches('#'), 16).map_err(|_e| ParseColorError(str.to_string()))?;
+        Ok(())
+    }
 
-        let r = ((var >> 16) & 0xff) as u8;
-        let g = ((var >> 8) & 0xff) as u8;
-        let b = (var & 0xff) as u8;
+    #[test]
+    fn test_audit_report() -> Result<()> {
+        use crate::db::{with_node_mut, Entry};
 
-        Ok(Self { r, g, b })
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let add = |password: &str| -> Result<_> {
+            let entry = db.create_new_entry(root_uuid, usize::MAX)?;
+            with_node_mut::<Entry, _, _>(&entry, |e| e.set_password(Some(password)));
+            let uuid = entry.borrow().get_uuid();
+            Ok((entry, uuid))
+        };
+
+        let (_, weak_1) = add("hunter2")?;
+        let (_, weak_2) = add("hunter2")?;
+        let (_, strong) = add("Xk9#mP2$vQ7!wR4&zT8@")?;
+        let (expired_entry, expired) = add("Lq3%nB8^cV1*yH6(jG5)")?;
+        expired_entry.borrow_mut().get_times_mut().set_expires(true);
+        expired_entry
+            .borrow_mut()
+            .get_times_mut()
+            .set_expiry_time(Some("2000-01-01T00:00:00".parse().unwrap()));
+        let (unchecked, _) = add("1234")?;
+        with_node_mut::<Entry, _, _>(&unchecked, |e| e.quality_check = Some(false));
+        let (_, no_password) = add("")?;
+
+        // the recycle bin is not audited
+        let bin = db.create_recycle_bin()?;
+        let bin_uuid = bin.borrow().get_uuid();
+        let recycled = db.create_new_entry(bin_uuid, 0)?;
+        with_node_mut::<Entry, _, _>(&recycled, |e| e.set_password(Some("hunter2")));
+
+        let report = db.audit_report();
+        assert_eq!(report.entries_checked, 6);
+        assert_eq!(report.weak, vec![weak_1, weak_2]);
+        assert_eq!(report.reused, vec![vec![weak_1, weak_2]]);
+        assert_eq!(report.reused_count(), 2);
+        assert_eq!(report.expired, vec![expired]);
+        assert!(!report.is_clean());
+        assert!(!report.weak.contains(&strong) && !report.weak.contains(&no_password));
+
+        #[cfg(feature = "serialization")]
+        {
+            let json = serde_json::to_value(&report).unwrap();
+            assert_eq!(json["entries_checked"], 6);
+            assert_eq!(json["expired"][0], expired.to_string());
+        }
+
+        Ok(())
     }
-}
 
-impl std::fmt::Display for Color {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "#{:0x}{:0x}{:0x}", self.r, self.g, self.b)
+    #[test]
+    fn test_entries_modified_since() -> Result<()> {
+        use crate::db::DeletedObject;
+        use uuid::Uuid;
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        let before = db.create_new_entry(root_uuid, 1)?;
+        let after = db.create_new_entry(group_uuid, 0)?;
+        let after_uuid = after.borrow().get_uuid();
+        let unset = db.create_new_entry(group_uuid, 1)?;
+
+        before.borrow_mut().get_times_mut().set_last_modification(Some(at(1000)));
+        after.borrow_mut().get_times_mut().set_last_modification(Some(at(3000)));
+        group.borrow_mut().get_times_mut().set_last_modification(Some(at(3000)));
+        unset.borrow_mut().get_times_mut().set_last_modification(None);
+
+        let cutoff = at(2000);
+        let modified = db.entries_modified_since(cutoff);
+        assert_eq!(modified.len(), 1);
+        assert_eq!(modified[0].borrow().get_uuid(), after_uuid);
+        assert!(db.entries_modified_since(at(3000)).is_empty());
+        assert_eq!(db.entries_modified_since(at(0)).len(), 2);
+
+        db.deleted_objects.objects.push(DeletedObject {
+            uuid: Uuid::new_v4(),
+            deletion_time: at(1500),
+        });
+        let deleted = Uuid::new_v4();
+        db.deleted_objects.objects.push(DeletedObject {
+            uuid: deleted,
+            deletion_time: at(2500),
+        });
+
+        #[cfg(feature = "serialization")]
+        {
+            let json: serde_json::Value = serde_json::from_str(&db.export_modified_since_json(cutoff).unwrap()).unwrap();
+            assert_eq!(json["entries"].as_array().unwrap().len(), 1);
+            assert_eq!(json["entries"][0]["uuid"], after_uuid.to_string());
+            assert_eq!(json["deleted"], serde_json::json!([deleted.to_string()]));
+        }
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod database_tests {
     #[cfg(feature = "save_kdbx4")]
-    use crate::{config::DatabaseConfig, db::Entry};
-    use crate::{
-        db::{Database, DatabaseKey},
-        Result,
-    };
-    use std::fs::File;
-
     #[test]
-    fn test_xml() -> Result<()> {
-        let key = DatabaseKey::new().with_password("demopass");
-        let mut f = File::open("tests/resources/test_db_with_password.kdbx")?;
-        let xml = Database::get_xml(&mut f, key)?;
+    fn test_get_xml_decrypted() -> Result<()> {
+        use crate::db::{with_node_mut, Node};
 
-        assert!(xml.len() > 100);
+        let key = DatabaseKey::new().with_password("test");
+        let db = Database::new(DatabaseConfig::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        for (index, password) in ["first-secret", "second <secret>"].into_iter().enumerate() {
+            let entry = db.create_new_entry(root_uuid, index)?;
+            with_node_mut::<Entry, _, _>(&entry, |e| {
+                e.set_title(Some(&format!("Entry {index}")));
+                e.set_password(Some(password));
+            });
+        }
+        let mut data = Vec::new();
+        db.save(&mut data, key.clone())?;
+
+        let xml = String::from_utf8(Database::get_xml(&mut data.as_slice(), key.clone())?).unwrap();
+        assert!(!xml.contains("first-secret"));
 
+        let xml = String::from_utf8(Database::get_xml_decrypted(&mut data.as_slice(), key)?).unwrap();
+        assert!(xml.contains(r#"<Value ProtectInMemory="True">first-secret</Value>"#));
+        assert!(xml.contains(r#"<Value ProtectInMemory="True">second &lt;secret&gt;</Value>"#));
+        assert!(xml.contains("<Value>Entry 0</Value>"));
+        assert!(!xml.contains("Protected=\"True\""));
         Ok(())
     }
 
+    #[cfg(feature = "save_kdbx4")]
     #[test]
-    fn test_open_invalid_version_header_size() {
-        assert!(Database::parse(&[], DatabaseKey::new().with_password("testing")).is_err());
-        assert!(Database::parse(&[0, 0, 0, 0, 0, 0, 0, 0], DatabaseKey::new().with_password("testing")).is_err());
-        assert!(Database::parse(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], DatabaseKey::new().with_password("testing")).is_err());
+    fn test_reload() -> Result<()> {
+        use crate::db::{with_node_mut, MergeEventType, Node};
+
+        let key = DatabaseKey::new().with_password("test");
+        let db = Database::new(DatabaseConfig::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let changed_uuid = db.create_new_entry(root_uuid, 0)?.borrow().get_uuid();
+        let unchanged_uuid = db.create_new_entry(root_uuid, 1)?.borrow().get_uuid();
+        let mut data = Vec::new();
+        db.save(&mut data, key.clone())?;
+
+        let mut ours = Database::open(&mut data.as_slice(), key.clone())?;
+        let changed = ours.search_node_by_uuid(changed_uuid).unwrap();
+        let unchanged = ours.search_node_by_uuid(unchanged_uuid).unwrap();
+
+        // another application changes one entry in the file
+        let theirs = Database::open(&mut data.as_slice(), key.clone())?;
+        with_node_mut::<Entry, _, _>(&theirs.search_node_by_uuid(changed_uuid).unwrap(), |e| {
+            e.set_title(Some("Changed elsewhere"));
+            e.times.set_last_modification(Some("2100-01-01T00:00:00".parse().unwrap()));
+        });
+        let mut data = Vec::new();
+        theirs.save(&mut data, key.clone())?;
+
+        let log = ours.reload(&mut data.as_slice(), key)?;
+        assert_eq!(log.events.len(), 1);
+        assert!(matches!(log.events[0].event_type, MergeEventType::EntryUpdated));
+        assert_eq!(log.events[0].node_uuid, changed_uuid);
+
+        assert_eq!(changed.borrow().get_title(), Some("Changed elsewhere"));
+        assert!(std::rc::Rc::ptr_eq(&changed, &ours.search_node_by_uuid(changed_uuid).unwrap()));
+        assert!(std::rc::Rc::ptr_eq(&unchanged, &ours.search_node_by_uuid(unchanged_uuid).unwrap()));
+        Ok(())
     }
 
     #[cfg(feature = "save_kdbx4")]
     #[test]
-    fn test_save() -> Result<()> {
-        use crate::{
-            db::Group,
-            db::{group_add_child, rc_refcell_node},
-        };
+    fn test_reload_attachments() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, AttachmentRef, HeaderAttachment};
 
+        let key = DatabaseKey::new().with_password("test");
         let db = Database::new(DatabaseConfig::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let local_uuid = db.create_new_entry(root_uuid, 0)?.borrow().get_uuid();
+        let external_uuid = db.create_new_entry(root_uuid, 1)?.borrow().get_uuid();
+        let mut data = Vec::new();
+        db.save(&mut data, key.clone())?;
 
-        group_add_child(&db.root, rc_refcell_node(Entry::default()), 0).unwrap();
-        group_add_child(&db.root, rc_refcell_node(Entry::default()), 1).unwrap();
-        group_add_child(&db.root, rc_refcell_node(Entry::default()), 2).unwrap();
+        let attach = |db: &mut Database, uuid, content: &[u8]| {
+            db.header_attachments.push(HeaderAttachment {
+                flags: 0,
+                content: content.to_vec(),
+            });
+            let identifier = (db.header_attachments.len() - 1).to_string();
+            with_node_mut::<Entry, _, _>(&db.search_node_by_uuid(uuid).unwrap(), |e| {
+                e.attachments.push(AttachmentRef {
+                    name: "file.txt".to_string(),
+                    identifier,
+                });
+                e.times.set_last_modification(Some("2100-01-01T00:00:00".parse().unwrap()));
+            });
+        };
 
-        let group = rc_refcell_node(Group::new("my group"));
-        group_add_child(&group, rc_refcell_node(Entry::default()), 0).unwrap();
-        group_add_child(&group, rc_refcell_node(Entry::default()), 1).unwrap();
-        group_add_child(&db.root, group, 3).unwrap();
+        // both sides add an attachment, which gets the index 0 on either side
+        let mut ours = Database::open(&mut data.as_slice(), key.clone())?;
+        attach(&mut ours, local_uuid, b"local");
+        let mut theirs = Database::open(&mut data.as_slice(), key.clone())?;
+        attach(&mut theirs, external_uuid, b"external");
+        let mut data = Vec::new();
+        theirs.save(&mut data, key.clone())?;
 
-        let mut buffer = Vec::new();
-        let key = DatabaseKey::new().with_password("testing");
+        ours.reload(&mut data.as_slice(), key)?;
+        let content = |uuid| {
+            let attachment = with_node::<Entry, _, _>(&ours.search_node_by_uuid(uuid).unwrap(), |e| e.attachments[0].clone()).unwrap();
+            ours.resolve_attachment(&attachment).unwrap().unwrap().into_owned()
+        };
+        assert_eq!(content(local_uuid), b"local");
+        assert_eq!(content(external_uuid), b"external");
+        assert_eq!(ours.header_attachments.len(), 2);
+        Ok(())
+    }
 
-        db.save(&mut buffer, key.clone())?;
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_reload_deleted_entry() -> Result<()> {
+        let key = DatabaseKey::new().with_password("test");
+        let mut db = Database::new(DatabaseConfig::default());
+        db.set_recycle_bin_enabled(false);
+        let root_uuid = db.root.borrow().get_uuid();
+        let deleted_uuid = db.create_new_entry(root_uuid, 0)?.borrow().get_uuid();
+        let kept_uuid = db.create_new_entry(root_uuid, 1)?.borrow().get_uuid();
+        let mut data = Vec::new();
+        db.save(&mut data, key.clone())?;
 
-        let db_loaded = Database::open(&mut buffer.as_slice(), key)?;
+        let mut ours = Database::open(&mut data.as_slice(), key.clone())?;
+        let mut theirs = Database::open(&mut data.as_slice(), key.clone())?;
+        theirs.remove_node_by_uuid(deleted_uuid)?;
+        theirs.meta.database_name = Some("Renamed elsewhere".to_string());
+        let mut data = Vec::new();
+        theirs.save(&mut data, key.clone())?;
 
-        assert_eq!(db, db_loaded);
+        ours.reload(&mut data.as_slice(), key)?;
+        assert!(ours.search_node_by_uuid(deleted_uuid).is_none());
+        assert!(ours.search_node_by_uuid(kept_uuid).is_some());
+        assert!(ours.deleted_objects.objects.iter().any(|o| o.uuid == deleted_uuid));
+        assert_eq!(ours.meta.database_name.as_deref(), Some("Renamed elsewhere"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_recycle_bin_group() -> Result<()> {
+        let mut db = Database::new(Default::default());
+        assert!(db.configured_recycle_bin_group().is_none());
+
+        let bin_uuid = db.create_recycle_bin()?.borrow().get_uuid();
+        db.set_recycle_bin_enabled(false);
+        assert!(db.get_recycle_bin().is_none());
+        let bin = db.configured_recycle_bin_group().unwrap();
+        assert_eq!(bin.borrow().get_uuid(), bin_uuid);
+
+        // a UUID that does not resolve to a group
+        db.remove_node_by_uuid(bin_uuid)?;
+        assert!(db.configured_recycle_bin_group().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_default_username() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, Group, NodePtr};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let work = db.create_new_group(root_uuid, 0)?;
+        let work_uuid = work.borrow().get_uuid();
+        let nested_uuid = db.create_new_group(work_uuid, 0)?.borrow().get_uuid();
+        let other_uuid = db.create_new_group(root_uuid, 1)?.borrow().get_uuid();
+        let username = |entry: &NodePtr| with_node::<Entry, _, _>(entry, |e| e.get_username().map(str::to_string)).flatten();
+
+        assert_eq!(username(&db.create_new_entry_with_defaults(work_uuid, 0)?), None);
+
+        with_node_mut::<Group, _, _>(&work, |g| g.set_default_username(Some("jdoe@work")));
+        assert_eq!(
+            username(&db.create_new_entry_with_defaults(work_uuid, 0)?),
+            Some("jdoe@work".to_string())
+        );
+        assert_eq!(
+            username(&db.create_new_entry_with_defaults(nested_uuid, 0)?),
+            Some("jdoe@work".to_string())
+        );
+        assert_eq!(username(&db.create_new_entry_with_defaults(other_uuid, 0)?), None);
+
+        db.meta.default_username = Some("jdoe".to_string());
+        assert_eq!(
+            username(&db.create_new_entry_with_defaults(other_uuid, 0)?),
+            Some("jdoe".to_string())
+        );
+        assert_eq!(username(&db.create_new_entry(other_uuid, 0)?), None);
+        assert_eq!(db.default_username_for(nested_uuid), Some("jdoe@work".to_string()));
+
+        with_node_mut::<Group, _, _>(&work, |g| g.set_default_username(None));
+        assert_eq!(db.default_username_for(nested_uuid), Some("jdoe".to_string()));
+        assert!(with_node::<Group, _, _>(&work, |g| g.get_custom_data().items.is_empty()).unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_references() -> Result<()> {
+        use crate::db::{with_node_mut, Entry, Node, Value};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let mut entries = Vec::new();
+        for (title, username, password) in [
+            ("Bank", "alice", "pw-bank"),
+            ("Bank Savings", "bob", "pw-savings"),
+            ("Alias", "{REF:U@T:Bank}", "{REF:P@U:bob}"),
+            ("Loop", "loop", "{REF:P@T:Loop}"),
+        ] {
+            let entry = db.create_new_entry(root_uuid, entries.len())?;
+            with_node_mut::<Entry, _, _>(&entry, |e| {
+                e.set_title(Some(title));
+                e.set_username(Some(username));
+                e.set_password(Some(password));
+            });
+            entries.push(entry);
+        }
+        with_node_mut::<Entry, _, _>(&entries[1], |e| {
+            e.fields.insert("Account".to_string(), Value::Unprotected("DE-1234".to_string()));
+        });
+        let bank_uuid = entries[0].borrow().get_uuid();
+
+        // both bank entries contain the title, the first one wins
+        assert_eq!(db.resolve_field(&entries[2], "UserName"), Some("alice".to_string()));
+        assert_eq!(db.resolve_field(&entries[2], "Password"), Some("pw-savings".to_string()));
+        assert_eq!(db.resolve_field(&entries[2], "URL"), None);
+
+        assert_eq!(db.resolve_references("{REF:T@I:Nobody}"), "{REF:T@I:Nobody}");
+        assert_eq!(db.resolve_references("x {REF:P@T:Nothing} y"), "x {REF:P@T:Nothing} y");
+        assert_eq!(
+            db.resolve_references(&format!("{{ref:t@i:{}}}", bank_uuid.simple())),
+            "Bank".to_string()
+        );
+        assert_eq!(
+            db.resolve_references("{REF:I@O:DE-1234}"),
+            entries[1].borrow().get_uuid().simple().to_string().to_uppercase()
+        );
+        assert_eq!(
+            db.resolve_references("{REF:U@A:}{REF:X@T:Bank}{REF:U@T:Bank"),
+            "{REF:U@A:}{REF:X@T:Bank}{REF:U@T:Bank"
+        );
+        assert_eq!(db.resolve_references("{REF:U@T:Bank}/{REF:U@U:bob}"), "alice/bob");
+        assert_eq!(db.resolve_references("{REF:P@T:bank}/{REF:P@U:BOB}"), "pw-bank/pw-savings");
+        assert_eq!(db.resolve_references("{REF:U@O:de-1234}"), "bob");
+
+        // a reference to itself is not followed forever
+        assert_eq!(db.resolve_field(&entries[3], "Password"), Some("{REF:P@T:Loop}".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_by_path_str() -> Result<()> {
+        use crate::db::{with_node_mut, Group};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        with_node_mut::<Group, _, _>(&group, |g| g.set_name("Work"));
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        entry.borrow_mut().set_title(Some("Client A/B"));
+        let other = db.create_new_entry(group_uuid, 1)?;
+        other.borrow_mut().set_title(Some(r"C:\Temp"));
+
+        let found = db.get_by_path_str(r"Work/Client A\/B").unwrap();
+        assert!(std::rc::Rc::ptr_eq(&found, &entry));
+        assert!(std::rc::Rc::ptr_eq(&db.get_by_path_str(r"/Work/Client A\/B").unwrap(), &entry));
+        assert!(std::rc::Rc::ptr_eq(&db.get_by_path_str(r"Work/C:\\Temp").unwrap(), &other));
+        assert!(std::rc::Rc::ptr_eq(&db.get_by_path_str("Work").unwrap(), &group));
+
+        // unescaped, the slash separates groups
+        assert!(db.get_by_path_str("Work/Client A/B").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_autotype_sequence_for_window() -> Result<()> {
+        use crate::db::{with_node_mut, AutoType, AutoTypeAssociation, Entry, Group, DEFAULT_AUTOTYPE_SEQUENCE};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        let set_autotype = |autotype: AutoType| with_node_mut::<Entry, _, _>(&entry, |e| e.set_autotype(Some(autotype)));
+
+        // enabled with a default sequence and no associations: applies to any window
+        set_autotype(AutoType {
+            enabled: true,
+            sequence: Some("{PASSWORD}{ENTER}".to_string()),
+            ..Default::default()
+        });
+        let sequence = db.autotype_sequence_for_window(&entry, "Some arbitrary window");
+        assert_eq!(sequence.as_deref(), Some("{PASSWORD}{ENTER}"));
+
+        // without an own default, the group's or the global default is used
+        set_autotype(AutoType {
+            enabled: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            db.autotype_sequence_for_window(&entry, "Terminal").as_deref(),
+            Some(DEFAULT_AUTOTYPE_SEQUENCE)
+        );
+        with_node_mut::<Group, _, _>(&group, |g| g.default_autotype_sequence = Some("{USERNAME}".to_string()));
+        assert_eq!(db.autotype_sequence_for_window(&entry, "Terminal").as_deref(), Some("{USERNAME}"));
+
+        // with associations, only matching windows get a sequence
+        set_autotype(AutoType {
+            enabled: true,
+            sequence: Some("{PASSWORD}".to_string()),
+            associations: vec![AutoTypeAssociation {
+                window: Some("*Firefox*".to_string()),
+                sequence: None,
+            }],
+            ..Default::default()
+        });
+        assert_eq!(
+            db.autotype_sequence_for_window(&entry, "Mozilla Firefox").as_deref(),
+            Some("{PASSWORD}")
+        );
+        assert_eq!(db.autotype_sequence_for_window(&entry, "Terminal"), None);
+
+        // disabled
+        set_autotype(AutoType::default());
+        assert_eq!(db.autotype_sequence_for_window(&entry, "Terminal"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_autotype_sequence_without_autotype_settings() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, Group, DEFAULT_AUTOTYPE_SEQUENCE};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group = db.create_new_group(root_uuid, 0)?;
+        let group_uuid = group.borrow().get_uuid();
+        let entry = db.create_new_entry(group_uuid, 0)?;
+        assert!(with_node::<Entry, _, _>(&entry, |e| e.autotype.is_none()).unwrap());
+
+        // a new entry has no auto-type settings, which means enabled for any window
+        assert!(db.is_autotype_enabled(&entry));
+        assert_eq!(
+            db.autotype_sequence_for_window(&entry, "Terminal").as_deref(),
+            Some(DEFAULT_AUTOTYPE_SEQUENCE)
+        );
+
+        // a group disabling auto-type disables it for the entries in it
+        with_node_mut::<Group, _, _>(&group, |g| g.set_autotype_enabled(Some(false)));
+        assert!(!db.is_autotype_enabled(&entry));
+        assert_eq!(db.autotype_sequence_for_window(&entry, "Terminal"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_new_entry_unique() -> Result<()> {
+        use crate::db::{with_node, Group};
+
+        let db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let group_uuid = db.create_new_group(root_uuid, 0)?.borrow().get_uuid();
+
+        let entry = db.create_new_entry_unique(group_uuid, 0, "Mail")?;
+        assert_eq!(entry.borrow().get_title(), Some("Mail"));
+        assert!(db.create_new_entry_unique(group_uuid, 1, "Mail").is_err());
+
+        let group = db.search_node_by_uuid(group_uuid).unwrap();
+        assert_eq!(with_node::<Group, _, _>(&group, |g| g.entries().len()), Some(1));
+        assert_eq!(with_node::<Group, _, _>(&group, |g| g.contains_title("Mail")), Some(true));
+
+        // the title only has to be unique within the group
+        db.create_new_entry_unique(root_uuid, 0, "Mail")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoding_anomalies() -> Result<()> {
+        use crate::db::{with_node, with_node_mut, Entry, Node};
+
+        let mut db = Database::new(Default::default());
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0)?;
+        let uuid = entry.borrow().get_uuid();
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.set_title(Some("CafÃ© login"));
+            e.set_username(Some("rÃ©my"));
+            e.set_password(Some("donâ€™t tell"));
+            e.set_notes(Some("Broken \u{FFFD} character"));
+            e.set_url(Some("https://café.example.com/naïve"));
+        });
+
+        let mut anomalies = db.find_encoding_anomalies();
+        anomalies.sort();
+        let fields = |names: &[&str]| names.iter().map(|name| (uuid, name.to_string())).collect::<Vec<_>>();
+        assert_eq!(anomalies, fields(&["Notes", "Password", "Title", "UserName"]));
+
+        assert_eq!(db.repair_encoding(&anomalies), 3);
+        with_node::<Entry, _, _>(&entry, |e| {
+            assert_eq!(e.get_title(), Some("Café login"));
+            assert_eq!(e.get_username(), Some("rémy"));
+            assert_eq!(e.get_password(), Some("don’t tell"));
+            assert_eq!(e.get_url(), Some("https://café.example.com/naïve"));
+            assert!(e.history.as_ref().is_some_and(|h| !h.get_entries().is_empty()));
+        });
+
+        // only the replacement character is left, which cannot be repaired
+        assert_eq!(db.find_encoding_anomalies(), fields(&["Notes"]));
+        assert_eq!(db.repair_encoding(&fields(&["Notes"])), 0);
+
+        // text that is meant literally is only repaired when asked for, and text that mixes the
+        // code pages is not taken for a double encoding at all
+        let literal = db.create_new_entry(root_uuid, 1)?;
+        let literal_uuid = literal.borrow().get_uuid();
+        with_node_mut::<Entry, _, _>(&literal, |e| {
+            e.set_title(Some("Ã©"));
+            e.set_notes(Some("don\u{E2}\u{80}\u{2122}t"));
+        });
+        assert_eq!(db.find_encoding_anomalies()[1..], [(literal_uuid, "Title".to_string())]);
+        assert_eq!(db.repair_encoding(&fields(&["Title"])), 0);
+        with_node::<Entry, _, _>(&literal, |e| {
+            assert_eq!(e.get_title(), Some("Ã©"));
+            assert_eq!(e.get_notes(), Some("don\u{E2}\u{80}\u{2122}t"));
+        });
         Ok(())
     }
 }
@@ -139,6 +139,25 @@ where
     node.borrow_mut().as_any_mut().downcast_mut::<T>().map(f)
 }
 
+/// Like `with_node`, returning an error instead of panicking if the node is currently borrowed
+/// mutably, e.g. by a closure further up the call stack
+pub fn try_with_node<T, F, R>(node: &NodePtr, f: F) -> std::result::Result<Option<R>, std::cell::BorrowError>
+where
+    T: 'static,
+    F: FnOnce(&T) -> R,
+{
+    Ok(node.try_borrow()?.as_any().downcast_ref::<T>().map(f))
+}
+
+/// Like `with_node_mut`, returning an error instead of panicking if the node is currently borrowed
+pub fn try_with_node_mut<T, F, R>(node: &NodePtr, f: F) -> std::result::Result<Option<R>, std::cell::BorrowMutError>
+where
+    T: 'static,
+    F: FnOnce(&mut T) -> R,
+{
+    Ok(node.try_borrow_mut()?.as_any_mut().downcast_mut::<T>().map(f))
+}
+
 pub fn node_is_entry(entry: &NodePtr) -> bool {
     with_node::<Entry, _, _>(entry, |_| true).unwrap_or(false)
 }
@@ -152,18 +171,14 @@ pub fn group_get_children(group: &NodePtr) -> Option<Vec<NodePtr>> {
 }
 
 pub fn group_add_child(parent: &NodePtr, child: NodePtr, index: usize) -> Result<()> {
-    with_node_mut::<Group, _, _>(parent, |parent| {
-        parent.add_child(child, index);
-        Ok::<_, crate::Error>(())
-    })
-    .unwrap_or(Err("parent is not a group".into()))?;
+    with_node_mut::<Group, _, _>(parent, |parent| parent.try_add_child(child, index)).unwrap_or(Err("parent is not a group".into()))?;
     Ok(())
 }
 
 pub fn group_remove_node_by_uuid(root: &NodePtr, uuid: Uuid) -> crate::Result<NodePtr> {
     let root_uuid = root.borrow().get_uuid();
     if root_uuid == uuid {
-        return Err("Cannot remove root node".into());
+        return Err(crate::Error::CannotRemoveRoot);
     }
 
     let node = search_node_by_uuid(root, uuid).ok_or("Node not found")?;
@@ -269,3 +284,33 @@ impl Iterator for NodeIterator {
         Some(next)
     }
 }
+
+#[cfg(test)]
+mod node_tests {
+    use super::{rc_refcell_node, try_with_node, try_with_node_mut, with_node, with_node_mut};
+    use crate::db::{Entry, Group, Node};
+
+    #[test]
+    fn try_with_node_on_borrowed_node() {
+        let node = rc_refcell_node(Entry::default());
+
+        assert_eq!(try_with_node::<Entry, _, _>(&node, |_| 1).ok(), Some(Some(1)));
+        assert_eq!(try_with_node::<Group, _, _>(&node, |_| 1).ok(), Some(None));
+        assert_eq!(
+            try_with_node_mut::<Entry, _, _>(&node, |e| e.set_title(Some("Title"))).ok(),
+            Some(Some(()))
+        );
+
+        with_node::<Entry, _, _>(&node, |_| {
+            assert_eq!(
+                try_with_node::<Entry, _, _>(&node, |e| e.get_title().map(str::to_string)).ok(),
+                Some(Some(Some("Title".to_string())))
+            );
+            assert!(try_with_node_mut::<Entry, _, _>(&node, |_| ()).is_err());
+        });
+        with_node_mut::<Entry, _, _>(&node, |_| {
+            assert!(try_with_node::<Entry, _, _>(&node, |_| ()).is_err());
+            assert!(try_with_node_mut::<Entry, _, _>(&node, |_| ()).is_err());
+        });
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+#[cfg(feature = "threadsafe")]
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+
+use uuid::Uuid;
+
+use crate::{
+    config::DatabaseConfig,
+    db::{group_get_children, with_node, AttachmentRef, Entry, Group, IconId, Meta, NodePtr, Times, Value},
+};
+
+/// An immutable copy of a database that holds no reference-counted nodes, so that it can be
+/// passed to other threads, e.g. for rendering. Create one with `Database::snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct DatabaseSnapshot {
+    pub config: DatabaseConfig,
+    pub meta: Meta,
+    pub root: GroupSnapshot,
+}
+
+/// Copy of a group and everything in it, see `DatabaseSnapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct GroupSnapshot {
+    pub uuid: Uuid,
+    pub name: Option<String>,
+    pub notes: Option<String>,
+    pub icon_id: Option<IconId>,
+    pub custom_icon_uuid: Option<Uuid>,
+    pub tags: Vec<String>,
+    pub times: Times,
+    pub groups: Vec<GroupSnapshot>,
+    pub entries: Vec<EntrySnapshot>,
+}
+
+/// Copy of an entry including its history, see `DatabaseSnapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+pub struct EntrySnapshot {
+    pub uuid: Uuid,
+    pub fields: HashMap<String, Value>,
+    pub tags: Vec<String>,
+    pub attachments: Vec<AttachmentRef>,
+    pub icon_id: Option<IconId>,
+    pub custom_icon_uuid: Option<Uuid>,
+    pub times: Times,
+    /// Previous versions of the entry, most recent first
+    pub history: Vec<EntrySnapshot>,
+}
+
+impl GroupSnapshot {
+    pub(crate) fn new(group: &NodePtr) -> Option<Self> {
+        let mut snapshot = with_node::<Group, _, _>(group, |group| GroupSnapshot {
+            uuid: group.uuid,
+            name: group.name.clone(),
+            notes: group.notes.clone(),
+            icon_id: group.icon_id,
+            custom_icon_uuid: group.custom_icon_uuid,
+            tags: group.tags.clone(),
+            times: group.times.clone(),
+            groups: Vec::new(),
+            entries: Vec::new(),
+        })?;
+        for child in group_get_children(group).unwrap_or_default() {
+            if let Some(entry) = with_node::<Entry, _, _>(&child, EntrySnapshot::new) {
+                snapshot.entries.push(entry);
+            } else if let Some(group) = GroupSnapshot::new(&child) {
+                snapshot.groups.push(group);
+            }
+        }
+        Some(snapshot)
+    }
+
+    /// Get the title of the group
+    pub fn get_title(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl EntrySnapshot {
+    fn new(entry: &Entry) -> Self {
+        EntrySnapshot {
+            uuid: entry.uuid,
+            fields: entry.fields.clone(),
+            tags: entry.tags.clone(),
+            attachments: entry.attachments.clone(),
+            icon_id: entry.icon_id,
+            custom_icon_uuid: entry.custom_icon_uuid,
+            times: entry.times.clone(),
+            history: entry
+                .history
+                .iter()
+                .flat_map(|h| h.entries.iter())
+                .map(EntrySnapshot::new)
+                .collect(),
+        }
+    }
+
+    /// Get a field by name, taking care of unprotecting Protected values automatically
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match self.fields.get(key) {
+            None | Some(&Value::Bytes(_)) => None,
+            Some(Value::Protected(pv)) => std::str::from_utf8(pv.unsecure()).ok(),
+            Some(Value::Unprotected(uv)) => Some(uv),
+        }
+    }
+
+    /// Convenience method for getting the value of the 'Title' field
+    pub fn get_title(&self) -> Option<&str> {
+        self.get("Title")
+    }
+
+    /// Convenience method for getting the value of the `UserName` field
+    pub fn get_username(&self) -> Option<&str> {
+        self.get("UserName")
+    }
+
+    /// Convenience method for getting the value of the 'Password' field
+    pub fn get_password(&self) -> Option<&str> {
+        self.get("Password")
+    }
+}
+
+/// A thread-safe, read-only snapshot of a database: a cheaply cloneable handle to a
+/// `DatabaseSnapshot` that can be shared between threads and tasks.
+///
+/// This does not make `Database` itself thread-safe. Its node tree stays single-threaded, so the
+/// database is edited, saved and merged on the thread that owns it, and every change has to be
+/// published to the readers by taking a new snapshot with `SyncDatabase::update`.
+#[cfg(feature = "threadsafe")]
+#[derive(Debug, Clone)]
+pub struct SyncDatabase {
+    inner: Arc<RwLock<DatabaseSnapshot>>,
+}
+
+#[cfg(feature = "threadsafe")]
+impl SyncDatabase {
+    pub fn new(db: &crate::db::Database) -> crate::Result<Self> {
+        Ok(Self {
+            inner: Arc::new(RwLock::new(db.snapshot()?)),
+        })
+    }
+
+    /// Get read access to the current snapshot
+    pub fn read(&self) -> RwLockReadGuard<'_, DatabaseSnapshot> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the shared snapshot with a new snapshot of `db`
+    pub fn update(&self, db: &crate::db::Database) -> crate::Result<()> {
+        let snapshot = db.snapshot()?;
+        *self.inner.write().unwrap_or_else(PoisonError::into_inner) = snapshot;
+        Ok(())
+    }
+}
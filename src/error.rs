@@ -11,6 +11,9 @@ pub enum Error {
     #[error("DatabaseError::RecycleBinAlreadyExists")]
     RecycleBinAlreadyExists,
 
+    #[error("DatabaseError::CannotRemoveRoot")]
+    CannotRemoveRoot,
+
     #[error("DatabaseOpenError {0}")]
     DatabaseOpenError(#[from] DatabaseOpenError),
 
@@ -162,6 +165,18 @@ pub enum DatabaseIntegrityError {
     #[error("Incomplete outer header: Missing {}", missing_field)]
     IncompleteOuterHeader { missing_field: String },
 
+    /// The outer header ends before its end marker, or a header entry is shorter than it should be
+    #[error("Truncated outer header at offset {}", offset)]
+    TruncatedOuterHeader { offset: usize },
+
+    /// The database could not be processed, e.g. rewritten, for a reason described by `message`
+    #[error("Malformed database: {}", message)]
+    Malformed { message: String },
+
+    /// The inner header ends before its end marker, or an inner header entry is shorter than it should be
+    #[error("Truncated inner header at offset {}", offset)]
+    TruncatedInnerHeader { offset: usize },
+
     #[error("Invalid inner header entry: {}", entry_type)]
     InvalidInnerHeaderEntry { entry_type: u8 },
 
@@ -222,6 +237,14 @@ pub enum DatabaseSaveError {
     /// An error getting randomness for keys occurred
     #[error(transparent)]
     Random(#[from] getrandom::Error),
+
+    /// A group or entry has inconsistent timestamps, see `DumpOptions::validate_times`
+    #[error("Invalid timestamps of {uuid}: {message}")]
+    InvalidTimes { uuid: uuid::Uuid, message: String },
+
+    /// An entry has a field name that cannot be written to XML, see `Entry::validate_field_name`
+    #[error("Invalid field name in {uuid}: {message}")]
+    InvalidFieldName { uuid: uuid::Uuid, message: String },
 }
 
 /// Errors related to the database key
@@ -247,6 +270,10 @@ pub enum DatabaseKeyError {
     #[error("Could not obtain a key from the keyfile")]
     InvalidKeyFile,
 
+    /// The key derivation was cancelled by the progress callback
+    #[error("Key derivation was cancelled")]
+    Cancelled,
+
     /// Could not get challenge response key.
     #[cfg(feature = "challenge_response")]
     #[error("Error with the challenge-response key: {0}")]
@@ -318,6 +345,10 @@ pub enum BlockStreamError {
 
     #[error("Block hash mismatch for block {}", block_index)]
     BlockHashMismatch { block_index: u64 },
+
+    /// The stream ends in the middle of a block
+    #[error("Block {} is truncated", block_index)]
+    TruncatedBlock { block_index: u64 },
 }
 
 /// Errors while parsing a `VariantDictionary`
@@ -369,6 +400,18 @@ pub enum XmlParseError {
     #[error("Decompression error: {}", _0)]
     Compression(#[source] std::io::Error),
 
+    /// A protected value could not be decoded, e.g. because of invalid base64
+    #[error("Invalid protected value in field '{}': {}", field, reason)]
+    InvalidProtectedValue { field: String, reason: String },
+
+    /// The XML declaration names an encoding other than UTF-8
+    #[error("Unsupported XML encoding: {}", _0)]
+    UnsupportedEncoding(String),
+
+    /// A timestamp was given as base64 but did not decode to 8 bytes
+    #[error("Invalid timestamp: {}", _0)]
+    InvalidTimestamp(String),
+
     /// An unexpected XML event occurred, such as opening an unexpected tag, or an error in the
     /// underlying XML reader
     #[error("Bad XML event: expected {}, got {:?}", expected, event)]
@@ -382,6 +425,18 @@ pub enum XmlParseError {
     Eof,
 }
 
+/// Errors when looking up a node by its path with `Group::get_typed`
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum GetError {
+    /// There is no node at the given path
+    #[error("Node not found")]
+    NotFound,
+
+    /// There is a node at the given path, but it has a different type than requested
+    #[error("Node has the wrong type")]
+    WrongType,
+}
+
 /// Error parsing a color code
 #[derive(Debug, thiserror::Error)]
 #[error("Cannot parse color: '{}'", _0)]
@@ -46,6 +46,20 @@ fn parse_header(data: &[u8]) -> Result<KDBHeader, DatabaseIntegrityError> {
     })
 }
 
+/// Check that the header of a KDB database is complete
+pub(crate) fn validate_header_kdb(data: &[u8]) -> Result<(), DatabaseIntegrityError> {
+    parse_header(data)?;
+    Ok(())
+}
+
+/// Read the key derivation settings from the header of a KDB database
+pub(crate) fn read_kdf_config_kdb(data: &[u8]) -> Result<KdfConfig, DatabaseIntegrityError> {
+    let header = parse_header(data)?;
+    Ok(KdfConfig::Aes {
+        rounds: u64::from(header.transform_rounds),
+    })
+}
+
 fn from_utf8(data: &[u8]) -> String {
     String::from_utf8_lossy(data).trim_end_matches('\0').to_owned()
 }
@@ -62,6 +76,18 @@ fn ensure_length(field_type: u16, field_size: u32, expected_field_size: u32) ->
     }
 }
 
+/// Read the type, size and value of the next group or entry field, or fail with `err` if the data
+/// ends before the field does
+fn read_field(data: &[u8], err: DatabaseIntegrityError) -> Result<(u16, u32, &[u8]), DatabaseIntegrityError> {
+    let Some(field_header) = data.get(..6) else {
+        return Err(err);
+    };
+    let field_type = LittleEndian::read_u16(field_header);
+    let field_size = LittleEndian::read_u32(&field_header[2..]);
+    let field_value = data.get(6..).and_then(|rest| rest.get(..field_size as usize)).ok_or(err)?;
+    Ok((field_type, field_size, field_value))
+}
+
 fn entry_name(field_type: u16) -> &'static str {
     match field_type {
         0x0004 => "Title",
@@ -103,9 +129,7 @@ fn parse_groups(root: &NodePtr, header_num_groups: u32, data: &mut &[u8]) -> Res
     let mut num_groups = 0; // the total number of parsed groups
     while num_groups < header_num_groups as usize {
         // Read group TLV
-        let field_type = LittleEndian::read_u16(&data[0..]);
-        let field_size = LittleEndian::read_u32(&data[2..]);
-        let field_value = &data[6..6 + field_size as usize];
+        let (field_type, field_size, field_value) = read_field(data, DatabaseIntegrityError::IncompleteKDBGroup)?;
 
         match field_type {
             0x0000 => {} // KeePass ignores this field type
@@ -181,9 +205,7 @@ fn parse_entries(root: &NodePtr, gid_map: &GidMap, header_num_entries: u32, data
     let mut num_entries = 0;
     while num_entries < header_num_entries {
         // Read entry TLV
-        let field_type = LittleEndian::read_u16(&data[0..]);
-        let field_size = LittleEndian::read_u32(&data[2..]);
-        let field_value = &data[6..6 + field_size as usize];
+        let (field_type, field_size, field_value) = read_field(data, DatabaseIntegrityError::IncompleteKDBEntry)?;
 
         match field_type {
             0x0000 => {} // KeePass ignores this field type
@@ -297,7 +319,9 @@ pub(crate) fn parse_kdb(data: &[u8], db_key: &DatabaseKey) -> Result<Database, D
         rounds: u64::from(header.transform_rounds),
     };
 
-    let transformed_key = kdf_config.get_kdf_seeded(&header.transform_seed).transform_key(&composite_key)?;
+    let transformed_key = kdf_config
+        .get_kdf_seeded(&header.transform_seed)
+        .transform_key(&composite_key, db_key.get_kdf_progress())?;
 
     let master_key = calculate_sha256(&[&header.master_seed, transformed_key.as_slice()]);
 
@@ -313,8 +337,9 @@ pub(crate) fn parse_kdb(data: &[u8], db_key: &DatabaseKey) -> Result<Database, D
     let payload_padded = outer_cipher_config
         .get_cipher(master_key.as_slice(), header.encryption_iv.as_ref())?
         .decrypt(payload_encrypted)?;
-    let padlen = payload_padded[payload_padded.len() - 1] as usize;
-    let payload = &payload_padded[..payload_padded.len() - padlen];
+    // a broken padding length leaves a payload that fails the hash check below
+    let padlen = payload_padded.last().map_or(0, |&padlen| padlen as usize);
+    let payload = &payload_padded[..payload_padded.len().saturating_sub(padlen)];
 
     // Check if we decrypted correctly
     let hash = calculate_sha256(&[payload]);
@@ -338,5 +363,8 @@ pub(crate) fn parse_kdb(data: &[u8], db_key: &DatabaseKey) -> Result<Database, D
         root: root_group.into(),
         deleted_objects: DeletedObjects::default(),
         meta: Meta::new(),
+        warnings: Vec::new(),
+        #[cfg(feature = "save_kdbx4")]
+        original_header: None,
     })
 }
@@ -0,0 +1,42 @@
+//! Databases written by the `KeePass` 2 pre-releases, which only differ from KDBX3 in the second
+//! signature (`KEEPASS_2_ID`). `KeePass` itself reads them with its regular KDBX reader, and so
+//! does this module. Limitations:
+//!
+//! * they can only be read, saving keeps failing with `DatabaseSaveError::UnsupportedVersion`
+//! * files from pre-releases whose header or XML layout still differed from the final KDBX3
+//!   format are reported as corrupt
+
+use crate::{
+    config::{DatabaseConfig, KdfConfig},
+    crypt::ciphers::Cipher,
+    db::Database,
+    error::DatabaseOpenError,
+    format::kdbx3::{check_key_kdbx3, decrypt_kdbx3, parse_kdbx3, read_kdf_config_kdbx3, validate_header_kdbx3},
+    key::DatabaseKey,
+};
+
+/// Open, decrypt and parse a `KeePass` 2 pre-release database from a source and a password
+pub(crate) fn parse_kdb2(data: &[u8], db_key: &DatabaseKey) -> Result<Database, DatabaseOpenError> {
+    parse_kdbx3(data, db_key)
+}
+
+/// Open and decrypt a `KeePass` 2 pre-release database from a source and a password
+#[allow(clippy::type_complexity)]
+pub(crate) fn decrypt_kdb2(data: &[u8], db_key: &DatabaseKey) -> Result<(DatabaseConfig, Box<dyn Cipher>, Vec<u8>), DatabaseOpenError> {
+    decrypt_kdbx3(data, db_key)
+}
+
+/// Check whether a key opens a `KeePass` 2 pre-release database
+pub(crate) fn check_key_kdb2(data: &[u8], db_key: &DatabaseKey) -> Result<bool, DatabaseOpenError> {
+    check_key_kdbx3(data, db_key)
+}
+
+/// Check that the outer header of a `KeePass` 2 pre-release database is complete and well-formed
+pub(crate) fn validate_header_kdb2(data: &[u8]) -> Result<(), DatabaseOpenError> {
+    validate_header_kdbx3(data)
+}
+
+/// Read the key derivation settings from the outer header of a `KeePass` 2 pre-release database
+pub(crate) fn read_kdf_config_kdb2(data: &[u8]) -> Result<KdfConfig, DatabaseOpenError> {
+    read_kdf_config_kdbx3(data)
+}
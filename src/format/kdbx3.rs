@@ -1,3 +1,5 @@
+#[cfg(feature = "save_kdbx4")]
+use crate::format::{HeaderSeeds, ParsedHeader};
 use crate::{
     config::{CompressionConfig, DatabaseConfig, InnerCipherConfig, KdfConfig, OuterCipherConfig},
     crypt::{calculate_sha256, ciphers::Cipher},
@@ -26,6 +28,24 @@ struct KDBX3Header {
     body_start: usize,
 }
 
+/// Check that the outer header of a KDBX3 database is complete and well-formed
+pub(crate) fn validate_header_kdbx3(data: &[u8]) -> Result<(), DatabaseOpenError> {
+    parse_outer_header(data)?;
+    Ok(())
+}
+
+/// Read the key derivation settings from the outer header of a KDBX3 database
+pub(crate) fn read_kdf_config_kdbx3(data: &[u8]) -> Result<KdfConfig, DatabaseOpenError> {
+    Ok(parse_outer_header(data)?.kdf_config)
+}
+
+/// Get the first `size` bytes of a header field that starts at `offset`
+fn read_header_field(field_buffer: &[u8], size: usize, offset: usize) -> Result<&[u8], DatabaseIntegrityError> {
+    field_buffer
+        .get(..size)
+        .ok_or(DatabaseIntegrityError::TruncatedOuterHeader { offset })
+}
+
 fn parse_outer_header(data: &[u8]) -> Result<KDBX3Header, DatabaseOpenError> {
     let mut outer_cipher: Option<OuterCipherConfig> = None;
     let mut compression: Option<CompressionConfig> = None;
@@ -82,8 +102,8 @@ fn parse_outer_header(data: &[u8]) -> Result<KDBX3Header, DatabaseOpenError> {
 
             // COMPRESSIONFLAGS - first byte determines compression of payload
             KDBXHeaderFieldID::CompressionFlags => {
-                compression =
-                    Some(CompressionConfig::try_from(LittleEndian::read_u32(field_buffer)).map_err(DatabaseIntegrityError::from)?);
+                let compression_id = read_header_field(field_buffer, 4, pos - field_length).map(LittleEndian::read_u32)?;
+                compression = Some(CompressionConfig::try_from(compression_id).map_err(DatabaseIntegrityError::from)?);
             }
 
             // MASTERSEED - Master seed for deriving the master key
@@ -93,7 +113,9 @@ fn parse_outer_header(data: &[u8]) -> Result<KDBX3Header, DatabaseOpenError> {
             KDBXHeaderFieldID::TransformSeed => transform_seed = Some(field_buffer.to_vec()),
 
             // TRANSFORMROUNDS - Number of rounds used in derivation of transformed key
-            KDBXHeaderFieldID::TransformRounds => transform_rounds = Some(LittleEndian::read_u64(field_buffer)),
+            KDBXHeaderFieldID::TransformRounds => {
+                transform_rounds = Some(read_header_field(field_buffer, 8, pos - field_length).map(LittleEndian::read_u64)?);
+            }
 
             // ENCRYPTIONIV - Initialization Vector for decrypting the payload
             KDBXHeaderFieldID::EncryptionIV => encryption_iv = Some(field_buffer.to_vec()),
@@ -107,8 +129,8 @@ fn parse_outer_header(data: &[u8]) -> Result<KDBX3Header, DatabaseOpenError> {
             // INNERRANDOMSTREAMID - specifies which cipher suite
             //                       to use for decrypting the inner protected values
             KDBXHeaderFieldID::InnerRandomStreamID => {
-                inner_random_stream_id =
-                    Some(InnerCipherConfig::try_from(LittleEndian::read_u32(field_buffer)).map_err(DatabaseIntegrityError::from)?);
+                let stream_id = read_header_field(field_buffer, 4, pos - field_length).map(LittleEndian::read_u32)?;
+                inner_random_stream_id = Some(InnerCipherConfig::try_from(stream_id).map_err(DatabaseIntegrityError::from)?);
             }
 
             _ => {
@@ -151,52 +173,34 @@ fn parse_outer_header(data: &[u8]) -> Result<KDBX3Header, DatabaseOpenError> {
     })
 }
 
-/// Open, decrypt and parse a `KeePass` database from a source and a password
-pub(crate) fn parse_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<Database, DatabaseOpenError> {
-    let (config, mut inner_decryptor, xml) = decrypt_kdbx3(data, db_key)?;
-
-    // Parse XML data blocks
-    let database_content = crate::xml_db::parse::parse(&xml, &mut *inner_decryptor).map_err(DatabaseIntegrityError::from)?;
-
-    let db = Database {
-        config,
-        header_attachments: Vec::new(),
-        root: rc_refcell_node(database_content.root.group).into(),
-        deleted_objects: database_content.root.deleted_objects,
-        meta: database_content.meta,
-    };
-
-    Ok(db)
+/// Check whether a key opens a `KeePass` KDBX3 database, only decrypting the payload
+/// far enough to compare its stream start bytes
+pub(crate) fn check_key_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<bool, DatabaseOpenError> {
+    let (config, header, master_key, payload_encrypted) = unlock_kdbx3(data, db_key)?;
+    stream_start_matches(&config, &header, &master_key, payload_encrypted)
 }
 
-/// Open and decrypt a `KeePass` KDBX3 database from a source and a password
+/// Parse the outer header and derive the master key.
+/// Returns the database configuration, the outer header, the master key and the encrypted payload.
 #[allow(clippy::type_complexity)]
-pub(crate) fn decrypt_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<(DatabaseConfig, Box<dyn Cipher>, Vec<u8>), DatabaseOpenError> {
+fn unlock_kdbx3<'a>(data: &'a [u8], db_key: &DatabaseKey) -> Result<(DatabaseConfig, KDBX3Header, Vec<u8>, &'a [u8]), DatabaseOpenError> {
     let version = DatabaseVersion::parse(data)?;
     let header = parse_outer_header(data)?;
 
-    // Derive stream key for decrypting inner protected values and set up decryption context
-    let stream_key = calculate_sha256(&[header.inner_random_stream_key.as_ref()]);
-
-    let inner_decryptor = header.inner_random_stream_id.get_cipher(stream_key.as_slice());
-
     let config = DatabaseConfig {
         version,
-        outer_cipher_config: header.outer_cipher,
-        compression_config: header.compression,
-        inner_cipher_config: header.inner_random_stream_id,
-        kdf_config: header.kdf_config,
+        outer_cipher_config: header.outer_cipher.clone(),
+        compression_config: header.compression.clone(),
+        inner_cipher_config: header.inner_random_stream_id.clone(),
+        kdf_config: header.kdf_config.clone(),
     };
 
-    let mut pos = header.body_start;
-
-    // Turn enums into appropriate trait objects
-    let compression = config.compression_config.get_compression();
-
     // Rest of file after header is payload
-    let payload_encrypted = data.get(pos..).ok_or_else(|| DatabaseIntegrityError::IncompleteOuterHeader {
-        missing_field: "Payload".into(),
-    })?;
+    let payload_encrypted = data
+        .get(header.body_start..)
+        .ok_or_else(|| DatabaseIntegrityError::IncompleteOuterHeader {
+            missing_field: "Payload".into(),
+        })?;
 
     // derive master key from composite key, transform_seed, transform_rounds and master_seed
     let key_elements = db_key.get_key_elements()?;
@@ -207,27 +211,102 @@ pub(crate) fn decrypt_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<(Databa
     let transformed_key = config
         .kdf_config
         .get_kdf_seeded(&header.transform_seed)
-        .transform_key(&composite_key)?;
+        .transform_key(&composite_key, db_key.get_kdf_progress())?;
 
     let master_key = calculate_sha256(&[header.master_seed.as_ref(), transformed_key.as_slice()]);
 
-    // Decrypt payload
-    let payload = config
+    Ok((config, header, master_key.to_vec(), payload_encrypted))
+}
+
+/// Decrypt the first cipher blocks of the payload and compare them to the stream start bytes
+/// of the header, which tells whether the master key is correct
+fn stream_start_matches(
+    config: &DatabaseConfig,
+    header: &KDBX3Header,
+    master_key: &[u8],
+    payload_encrypted: &[u8],
+) -> Result<bool, DatabaseOpenError> {
+    // Round up to whole blocks of the CBC ciphers
+    let start_len = header.stream_start.len().div_ceil(16) * 16;
+    let start_encrypted = payload_encrypted
+        .get(..start_len)
+        .ok_or_else(|| DatabaseIntegrityError::IncompleteOuterHeader {
+            missing_field: "Payload".into(),
+        })?;
+
+    let start = config
         .outer_cipher_config
-        .get_cipher(master_key.as_slice(), header.encryption_iv.as_ref())?
-        .decrypt(payload_encrypted)?;
+        .get_cipher(master_key, header.encryption_iv.as_ref())?
+        .decrypt_start(start_encrypted)?;
+
+    Ok(start.get(..header.stream_start.len()) == Some(header.stream_start.as_slice()))
+}
+
+/// Open, decrypt and parse a `KeePass` database from a source and a password
+pub(crate) fn parse_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<Database, DatabaseOpenError> {
+    let mut decrypted = decrypt_kdbx3_with_header(data, db_key)?;
+
+    // Parse XML data blocks
+    let database_content =
+        crate::xml_db::parse::parse(&decrypted.xml, &mut *decrypted.inner_decryptor).map_err(DatabaseIntegrityError::from)?;
+
+    let db = Database {
+        config: decrypted.config,
+        header_attachments: Vec::new(),
+        root: rc_refcell_node(database_content.root.group).into(),
+        deleted_objects: database_content.root.deleted_objects,
+        meta: database_content.meta,
+        warnings: database_content.root.warnings,
+        #[cfg(feature = "save_kdbx4")]
+        original_header: Some(decrypted.original_header),
+    };
+
+    Ok(db)
+}
+
+/// Open and decrypt a `KeePass` KDBX3 database from a source and a password
+#[allow(clippy::type_complexity)]
+pub(crate) fn decrypt_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<(DatabaseConfig, Box<dyn Cipher>, Vec<u8>), DatabaseOpenError> {
+    let decrypted = decrypt_kdbx3_with_header(data, db_key)?;
+    Ok((decrypted.config, decrypted.inner_decryptor, decrypted.xml))
+}
 
-    // Check if we decrypted correctly
-    let stream_start = payload
-        .get(0..header.stream_start.len())
-        .ok_or_else(|| DatabaseKeyError::IncorrectKey)?;
-    if stream_start != header.stream_start.as_slice() {
+/// A decrypted KDBX3 database whose XML is not parsed yet
+struct DecryptedKdbx3 {
+    config: DatabaseConfig,
+    inner_decryptor: Box<dyn Cipher>,
+    xml: Vec<u8>,
+    /// Seeds and header of the file for re-saving it
+    #[cfg(feature = "save_kdbx4")]
+    original_header: ParsedHeader,
+}
+
+/// Like `decrypt_kdbx3`, but also returns the seeds and header of the file
+fn decrypt_kdbx3_with_header(data: &[u8], db_key: &DatabaseKey) -> Result<DecryptedKdbx3, DatabaseOpenError> {
+    let (config, header, master_key, payload_encrypted) = unlock_kdbx3(data, db_key)?;
+
+    // Check the key on the first blocks before decrypting the whole payload
+    if !stream_start_matches(&config, &header, &master_key, payload_encrypted)? {
         return Err(DatabaseKeyError::IncorrectKey.into());
     }
 
-    let mut buf = Vec::new();
+    let payload = config
+        .outer_cipher_config
+        .get_cipher(&master_key, header.encryption_iv.as_ref())?
+        .decrypt(payload_encrypted)?;
+
+    // Derive stream key for decrypting inner protected values and set up decryption context
+    let stream_key = calculate_sha256(&[header.inner_random_stream_key.as_ref()]);
+
+    let inner_decryptor = header.inner_random_stream_id.get_cipher(stream_key.as_slice());
 
-    pos = 32;
+    // Turn enums into appropriate trait objects
+    let compression = config.compression_config.get_compression();
+
+    // the blocks take up the whole payload apart from their small headers
+    let mut buf = Vec::with_capacity(payload.len());
+
+    let mut pos = 32;
     let mut block_index = 0;
     loop {
         // Parse blocks in payload.
@@ -241,16 +320,22 @@ pub(crate) fn decrypt_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<(Databa
         //   block_buffer_compressed: [u8, block_size]      // Block data, possibly compressed
         // )
 
+        let truncated = || BlockStreamError::TruncatedBlock { block_index };
+
         // let block_id = LittleEndian::read_u32(&payload[pos..(pos + 4)]);
-        let block_hash = &payload[(pos + 4)..(pos + 36)];
-        let block_size = LittleEndian::read_u32(&payload[(pos + 36)..(pos + 40)]) as usize;
+        let block_hash = payload.get((pos + 4)..(pos + 36)).ok_or_else(truncated)?;
+        let block_size = payload.get((pos + 36)..(pos + 40)).ok_or_else(truncated)?;
+        let block_size = LittleEndian::read_u32(block_size) as usize;
 
         // A block with size 0 means we have hit EOF
         if block_size == 0 {
             break;
         }
 
-        let block_buffer_compressed = &payload[(pos + 40)..(pos + 40 + block_size)];
+        let block_buffer_compressed = payload
+            .get((pos + 40)..)
+            .and_then(|rest| rest.get(..block_size))
+            .ok_or_else(truncated)?;
 
         // Test block hash
         let block_hash_check = calculate_sha256(&[block_buffer_compressed]);
@@ -258,8 +343,7 @@ pub(crate) fn decrypt_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<(Databa
             return Err(BlockStreamError::BlockHashMismatch { block_index }.into());
         }
 
-        // Decompress block_buffer_compressed
-        buf.append(&mut block_buffer_compressed.to_vec());
+        buf.extend_from_slice(block_buffer_compressed);
 
         pos += 40 + block_size;
         block_index += 1;
@@ -267,5 +351,25 @@ pub(crate) fn decrypt_kdbx3(data: &[u8], db_key: &DatabaseKey) -> Result<(Databa
 
     let xml = compression.decompress(&buf)?;
 
-    Ok((config, inner_decryptor, xml))
+    #[cfg(feature = "save_kdbx4")]
+    let original_header = ParsedHeader {
+        config: config.clone(),
+        seeds: HeaderSeeds {
+            master_seed: header.master_seed,
+            outer_iv: header.encryption_iv,
+            kdf_seed: header.transform_seed,
+            inner_random_stream_key: header.inner_random_stream_key,
+        },
+        header_data: data[..header.body_start].to_vec(),
+        header_hmac: None,
+        payload_sha256: calculate_sha256(&[&xml]).to_vec(),
+    };
+
+    Ok(DecryptedKdbx3 {
+        config,
+        inner_decryptor,
+        xml,
+        #[cfg(feature = "save_kdbx4")]
+        original_header,
+    })
 }
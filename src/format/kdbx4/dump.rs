@@ -1,10 +1,15 @@
 use std::io::Write;
 
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use cipher::generic_array::{
+    typenum::{U32, U64},
+    GenericArray,
+};
 
 use crate::{
+    config::DumpOptions,
     crypt,
-    db::{Database, HeaderAttachment},
+    db::{with_node, Database, Entry, HeaderAttachment, NodeIterator},
     error::DatabaseSaveError,
     format::{
         kdbx4::{
@@ -12,7 +17,7 @@ use crate::{
             HEADER_MASTER_SEED, HEADER_MASTER_SEED_SIZE, HEADER_OUTER_ENCRYPTION_ID, INNER_HEADER_BINARY_ATTACHMENTS, INNER_HEADER_END,
             INNER_HEADER_RANDOM_STREAM_ID, INNER_HEADER_RANDOM_STREAM_KEY,
         },
-        DatabaseVersion,
+        DatabaseVersion, HeaderSeeds, ParsedHeader,
     },
     hmac_block_stream,
     io::WriteLengthTaggedExt,
@@ -23,84 +28,308 @@ use crate::{
 /// Dump a `KeePass` database using the key elements
 #[allow(dead_code)]
 pub fn dump_kdbx4(db: &Database, db_key: &DatabaseKey, writer: &mut dyn Write) -> Result<(), DatabaseSaveError> {
+    dump_kdbx4_with_options(db, db_key, &DumpOptions::default(), writer)
+}
+
+/// Dump a `KeePass` database using the key elements and the given options
+pub fn dump_kdbx4_with_options(
+    db: &Database,
+    db_key: &DatabaseKey,
+    options: &DumpOptions,
+    writer: &mut dyn Write,
+) -> Result<(), DatabaseSaveError> {
+    dump_kdbx4_with_progress(db, db_key, options, &mut |_, _| {}, writer)
+}
+
+/// Dump a `KeePass` database using the key elements and the given options, calling `progress` with
+/// the number of entries written so far and the total number of entries
+pub fn dump_kdbx4_with_progress(
+    db: &Database,
+    db_key: &DatabaseKey,
+    options: &DumpOptions,
+    progress: &mut dyn FnMut(usize, usize),
+    writer: &mut dyn Write,
+) -> Result<(), DatabaseSaveError> {
     if !matches!(db.config.version, DatabaseVersion::KDB4(_)) {
         return Err(DatabaseSaveError::UnsupportedVersion);
     }
 
-    // generate encryption keys and seeds on the fly when saving
+    if options.validate_times {
+        for node in NodeIterator::new(&db.root) {
+            let node = node.borrow();
+            if let Err(err) = node.get_times().validate() {
+                let uuid = node.get_uuid();
+                return Err(DatabaseSaveError::InvalidTimes {
+                    uuid,
+                    message: err.to_string(),
+                });
+            }
+        }
+    }
+
+    for node in NodeIterator::new(&db.root) {
+        if let Some(Err(err)) = with_node::<Entry, _, _>(&node, Entry::validate_field_names) {
+            let uuid = node.borrow().get_uuid();
+            return Err(DatabaseSaveError::InvalidFieldName {
+                uuid,
+                message: err.to_string(),
+            });
+        }
+    }
+
+    let reused = match &db.original_header {
+        Some(original_header) if options.reuse_seeds => prepare_with_original_header(db, db_key, original_header, progress)?,
+        _ => None,
+    };
+
+    let prepared = match reused {
+        Some(prepared) => prepared,
+        None => {
+            let seeds = generate_seeds(db)?;
+            let payload = dump_payload(db, &seeds.inner_random_stream_key, progress)?;
+            prepare_with_seeds(db, db_key, seeds, payload)?
+        }
+    };
+
+    write_prepared(db, &prepared, writer)
+}
+
+/// Dump a `KeePass` database around a hand-crafted payload (inner header and XML), for testing how
+/// the parser copes with anomalies the dumper never produces
+#[cfg(test)]
+pub(crate) fn dump_kdbx4_with_raw_payload(
+    db: &Database,
+    db_key: &DatabaseKey,
+    payload: Vec<u8>,
+    writer: &mut dyn Write,
+) -> Result<(), DatabaseSaveError> {
+    let prepared = prepare_with_seeds(db, db_key, generate_seeds(db)?, payload)?;
+    write_prepared(db, &prepared, writer)
+}
+
+/// Generate fresh encryption keys and seeds for saving
+fn generate_seeds(db: &Database) -> Result<HeaderSeeds, DatabaseSaveError> {
     let mut master_seed = vec![0; HEADER_MASTER_SEED_SIZE];
     getrandom::getrandom(&mut master_seed)?;
 
+    let (_, kdf_seed) = db.config.kdf_config.get_kdf_and_seed()?;
+
+    Ok(HeaderSeeds {
+        master_seed,
+        outer_iv: generate_outer_iv(db)?,
+        kdf_seed,
+        inner_random_stream_key: generate_inner_random_stream_key(db)?,
+    })
+}
+
+fn prepare_with_seeds(
+    db: &Database,
+    db_key: &DatabaseKey,
+    seeds: HeaderSeeds,
+    payload: Vec<u8>,
+) -> Result<PreparedDump, DatabaseSaveError> {
+    let header_data = dump_outer_header(db, &seeds)?;
+    let (master_key, hmac_key) = derive_keys(db, db_key, &seeds)?;
+    let header_hmac = calculate_header_hmac(&header_data, &hmac_key)?;
+
+    Ok(PreparedDump {
+        header_data,
+        header_hmac,
+        master_key,
+        hmac_key,
+        outer_iv: seeds.outer_iv,
+        payload,
+    })
+}
+
+fn write_prepared(db: &Database, prepared: &PreparedDump, writer: &mut dyn Write) -> Result<(), DatabaseSaveError> {
+    let header_sha256 = crypt::calculate_sha256(&[&prepared.header_data]);
+
+    // write out header, header hash and header HMAC
+    _ = writer.write(&prepared.header_data)?;
+    _ = writer.write(header_sha256.as_slice())?;
+    _ = writer.write(&prepared.header_hmac)?;
+
+    let payload_compressed = db.config.compression_config.get_compression().compress(&prepared.payload)?;
+
+    let payload_encrypted = db
+        .config
+        .outer_cipher_config
+        .get_cipher(prepared.master_key.as_slice(), &prepared.outer_iv)?
+        .encrypt(&payload_compressed)?;
+
+    let payload_hmac = hmac_block_stream::write_hmac_block_stream(&payload_encrypted, &prepared.hmac_key)?;
+    _ = writer.write(&payload_hmac)?;
+
+    Ok(())
+}
+
+/// Everything needed to write out a KDBX4 file
+struct PreparedDump {
+    header_data: Vec<u8>,
+    header_hmac: Vec<u8>,
+    master_key: GenericArray<u8, U32>,
+    hmac_key: GenericArray<u8, U64>,
+    outer_iv: Vec<u8>,
+    payload: Vec<u8>,
+}
+
+/// Prepare a dump reusing the header and seeds of the file the database was opened from. Returns `None`
+/// if the configuration or the key changed since, in which case a fresh header has to be written.
+///
+/// The original header bytes are kept so that the order of its fields is preserved. If the content
+/// changed, the IV in that header and the inner stream key are replaced by fresh ones, since they must
+/// never be reused for different content. In that case the payload is dumped twice and `progress`
+/// starts over for the second dump.
+fn prepare_with_original_header(
+    db: &Database,
+    db_key: &DatabaseKey,
+    original_header: &ParsedHeader,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<Option<PreparedDump>, DatabaseSaveError> {
+    let Some(original_header_hmac) = &original_header.header_hmac else {
+        return Ok(None);
+    };
+    if db.config != original_header.config {
+        return Ok(None);
+    }
+
+    // the key is unchanged if it reproduces the HMAC of the original header
+    let (master_key, hmac_key) = derive_keys(db, db_key, &original_header.seeds)?;
+    if calculate_header_hmac(&original_header.header_data, &hmac_key)? != *original_header_hmac {
+        return Ok(None);
+    }
+
+    let payload = dump_payload(db, &original_header.seeds.inner_random_stream_key, progress)?;
+    if crypt::calculate_sha256(&[&payload]).as_slice() == original_header.payload_sha256.as_slice() {
+        return Ok(Some(PreparedDump {
+            header_data: original_header.header_data.clone(),
+            header_hmac: original_header_hmac.clone(),
+            master_key,
+            hmac_key,
+            outer_iv: original_header.seeds.outer_iv.clone(),
+            payload,
+        }));
+    }
+
+    let outer_iv = generate_outer_iv(db)?;
+    let header_data = replace_outer_header_entry(&original_header.header_data, HEADER_ENCRYPTION_IV, &outer_iv)?;
+    let header_hmac = calculate_header_hmac(&header_data, &hmac_key)?;
+    let payload = dump_payload(db, &generate_inner_random_stream_key(db)?, progress)?;
+
+    Ok(Some(PreparedDump {
+        header_data,
+        header_hmac,
+        master_key,
+        hmac_key,
+        outer_iv,
+        payload,
+    }))
+}
+
+fn generate_outer_iv(db: &Database) -> Result<Vec<u8>, DatabaseSaveError> {
     let mut outer_iv = vec![0; db.config.outer_cipher_config.get_iv_size()];
     getrandom::getrandom(&mut outer_iv)?;
+    Ok(outer_iv)
+}
 
+fn generate_inner_random_stream_key(db: &Database) -> Result<Vec<u8>, DatabaseSaveError> {
     let mut inner_random_stream_key = vec![0; db.config.inner_cipher_config.get_key_size()];
     getrandom::getrandom(&mut inner_random_stream_key)?;
+    Ok(inner_random_stream_key)
+}
 
-    let (kdf, kdf_seed) = db.config.kdf_config.get_kdf_and_seed()?;
-
-    #[cfg(feature = "challenge_response")]
-    let db_key = db_key.clone().perform_challenge(&kdf_seed)?;
-
-    // dump the outer header - need to buffer so that SHA256 can be computed
+/// Dump the outer header - need to buffer so that SHA256 can be computed
+fn dump_outer_header(db: &Database, seeds: &HeaderSeeds) -> Result<Vec<u8>, DatabaseSaveError> {
     let mut header_data = Vec::new();
     KDBX4OuterHeader {
         version: db.config.version.clone(),
         outer_cipher_config: db.config.outer_cipher_config.clone(),
         compression_config: db.config.compression_config.clone(),
-        master_seed: master_seed.clone(),
-        outer_iv: outer_iv.clone(),
+        master_seed: seeds.master_seed.clone(),
+        outer_iv: seeds.outer_iv.clone(),
         kdf_config: db.config.kdf_config.clone(),
-        kdf_seed,
+        kdf_seed: seeds.kdf_seed.clone(),
     }
     .dump(&mut header_data)?;
+    Ok(header_data)
+}
 
-    let header_sha256 = crypt::calculate_sha256(&[&header_data]);
+/// Copy a dumped outer header, replacing the value of one of its entries and keeping everything else in place
+fn replace_outer_header_entry(header_data: &[u8], entry_type: u8, value: &[u8]) -> Result<Vec<u8>, DatabaseSaveError> {
+    let mut pos = DatabaseVersion::get_version_header_size();
+    let mut result = header_data[..pos].to_vec();
+
+    while pos + 5 <= header_data.len() {
+        let current_type = header_data[pos];
+        let entry_length = LittleEndian::read_u32(&header_data[(pos + 1)..(pos + 5)]) as usize;
+        let entry_end = (pos + 5 + entry_length).min(header_data.len());
+
+        if current_type == entry_type {
+            result.write_u8(current_type)?;
+            result.write_with_len(value)?;
+        } else {
+            result.extend_from_slice(&header_data[pos..entry_end]);
+        }
 
-    // write out header and header hash
-    _ = writer.write(&header_data)?;
-    _ = writer.write(header_sha256.as_slice())?;
+        pos = entry_end;
+    }
+
+    Ok(result)
+}
+
+/// Derive the master key and the HMAC key from the key elements and the seeds
+#[allow(clippy::type_complexity)]
+fn derive_keys(
+    db: &Database,
+    db_key: &DatabaseKey,
+    seeds: &HeaderSeeds,
+) -> Result<(GenericArray<u8, U32>, GenericArray<u8, U64>), DatabaseSaveError> {
+    #[cfg(feature = "challenge_response")]
+    let db_key = db_key.clone().perform_challenge(&seeds.kdf_seed)?;
 
     // derive master key from composite key, transform_seed, transform_rounds and master_seed
     let key_elements = db_key.get_key_elements()?;
     let key_elements: Vec<&[u8]> = key_elements.iter().map(|v| &v[..]).collect();
     let composite_key = crypt::calculate_sha256(&key_elements);
-    let transformed_key = kdf.transform_key(&composite_key)?;
-    let master_key = crypt::calculate_sha256(&[&master_seed, transformed_key.as_slice()]);
+    let transformed_key = db
+        .config
+        .kdf_config
+        .get_kdf_seeded(&seeds.kdf_seed)
+        .transform_key(&composite_key, db_key.get_kdf_progress())?;
+    let master_key = crypt::calculate_sha256(&[&seeds.master_seed, transformed_key.as_slice()]);
+
+    let hmac_key = crypt::calculate_sha512(&[&seeds.master_seed, transformed_key.as_slice(), &hmac_block_stream::HMAC_KEY_END]);
 
-    // verify credentials
-    let hmac_key = crypt::calculate_sha512(&[&master_seed, transformed_key.as_slice(), &hmac_block_stream::HMAC_KEY_END]);
-    let header_hmac_key = hmac_block_stream::get_hmac_block_key(u64::MAX, &hmac_key);
-    let header_hmac = crypt::calculate_hmac(&[&header_data], header_hmac_key.as_slice())?;
+    Ok((master_key, hmac_key))
+}
 
-    _ = writer.write(header_hmac.as_slice())?;
+fn calculate_header_hmac(header_data: &[u8], hmac_key: &GenericArray<u8, U64>) -> Result<Vec<u8>, DatabaseSaveError> {
+    let header_hmac_key = hmac_block_stream::get_hmac_block_key(u64::MAX, hmac_key);
+    Ok(crypt::calculate_hmac(&[header_data], header_hmac_key.as_slice())?.to_vec())
+}
 
+/// Dump the inner header and the XML document into a buffer
+fn dump_payload(
+    db: &Database,
+    inner_random_stream_key: &[u8],
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<Vec<u8>, DatabaseSaveError> {
     // Initialize inner encryptor from inner header params
-    let mut inner_cipher = db.config.inner_cipher_config.get_cipher(&inner_random_stream_key);
+    let mut inner_cipher = db.config.inner_cipher_config.get_cipher(inner_random_stream_key);
 
     // dump inner header into buffer
     let mut payload = Vec::new();
     KDBX4InnerHeader {
         inner_random_stream: db.config.inner_cipher_config.clone(),
-        inner_random_stream_key,
+        inner_random_stream_key: inner_random_stream_key.to_vec(),
     }
     .dump(&db.header_attachments, &mut payload)?;
 
     // after inner header is one XML document
-    crate::xml_db::dump::dump(db, &mut *inner_cipher, &mut payload)?;
+    crate::xml_db::dump::dump_with_progress(db, &mut *inner_cipher, false, progress, &mut payload)?;
 
-    let payload_compressed = db.config.compression_config.get_compression().compress(&payload)?;
-
-    let payload_encrypted = db
-        .config
-        .outer_cipher_config
-        .get_cipher(master_key.as_slice(), &outer_iv)?
-        .encrypt(&payload_compressed)?;
-
-    let payload_hmac = hmac_block_stream::write_hmac_block_stream(&payload_encrypted, &hmac_key)?;
-    _ = writer.write(&payload_hmac)?;
-
-    Ok(())
+    Ok(payload)
 }
 
 impl HeaderAttachment {
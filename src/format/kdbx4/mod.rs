@@ -7,9 +7,13 @@ use crate::{
     format::DatabaseVersion,
 };
 
-#[cfg(feature = "save_kdbx4")]
+#[cfg(all(test, feature = "save_kdbx4"))]
 pub(crate) use crate::format::kdbx4::dump::dump_kdbx4;
-pub(crate) use crate::format::kdbx4::parse::{decrypt_kdbx4, parse_kdbx4};
+#[cfg(feature = "save_kdbx4")]
+pub(crate) use crate::format::kdbx4::dump::dump_kdbx4_with_options;
+#[cfg(feature = "save_kdbx4")]
+pub(crate) use crate::format::kdbx4::dump::dump_kdbx4_with_progress;
+pub(crate) use crate::format::kdbx4::parse::{check_key_kdbx4, decrypt_kdbx4, parse_kdbx4, read_kdf_config_kdbx4, validate_header_kdbx4};
 
 /// Size for a master seed in bytes
 #[cfg(feature = "save_kdbx4")]
@@ -61,7 +65,10 @@ mod kdbx4_tests {
     use crate::{
         config::{CompressionConfig, DatabaseConfig, InnerCipherConfig, KdfConfig, OuterCipherConfig},
         db::{group_add_child, node::*, Database, Entry, Group, HeaderAttachment},
-        format::{kdbx4::dump::dump_kdbx4, KDBX4_CURRENT_MINOR_VERSION},
+        format::{
+            kdbx4::dump::{dump_kdbx4, dump_kdbx4_with_options, dump_kdbx4_with_raw_payload},
+            KDBX4_CURRENT_MINOR_VERSION,
+        },
         key::DatabaseKey,
     };
 
@@ -222,4 +229,90 @@ mod kdbx4_tests {
         assert_eq!(header_attachments[0].flags, 1);
         assert_eq!(header_attachments[0].content, [0x01, 0x02, 0x03, 0x04]);
     }
+
+    fn outer_header_entries(data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        use byteorder::{ByteOrder, LittleEndian};
+
+        let mut entries = Vec::new();
+        let mut pos = DatabaseVersion::get_version_header_size();
+        loop {
+            let entry_type = data[pos];
+            let entry_length = LittleEndian::read_u32(&data[(pos + 1)..(pos + 5)]) as usize;
+            entries.push((entry_type, data[(pos + 5)..(pos + 5 + entry_length)].to_vec()));
+            pos += 5 + entry_length;
+            if entry_type == HEADER_END {
+                return entries;
+            }
+        }
+    }
+
+    #[test]
+    pub fn original_header_field_order() {
+        use crate::config::DumpOptions;
+
+        let data = std::fs::read("tests/resources/test_db_kdbx4_with_password_aes.kdbx").unwrap();
+        let db_key = DatabaseKey::new().with_password("demopass");
+        let db = parse_kdbx4(&data, &db_key).unwrap();
+
+        let options = DumpOptions {
+            reuse_seeds: true,
+            ..Default::default()
+        };
+        let mut encrypted_db = Vec::new();
+        dump_kdbx4_with_options(&db, &db_key, &options, &mut encrypted_db).unwrap();
+
+        // the XML written by KeePass differs from ours, so only the IV is replaced
+        let original = outer_header_entries(&data);
+        let saved = outer_header_entries(&encrypted_db);
+        assert_eq!(
+            original.iter().map(|(entry_type, _)| *entry_type).collect::<Vec<_>>(),
+            saved.iter().map(|(entry_type, _)| *entry_type).collect::<Vec<_>>()
+        );
+        for ((entry_type, original_value), (_, saved_value)) in original.iter().zip(&saved) {
+            if *entry_type == HEADER_ENCRYPTION_IV {
+                assert_ne!(original_value, saved_value);
+            } else {
+                assert_eq!(original_value, saved_value);
+            }
+        }
+
+        assert_eq!(parse_kdbx4(&encrypted_db, &db_key).unwrap(), db);
+
+        // with another key, a fresh header is written
+        let other_key = DatabaseKey::new().with_password("other");
+        let mut encrypted_db = Vec::new();
+        dump_kdbx4_with_options(&db, &other_key, &options, &mut encrypted_db).unwrap();
+        let saved = outer_header_entries(&encrypted_db);
+        let master_seed = |entries: &[(u8, Vec<u8>)]| entries.iter().find(|(entry_type, _)| *entry_type == HEADER_MASTER_SEED).cloned();
+        assert_ne!(master_seed(&original), master_seed(&saved));
+    }
+
+    #[test]
+    fn open_with_warnings() {
+        let db = Database::new(DatabaseConfig {
+            inner_cipher_config: InnerCipherConfig::Plain,
+            ..DatabaseConfig::default()
+        });
+        let db_key = DatabaseKey::new().with_password("test");
+
+        let field = |entry_type: u8, content: &[u8]| {
+            let mut field = vec![entry_type];
+            field.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            field.extend_from_slice(content);
+            field
+        };
+        let mut payload = field(INNER_HEADER_RANDOM_STREAM_ID, &InnerCipherConfig::Plain.dump().to_le_bytes());
+        payload.extend(field(INNER_HEADER_RANDOM_STREAM_KEY, &[0; 32]));
+        payload.extend(field(0x42, b"from the future"));
+        payload.extend(field(INNER_HEADER_END, &[]));
+        payload.extend_from_slice(b"<KeePassFile><Meta/><Root><Group><Name>Root</Name></Group></Root></KeePassFile>");
+
+        let mut data = Vec::new();
+        dump_kdbx4_with_raw_payload(&db, &db_key, payload, &mut data).unwrap();
+
+        let (db, warnings) = Database::open_with_warnings(&mut data.as_slice(), db_key).unwrap();
+        assert_eq!(db.root.borrow().get_title(), Some("Root"));
+        assert_eq!(warnings, vec!["Skipped unknown inner header field 0x42".to_string()]);
+        assert_eq!(db.warnings(), warnings);
+    }
 }
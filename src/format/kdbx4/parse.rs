@@ -1,6 +1,10 @@
 use std::convert::{TryFrom, TryInto};
 
 use byteorder::{ByteOrder, LittleEndian};
+use cipher::generic_array::{
+    typenum::{U32, U64},
+    GenericArray,
+};
 
 use crate::{
     config::{CompressionConfig, DatabaseConfig, InnerCipherConfig, KdfConfig, OuterCipherConfig},
@@ -21,28 +25,41 @@ use crate::{
 };
 
 use super::KDBX4InnerHeader;
+#[cfg(feature = "save_kdbx4")]
+use crate::format::{HeaderSeeds, ParsedHeader};
+
+impl TryFrom<&[u8]> for HeaderAttachment {
+    type Error = DatabaseIntegrityError;
 
-impl From<&[u8]> for HeaderAttachment {
-    fn from(data: &[u8]) -> Self {
-        let flags = data[0];
-        let content = data[1..].to_vec();
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (&flags, content) = data.split_first().ok_or_else(|| DatabaseIntegrityError::IncompleteInnerHeader {
+            missing_field: "Binary attachment flags".into(),
+        })?;
 
-        HeaderAttachment { flags, content }
+        Ok(HeaderAttachment {
+            flags,
+            content: content.to_vec(),
+        })
     }
 }
 
 /// Open, decrypt and parse a `KeePass` database from a source and key elements
 pub(crate) fn parse_kdbx4(data: &[u8], db_key: &DatabaseKey) -> Result<Database, DatabaseOpenError> {
-    let (config, header_attachments, mut inner_decryptor, xml) = decrypt_kdbx4(data, db_key)?;
+    let mut warnings = Vec::new();
+    let mut decrypted = decrypt_kdbx4_with_header(data, db_key, &mut warnings)?;
 
-    let database_content = crate::xml_db::parse::parse(&xml, &mut *inner_decryptor)?;
+    let database_content = crate::xml_db::parse::parse(&decrypted.xml, &mut *decrypted.inner_decryptor)?;
+    warnings.extend(database_content.root.warnings);
 
     let db = Database {
-        config,
-        header_attachments,
+        config: decrypted.config,
+        header_attachments: decrypted.header_attachments,
         root: rc_refcell_node(database_content.root.group).into(),
         deleted_objects: database_content.root.deleted_objects,
         meta: database_content.meta,
+        warnings,
+        #[cfg(feature = "save_kdbx4")]
+        original_header: Some(decrypted.original_header),
     };
 
     Ok(db)
@@ -54,6 +71,103 @@ pub(crate) fn decrypt_kdbx4(
     data: &[u8],
     db_key: &DatabaseKey,
 ) -> Result<(DatabaseConfig, Vec<HeaderAttachment>, Box<dyn Cipher>, Vec<u8>), DatabaseOpenError> {
+    let decrypted = decrypt_kdbx4_with_header(data, db_key, &mut Vec::new())?;
+    Ok((
+        decrypted.config,
+        decrypted.header_attachments,
+        decrypted.inner_decryptor,
+        decrypted.xml,
+    ))
+}
+
+/// A decrypted KDBX4 database whose XML is not parsed yet
+struct DecryptedKdbx4 {
+    config: DatabaseConfig,
+    header_attachments: Vec<HeaderAttachment>,
+    inner_decryptor: Box<dyn Cipher>,
+    xml: Vec<u8>,
+    /// Seeds and header of the file for re-saving it unchanged
+    #[cfg(feature = "save_kdbx4")]
+    original_header: ParsedHeader,
+}
+
+/// Like `decrypt_kdbx4`, but also returns the seeds and header of the file for re-saving it unchanged.
+/// Non-fatal issues with the inner header are pushed to `warnings`.
+fn decrypt_kdbx4_with_header(data: &[u8], db_key: &DatabaseKey, warnings: &mut Vec<String>) -> Result<DecryptedKdbx4, DatabaseOpenError> {
+    let (outer_header, hmac_block_stream, master_key, hmac_key) = unlock_kdbx4(data, db_key)?;
+
+    // read encrypted payload from hmac-verified block stream
+    let payload_encrypted = hmac_block_stream::read_hmac_block_stream(hmac_block_stream, &hmac_key)?;
+
+    // Decrypt and decompress encrypted payload
+    let payload_compressed = outer_header
+        .outer_cipher_config
+        .get_cipher(master_key.as_slice(), &outer_header.outer_iv)?
+        .decrypt(&payload_encrypted)?;
+
+    let payload = outer_header.compression_config.get_compression().decompress(&payload_compressed)?;
+
+    // KDBX4 has inner header, too - parse it
+    let (header_attachments, inner_header, body_start) = parse_inner_header(&payload, warnings)?;
+
+    // after inner header is one XML document
+    let xml = &payload[body_start..];
+
+    // initialize the inner decryptor
+    let inner_decryptor = inner_header.inner_random_stream.get_cipher(&inner_header.inner_random_stream_key);
+
+    let config = DatabaseConfig {
+        version: outer_header.version,
+        outer_cipher_config: outer_header.outer_cipher_config,
+        compression_config: outer_header.compression_config,
+        inner_cipher_config: inner_header.inner_random_stream,
+        kdf_config: outer_header.kdf_config,
+    };
+
+    // the header hash and HMAC (32 bytes each) sit between the header data and the block stream
+    #[cfg(feature = "save_kdbx4")]
+    let original_header = {
+        let header_end = data.len() - hmac_block_stream.len() - 64;
+        ParsedHeader {
+            config: config.clone(),
+            seeds: HeaderSeeds {
+                master_seed: outer_header.master_seed,
+                outer_iv: outer_header.outer_iv,
+                kdf_seed: outer_header.kdf_seed,
+                inner_random_stream_key: inner_header.inner_random_stream_key,
+            },
+            header_data: data[..header_end].to_vec(),
+            header_hmac: Some(data[(header_end + 32)..(header_end + 64)].to_vec()),
+            payload_sha256: crypt::calculate_sha256(&[&payload]).to_vec(),
+        }
+    };
+
+    Ok(DecryptedKdbx4 {
+        config,
+        header_attachments,
+        inner_decryptor,
+        xml: xml.to_vec(),
+        #[cfg(feature = "save_kdbx4")]
+        original_header,
+    })
+}
+
+/// Check whether a key opens a `KeePass` KDBX4 database, only verifying the header HMAC
+pub(crate) fn check_key_kdbx4(data: &[u8], db_key: &DatabaseKey) -> Result<bool, DatabaseOpenError> {
+    match unlock_kdbx4(data, db_key) {
+        Ok(_) => Ok(true),
+        Err(DatabaseOpenError::Key(DatabaseKeyError::IncorrectKey)) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Parse the outer header and derive the master key and HMAC key, verifying them against the header HMAC.
+/// Returns the outer header, the HMAC block stream that follows it and the derived keys.
+#[allow(clippy::type_complexity)]
+fn unlock_kdbx4<'a>(
+    data: &'a [u8],
+    db_key: &DatabaseKey,
+) -> Result<(KDBX4OuterHeader, &'a [u8], GenericArray<u8, U32>, GenericArray<u8, U64>), DatabaseOpenError> {
     // parse header
     let (outer_header, inner_header_start) = parse_outer_header(data)?;
 
@@ -62,9 +176,14 @@ pub(crate) fn decrypt_kdbx4(
     //      header_sha256       - A Sha256 hash of header_data (for verification of header integrity)
     //      header_hmac         - A HMAC of the header_data (for verification of the key_elements)
     //      hmac_block_stream   - A HMAC-verified block stream of encrypted and compressed blocks
+    let truncated = || DatabaseIntegrityError::TruncatedOuterHeader {
+        offset: inner_header_start,
+    };
     let header_data = &data[0..inner_header_start];
-    let header_sha256 = &data[inner_header_start..(inner_header_start + 32)];
-    let header_hmac = &data[(inner_header_start + 32)..(inner_header_start + 64)];
+    let header_sha256 = data.get(inner_header_start..(inner_header_start + 32)).ok_or_else(truncated)?;
+    let header_hmac = data
+        .get((inner_header_start + 32)..(inner_header_start + 64))
+        .ok_or_else(truncated)?;
     let hmac_block_stream = &data[(inner_header_start + 64)..];
 
     // verify header
@@ -82,7 +201,7 @@ pub(crate) fn decrypt_kdbx4(
     let transformed_key = outer_header
         .kdf_config
         .get_kdf_seeded(&outer_header.kdf_seed)
-        .transform_key(&composite_key)?;
+        .transform_key(&composite_key, db_key.get_kdf_progress())?;
     let t_k = transformed_key.as_slice();
     let master_key = crypt::calculate_sha256(&[outer_header.master_seed.as_ref(), t_k]);
 
@@ -93,35 +212,18 @@ pub(crate) fn decrypt_kdbx4(
         return Err(DatabaseKeyError::IncorrectKey.into());
     }
 
-    // read encrypted payload from hmac-verified block stream
-    let payload_encrypted = hmac_block_stream::read_hmac_block_stream(hmac_block_stream, &hmac_key)?;
-
-    // Decrypt and decompress encrypted payload
-    let payload_compressed = outer_header
-        .outer_cipher_config
-        .get_cipher(master_key.as_slice(), &outer_header.outer_iv)?
-        .decrypt(&payload_encrypted)?;
-
-    let payload = outer_header.compression_config.get_compression().decompress(&payload_compressed)?;
-
-    // KDBX4 has inner header, too - parse it
-    let (header_attachments, inner_header, body_start) = parse_inner_header(&payload)?;
-
-    // after inner header is one XML document
-    let xml = &payload[body_start..];
-
-    // initialize the inner decryptor
-    let inner_decryptor = inner_header.inner_random_stream.get_cipher(&inner_header.inner_random_stream_key);
+    Ok((outer_header, hmac_block_stream, master_key, hmac_key))
+}
 
-    let config = DatabaseConfig {
-        version: outer_header.version,
-        outer_cipher_config: outer_header.outer_cipher_config,
-        compression_config: outer_header.compression_config,
-        inner_cipher_config: inner_header.inner_random_stream,
-        kdf_config: outer_header.kdf_config,
-    };
+/// Check that the outer header of a KDBX4 database is complete and well-formed
+pub(crate) fn validate_header_kdbx4(data: &[u8]) -> Result<(), DatabaseOpenError> {
+    parse_outer_header(data)?;
+    Ok(())
+}
 
-    Ok((config, header_attachments, inner_decryptor, xml.to_vec()))
+/// Read the key derivation settings from the outer header of a KDBX4 database
+pub(crate) fn read_kdf_config_kdbx4(data: &[u8]) -> Result<KdfConfig, DatabaseOpenError> {
+    Ok(parse_outer_header(data)?.0.kdf_config)
 }
 
 fn parse_outer_header(data: &[u8]) -> Result<(KDBX4OuterHeader, usize), DatabaseOpenError> {
@@ -149,9 +251,10 @@ fn parse_outer_header(data: &[u8]) -> Result<(KDBX4OuterHeader, usize), Database
         //   entry_buffer: [u8; entry_length]       // the entry buffer
         // )
 
-        let entry_type = data[pos];
-        let entry_length: usize = LittleEndian::read_u32(&data[pos + 1..(pos + 5)]) as usize;
-        let entry_buffer = &data[(pos + 5)..(pos + 5 + entry_length)];
+        let truncated = || DatabaseIntegrityError::TruncatedOuterHeader { offset: pos };
+        let entry_type = *data.get(pos).ok_or_else(truncated)?;
+        let entry_length = data.get((pos + 1)..(pos + 5)).ok_or_else(truncated).map(LittleEndian::read_u32)? as usize;
+        let entry_buffer = data.get((pos + 5)..(pos + 5 + entry_length)).ok_or_else(truncated)?;
 
         pos += 5 + entry_length;
 
@@ -167,7 +270,11 @@ fn parse_outer_header(data: &[u8]) -> Result<(KDBX4OuterHeader, usize), Database
             }
 
             HEADER_COMPRESSION_ID => {
-                compression_config = Some(CompressionConfig::try_from(LittleEndian::read_u32(entry_buffer))?);
+                let truncated = DatabaseIntegrityError::TruncatedOuterHeader {
+                    offset: pos - entry_length,
+                };
+                let compression_id = entry_buffer.get(..4).ok_or(truncated).map(LittleEndian::read_u32)?;
+                compression_config = Some(CompressionConfig::try_from(compression_id)?);
             }
 
             HEADER_MASTER_SEED => master_seed = Some(entry_buffer.to_vec()),
@@ -215,7 +322,10 @@ fn parse_outer_header(data: &[u8]) -> Result<(KDBX4OuterHeader, usize), Database
     ))
 }
 
-fn parse_inner_header(data: &[u8]) -> Result<(Vec<HeaderAttachment>, KDBX4InnerHeader, usize), DatabaseOpenError> {
+fn parse_inner_header(
+    data: &[u8],
+    warnings: &mut Vec<String>,
+) -> Result<(Vec<HeaderAttachment>, KDBX4InnerHeader, usize), DatabaseOpenError> {
     let mut pos = 0;
 
     let mut inner_random_stream = None;
@@ -223,9 +333,13 @@ fn parse_inner_header(data: &[u8]) -> Result<(Vec<HeaderAttachment>, KDBX4InnerH
     let mut header_attachments = Vec::new();
 
     loop {
-        let entry_type = data[pos];
-        let entry_length: usize = LittleEndian::read_u32(&data[pos + 1..(pos + 5)]) as usize;
-        let entry_buffer = &data[(pos + 5)..(pos + 5 + entry_length)];
+        let truncated = || DatabaseIntegrityError::TruncatedInnerHeader { offset: pos };
+        let entry_type = *data.get(pos).ok_or_else(truncated)?;
+        let entry_length = data.get((pos + 1)..(pos + 5)).ok_or_else(truncated).map(LittleEndian::read_u32)? as usize;
+        let entry_buffer = data
+            .get((pos + 5)..)
+            .and_then(|rest| rest.get(..entry_length))
+            .ok_or_else(truncated)?;
 
         pos += 5 + entry_length;
 
@@ -233,19 +347,22 @@ fn parse_inner_header(data: &[u8]) -> Result<(Vec<HeaderAttachment>, KDBX4InnerH
             INNER_HEADER_END => break,
 
             INNER_HEADER_RANDOM_STREAM_ID => {
-                inner_random_stream = Some(InnerCipherConfig::try_from(LittleEndian::read_u32(entry_buffer))?);
+                let truncated = DatabaseIntegrityError::TruncatedInnerHeader {
+                    offset: pos - entry_length,
+                };
+                let stream_id = entry_buffer.get(..4).ok_or(truncated).map(LittleEndian::read_u32)?;
+                inner_random_stream = Some(InnerCipherConfig::try_from(stream_id)?);
             }
 
             INNER_HEADER_RANDOM_STREAM_KEY => inner_random_stream_key = Some(entry_buffer.to_vec()),
 
             INNER_HEADER_BINARY_ATTACHMENTS => {
-                let header_attachment = HeaderAttachment::from(entry_buffer);
+                let header_attachment = HeaderAttachment::try_from(entry_buffer)?;
                 header_attachments.push(header_attachment);
             }
 
-            _ => {
-                return Err(DatabaseIntegrityError::InvalidInnerHeaderEntry { entry_type }.into());
-            }
+            // like KeePass, skip fields added by newer versions of the format
+            _ => warnings.push(format!("Skipped unknown inner header field {entry_type:#04x}")),
         }
     }
 
@@ -256,6 +373,14 @@ fn parse_inner_header(data: &[u8]) -> Result<(Vec<HeaderAttachment>, KDBX4InnerH
     let inner_random_stream = get_or_err(inner_random_stream, "Inner random stream")?;
     let inner_random_stream_key = get_or_err(inner_random_stream_key, "Inner random stream key")?;
 
+    // Salsa20 takes the key as it is, which only works with 256 bits
+    if inner_random_stream == InnerCipherConfig::Salsa20 && inner_random_stream_key.len() != 32 {
+        return Err(DatabaseIntegrityError::InvalidInnerHeaderEntry {
+            entry_type: INNER_HEADER_RANDOM_STREAM_KEY,
+        }
+        .into());
+    }
+
     let inner_header = KDBX4InnerHeader {
         inner_random_stream,
         inner_random_stream_key,
@@ -263,3 +388,58 @@ fn parse_inner_header(data: &[u8]) -> Result<(Vec<HeaderAttachment>, KDBX4InnerH
 
     Ok((header_attachments, inner_header, pos))
 }
+
+#[cfg(test)]
+mod inner_header_tests {
+    use super::parse_inner_header;
+    use crate::config::InnerCipherConfig;
+
+    fn field(entry_type: u8, content: &[u8]) -> Vec<u8> {
+        let mut field = vec![entry_type];
+        field.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        field.extend_from_slice(content);
+        field
+    }
+
+    #[test]
+    fn skip_unknown_inner_header_fields() {
+        let mut data = field(0x01, &3u32.to_le_bytes());
+        data.extend(field(0x42, b"from the future"));
+        data.extend(field(0x02, &[7; 64]));
+        data.extend(field(0x03, b"\x01attachment"));
+        data.extend(field(0x00, &[]));
+        let body_start = data.len();
+        data.extend_from_slice(b"<KeePassFile/>");
+
+        let mut warnings = Vec::new();
+        let (attachments, inner_header, pos) = parse_inner_header(&data, &mut warnings).unwrap();
+        assert_eq!(pos, body_start);
+        assert_eq!(warnings, vec!["Skipped unknown inner header field 0x42".to_string()]);
+        assert_eq!(inner_header.inner_random_stream, InnerCipherConfig::ChaCha20);
+        assert_eq!(inner_header.inner_random_stream_key, vec![7; 64]);
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].content, b"attachment");
+
+        // a field running past the end of the data is an error, not a panic
+        assert!(parse_inner_header(&data[..body_start - 10], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn reject_malformed_inner_header_fields() {
+        let stream_id = field(0x01, &3u32.to_le_bytes());
+        let stream_key = field(0x02, &[7; 64]);
+        let end = field(0x00, &[]);
+        let parse = |fields: &[&[u8]]| parse_inner_header(&fields.concat(), &mut Vec::new());
+
+        assert!(parse(&[&stream_id, &stream_key, &end]).is_ok());
+        assert!(parse(&[&stream_id, &stream_key]).is_err());
+        assert!(parse(&[&stream_id, &stream_key, &end[..3]]).is_err());
+        // an attachment without its flags byte
+        assert!(parse(&[&stream_id, &stream_key, &field(0x03, &[]), &end]).is_err());
+        // a stream ID shorter than 32 bits
+        assert!(parse(&[&field(0x01, &[3]), &stream_key, &end]).is_err());
+        // Salsa20 with a key that is not 256 bits long
+        assert!(parse(&[&field(0x01, &2u32.to_le_bytes()), &stream_key, &end]).is_err());
+        assert!(parse(&[&field(0x01, &2u32.to_le_bytes()), &field(0x02, &[7; 32]), &end]).is_ok());
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod kdb;
+pub(crate) mod kdb2;
 pub(crate) mod kdbx3;
 pub(crate) mod kdbx4;
 pub(crate) mod kdbx_header_field_id;
@@ -10,6 +11,8 @@ use std::io::Write;
 use byteorder::WriteBytesExt;
 use byteorder::{ByteOrder, LittleEndian};
 
+#[cfg(feature = "save_kdbx4")]
+use crate::config::DatabaseConfig;
 use crate::error::DatabaseIntegrityError;
 
 const KDBX_IDENTIFIER: [u8; 4] = [0x03, 0xd9, 0xa2, 0x9a];
@@ -26,6 +29,30 @@ pub const KDBX4_MAJOR_VERSION: u16 = 4;
 
 pub const KDBX4_CURRENT_MINOR_VERSION: u16 = 0;
 
+/// Random seeds and IVs that a database file is encrypted with
+#[cfg(feature = "save_kdbx4")]
+#[derive(Debug, Clone)]
+pub(crate) struct HeaderSeeds {
+    pub(crate) master_seed: Vec<u8>,
+    pub(crate) outer_iv: Vec<u8>,
+    pub(crate) kdf_seed: Vec<u8>,
+    pub(crate) inner_random_stream_key: Vec<u8>,
+}
+
+/// The header of a database file as it was parsed, kept on the `Database` so that saving it again can
+/// preserve the header, along with a hash of the payload to tell whether the content changed since
+#[cfg(feature = "save_kdbx4")]
+#[derive(Debug, Clone)]
+pub(crate) struct ParsedHeader {
+    pub(crate) config: DatabaseConfig,
+    pub(crate) seeds: HeaderSeeds,
+    /// Raw outer header, with its fields in the order they appeared in the file
+    pub(crate) header_data: Vec<u8>,
+    /// HMAC of the outer header, only present in KDBX4
+    pub(crate) header_hmac: Option<Vec<u8>>,
+    pub(crate) payload_sha256: Vec<u8>,
+}
+
 /// Supported KDB database versions, with the associated
 /// minor version.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,10 +17,11 @@ pub(crate) fn read_hmac_block_stream(data: &[u8], key: &GenericArray<u8, U64>) -
     let mut block_index: u64 = 0;
 
     while pos < data.len() {
-        let hmac = &data[pos..(pos + 32)];
-        let size_bytes = &data[(pos + 32)..(pos + 36)];
+        let truncated = || BlockStreamError::TruncatedBlock { block_index };
+        let hmac = data.get(pos..(pos + 32)).ok_or_else(truncated)?;
+        let size_bytes = data.get((pos + 32)..(pos + 36)).ok_or_else(truncated)?;
         let size = LittleEndian::read_u32(size_bytes) as usize;
-        let block = &data[(pos + 36)..(pos + 36 + size)];
+        let block = data.get((pos + 36)..).and_then(|rest| rest.get(..size)).ok_or_else(truncated)?;
 
         // verify block hmac
         let hmac_block_key = get_hmac_block_key(block_index, key);
@@ -1,6 +1,7 @@
-use std::io::Read;
+use std::{io::Read, sync::Arc};
 
 use base64::{engine::general_purpose as base64_engine, Engine as _};
+use secstr::SecStr;
 use xml::name::OwnedName;
 use xml::reader::{EventReader, XmlEvent};
 use zeroize::{Zeroize, ZeroizeOnDrop};
@@ -16,6 +17,26 @@ use crate::{crypt::calculate_sha256, error::DatabaseKeyError};
 pub type KeyElement = Vec<u8>;
 pub type KeyElements = Vec<KeyElement>;
 
+/// Callback invoked while the key derivation function runs, with the work done so far and the
+/// total amount of work. AES-KDF reports transformation rounds, Argon2 only reports `(0, 1)` when
+/// starting and `(1, 1)` when finished. Returning `false` cancels the key derivation.
+pub type KdfProgress = dyn Fn(u64, u64) -> bool + Send + Sync;
+
+#[derive(Clone)]
+struct KdfProgressCallback(Arc<KdfProgress>);
+
+impl std::fmt::Debug for KdfProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("KdfProgressCallback")
+    }
+}
+
+impl PartialEq for KdfProgressCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
 #[cfg(feature = "challenge_response")]
 fn parse_yubikey_slot(slot_number: &str) -> Result<Slot, DatabaseKeyError> {
     if let Some(slot) = Slot::from_str(slot_number) {
@@ -84,6 +105,30 @@ fn parse_xml_keyfile(xml: &[u8]) -> Result<KeyElement, DatabaseKeyError> {
     }
 }
 
+/// Check that keyfile contents can be used as a key: they must not be empty, and an XML keyfile
+/// must contain a key instead of being hashed as a whole
+fn validate_keyfile(buffer: &[u8]) -> Result<(), DatabaseKeyError> {
+    if buffer.is_empty() {
+        return Err(DatabaseKeyError::InvalidKeyFile);
+    }
+    match parse_xml_keyfile(buffer) {
+        Err(DatabaseKeyError::InvalidKeyFile) if is_keyfile_xml(buffer) => Err(DatabaseKeyError::InvalidKeyFile),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the buffer is an XML document with a `KeyFile` root element
+fn is_keyfile_xml(buffer: &[u8]) -> bool {
+    EventReader::new(buffer)
+        .into_iter()
+        .find_map(|ev| match ev {
+            Ok(XmlEvent::StartElement { name, .. }) => Some(name.local_name == "KeyFile"),
+            Ok(_) => None,
+            Err(_) => Some(false),
+        })
+        .unwrap_or(false)
+}
+
 fn parse_keyfile(buffer: &[u8]) -> KeyElement {
     // try to parse the buffer as XML, if successful, use that data instead of full file
     if let Ok(v) = parse_xml_keyfile(buffer) {
@@ -204,23 +249,31 @@ impl ChallengeResponseKey {
 /// A `KeePass` key, which might consist of a password and/or a keyfile
 #[derive(Debug, Clone, Default, PartialEq, Zeroize, ZeroizeOnDrop)]
 pub struct DatabaseKey {
-    password: Option<String>,
+    password: Option<Vec<u8>>,
     keyfile: Option<Vec<u8>>,
     #[cfg(feature = "challenge_response")]
     challenge_response_key: Option<ChallengeResponseKey>,
     #[cfg(feature = "challenge_response")]
     challenge_response_result: Option<KeyElement>,
+    #[zeroize(skip)]
+    kdf_progress: Option<KdfProgressCallback>,
 }
 
 impl DatabaseKey {
     pub fn with_password(mut self, password: &str) -> Self {
-        self.password = Some(password.to_string());
+        self.password = Some(password.as_bytes().to_vec());
+        self
+    }
+
+    /// Like `with_password`, for a password that is already held in a `SecStr`
+    pub fn with_password_secstr(mut self, password: SecStr) -> Self {
+        self.password = Some(password.unsecure().to_vec());
         self
     }
 
     #[cfg(feature = "utilities")]
     pub fn with_password_from_prompt(mut self, prompt_message: &str) -> Result<Self, std::io::Error> {
-        self.password = Some(rpassword::prompt_password(prompt_message)?);
+        self.password = Some(rpassword::prompt_password(prompt_message)?.into_bytes());
         Ok(self)
     }
 
@@ -239,6 +292,15 @@ impl DatabaseKey {
         Ok(self)
     }
 
+    /// Like `with_keyfile`, for keyfile contents that are already in memory.
+    /// The keyfile format is detected the same way in both cases, but the contents are checked
+    /// right away: empty contents and XML keyfiles without a key are rejected.
+    pub fn with_keyfile_bytes(mut self, keyfile: &[u8]) -> Result<Self, DatabaseKeyError> {
+        validate_keyfile(keyfile)?;
+        self.keyfile = Some(keyfile.to_vec());
+        Ok(self)
+    }
+
     #[cfg(feature = "challenge_response")]
     pub fn with_challenge_response_key(mut self, challenge_response_key: ChallengeResponseKey) -> Self {
         self.challenge_response_key = Some(challenge_response_key);
@@ -255,6 +317,19 @@ impl DatabaseKey {
         Ok(self)
     }
 
+    /// Set a callback to report progress of the key derivation, and to cancel it
+    pub fn with_kdf_progress<F>(mut self, progress: F) -> Self
+    where
+        F: Fn(u64, u64) -> bool + Send + Sync + 'static,
+    {
+        self.kdf_progress = Some(KdfProgressCallback(Arc::new(progress)));
+        self
+    }
+
+    pub(crate) fn get_kdf_progress(&self) -> Option<&KdfProgress> {
+        self.kdf_progress.as_ref().map(|progress| progress.0.as_ref())
+    }
+
     pub fn new() -> Self {
         DatabaseKey::default()
     }
@@ -263,7 +338,7 @@ impl DatabaseKey {
         let mut out = Vec::new();
 
         if let Some(p) = &self.password {
-            out.push(calculate_sha256(&[p.as_bytes()]).to_vec());
+            out.push(calculate_sha256(&[p]).to_vec());
         }
 
         if let Some(ref f) = self.keyfile {
@@ -303,6 +378,7 @@ impl DatabaseKey {
 mod key_tests {
 
     use crate::error::DatabaseKeyError;
+    use secstr::SecStr;
 
     use super::DatabaseKey;
 
@@ -356,6 +432,29 @@ mod key_tests {
             .get_key_elements()?;
         assert_eq!(ke.len(), 1);
 
+        let from_str = DatabaseKey::new().with_password("asdf").get_key_elements()?;
+        let from_secstr = DatabaseKey::new().with_password_secstr(SecStr::from("asdf")).get_key_elements()?;
+        assert_eq!(from_str, from_secstr);
+
+        // in-memory keyfiles are handled exactly like the ones read from a `Read`
+        for keyfile in [
+            "bare-key-file",
+            "0123456789ABCDEF0123456789ABCDEF",
+            "<KeyFile><Key><Data>NXyYiJMHg3ls+eBmjbAjWec9lcOToJiofbhNiFMTJMw=</Data></Key></KeyFile>",
+            xml_keyfile_v2.trim(),
+        ] {
+            let from_read = DatabaseKey::new().with_keyfile(&mut keyfile.as_bytes())?.get_key_elements()?;
+            let from_bytes = DatabaseKey::new().with_keyfile_bytes(keyfile.as_bytes())?.get_key_elements()?;
+            assert_eq!(from_read, from_bytes);
+        }
+        assert!(DatabaseKey::new().with_keyfile_bytes(b"").is_err());
+        assert!(DatabaseKey::new()
+            .with_keyfile_bytes(b"<KeyFile><Meta><Version>2.0</Version></Meta></KeyFile>")
+            .is_err());
+        assert!(DatabaseKey::new()
+            .with_keyfile_bytes(b"<Not><A><KeyFile></KeyFile></A></Not>")
+            .is_ok());
+
         // other XML files will just be hashed as a "bare" keyfile
         let ke = DatabaseKey::new()
             .with_keyfile(&mut "<Not><A><KeyFile></KeyFile></A></Not>".as_bytes())?
@@ -370,6 +469,7 @@ mod key_tests {
             challenge_response_key: None,
             #[cfg(feature = "challenge_response")]
             challenge_response_result: None,
+            kdf_progress: None,
         }
         .get_key_elements()
         .is_err());
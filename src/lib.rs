@@ -19,7 +19,7 @@ pub use self::key::ChallengeResponseKey;
 pub use self::{
     config::DatabaseConfig,
     error::{BoxError, Error, Result},
-    key::DatabaseKey,
+    key::{DatabaseKey, KdfProgress},
 };
 pub use chrono::NaiveDateTime;
 pub use uuid::Uuid;
@@ -31,7 +31,10 @@ impl VariantDictionary {
     }
 
     pub(crate) fn parse(buffer: &[u8]) -> Result<VariantDictionary, VariantDictionaryError> {
-        let version = LittleEndian::read_u16(&buffer[0..2]);
+        // a dictionary that is cut off never reaches its end marker
+        let truncated = || VariantDictionaryError::NotTerminated;
+
+        let version = buffer.get(0..2).map(LittleEndian::read_u16).ok_or_else(truncated)?;
 
         if version != VARIANT_DICTIONARY_VERSION {
             return Err(VariantDictionaryError::InvalidVersion { version });
@@ -44,24 +47,31 @@ impl VariantDictionary {
             let value_type = buffer[pos];
             pos += 1;
 
-            let key_length = LittleEndian::read_u32(&buffer[pos..(pos + 4)]) as usize;
+            let key_length = buffer.get(pos..(pos + 4)).map(LittleEndian::read_u32).ok_or_else(truncated)? as usize;
             pos += 4;
 
-            let key = String::from_utf8_lossy(&buffer[pos..(pos + key_length)]).to_string();
+            let key = buffer.get(pos..).and_then(|rest| rest.get(..key_length)).ok_or_else(truncated)?;
+            let key = String::from_utf8_lossy(key).to_string();
             pos += key_length;
 
-            let value_length = LittleEndian::read_u32(&buffer[pos..(pos + 4)]) as usize;
+            let value_length = buffer.get(pos..(pos + 4)).map(LittleEndian::read_u32).ok_or_else(truncated)? as usize;
             pos += 4;
 
-            let value_buffer = &buffer[pos..(pos + value_length)];
+            let value_buffer = buffer.get(pos..).and_then(|rest| rest.get(..value_length)).ok_or_else(truncated)?;
             pos += value_length;
 
+            let fixed = |size: usize| {
+                value_buffer
+                    .get(..size)
+                    .ok_or_else(|| VariantDictionaryError::Mistyped { key: key.clone() })
+            };
+
             let value = match value_type {
-                U32_TYPE_ID => VariantDictionaryValue::UInt32(LittleEndian::read_u32(value_buffer)),
-                U64_TYPE_ID => VariantDictionaryValue::UInt64(LittleEndian::read_u64(value_buffer)),
+                U32_TYPE_ID => VariantDictionaryValue::UInt32(LittleEndian::read_u32(fixed(4)?)),
+                U64_TYPE_ID => VariantDictionaryValue::UInt64(LittleEndian::read_u64(fixed(8)?)),
                 BOOL_TYPE_ID => VariantDictionaryValue::Bool(value_buffer != [0]),
-                I32_TYPE_ID => VariantDictionaryValue::Int32(LittleEndian::read_i32(value_buffer)),
-                I64_TYPE_ID => VariantDictionaryValue::Int64(LittleEndian::read_i64(value_buffer)),
+                I32_TYPE_ID => VariantDictionaryValue::Int32(LittleEndian::read_i32(fixed(4)?)),
+                I64_TYPE_ID => VariantDictionaryValue::Int64(LittleEndian::read_i64(fixed(8)?)),
                 STR_TYPE_ID => VariantDictionaryValue::String(String::from_utf8_lossy(value_buffer).to_string()),
                 BYTES_TYPE_ID => VariantDictionaryValue::ByteArray(value_buffer.to_vec()),
                 _ => {
@@ -301,6 +311,17 @@ mod variant_dictionary_tests {
         dbg!(&res);
         assert!(matches!(res, Err(VariantDictionaryError::InvalidValueType { value_type: 0xAA })));
 
+        let res = VariantDictionary::parse(&hex!("00"));
+        assert!(matches!(res, Err(VariantDictionaryError::NotTerminated)));
+
+        // the key runs past the end of the buffer
+        let res = VariantDictionary::parse(&hex!("000104FFFFFF004142430400000015CD5B0700"));
+        assert!(matches!(res, Err(VariantDictionaryError::NotTerminated)));
+
+        // a u32 value with only two bytes
+        let res = VariantDictionary::parse(&hex!("000104030000004142430200000015CD00"));
+        assert!(matches!(res, Err(VariantDictionaryError::Mistyped { .. })));
+
         Ok(())
     }
 
@@ -1,46 +1,62 @@
 use base64::{engine::general_purpose as base64_engine, Engine as _};
+use secstr::SecStr;
 use xml::writer::{EventWriter, XmlEvent as WriterEvent};
 
 use crate::{
     crypt::ciphers::Cipher,
-    db::{AutoType, AutoTypeAssociation, Entry, History, Value},
-    xml_db::dump::{DumpXml, SimpleTag},
+    db::{AttachmentRef, AutoType, AutoTypeAssociation, Entry, History, Value},
+    xml_db::{
+        dump::{DumpProgress, DumpXml, SimpleTag},
+        is_xml_char,
+    },
 };
 
-fn escape_xml(input: &str) -> String {
-    input
-        .chars()
-        .map(|c| match c {
-            '<' => "&lt;".to_string(),
-            '>' => "&gt;".to_string(),
-            '&' => "&amp;".to_string(),
-            '\'' => "&apos;".to_string(),
-            '"' => "&quot;".to_string(),
-            _ if c.is_control() && c != '\n' && c != '\r' && c != '\t' => {
-                format!("&#x{:X};", c as u32)
-            }
-            _ => c.to_string(),
-        })
-        .collect()
+/// Drop the characters that XML 1.0 does not allow, like `KeePass` does when saving. Markup
+/// characters are escaped by the XML writer.
+fn xml_safe(input: &str) -> String {
+    input.chars().filter(|&c| is_xml_char(c)).collect()
 }
 
 impl DumpXml for Entry {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
+        self.dump_xml_with_progress(writer, inner_cipher, &mut DumpProgress::new(&mut |_, _| {}, 0))
+    }
+
+    /// Writes the entry, protecting the standard fields that the memory protection settings in
+    /// `progress` require to be protected even if they are held unprotected in memory
+    fn dump_xml_with_progress<E: std::io::Write>(
+        &self,
+        writer: &mut EventWriter<E>,
+        inner_cipher: &mut dyn Cipher,
+        progress: &mut DumpProgress,
+    ) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("Entry"))?;
 
         SimpleTag("UUID", &self.uuid).dump_xml(writer, inner_cipher)?;
 
-        SimpleTag("Tags", &escape_xml(&self.tags.join(";"))).dump_xml(writer, inner_cipher)?;
+        SimpleTag("Tags", &xml_safe(&self.tags.join(";"))).dump_xml(writer, inner_cipher)?;
 
-        for (field_name, field_value) in &self.fields {
+        // `fields` is a HashMap, so sort by key to keep the output reproducible
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        for (field_name, field_value) in fields {
             writer.write(WriterEvent::start_element("String"))?;
 
-            SimpleTag("Key", &escape_xml(field_name)).dump_xml(writer, inner_cipher)?;
-            field_value.dump_xml(writer, inner_cipher)?;
+            SimpleTag("Key", &xml_safe(field_name)).dump_xml(writer, inner_cipher)?;
+            match field_value {
+                Value::Unprotected(value) if progress.protects_field(field_name) => {
+                    Value::Protected(SecStr::from(value.as_str())).dump_xml(writer, inner_cipher)?;
+                }
+                _ => field_value.dump_xml(writer, inner_cipher)?,
+            }
 
             writer.write(WriterEvent::end_element())?; // String
         }
 
+        for attachment in &self.attachments {
+            attachment.dump_xml(writer, inner_cipher)?;
+        }
+
         self.custom_data.dump_xml(writer, inner_cipher)?;
 
         if let Some(ref value) = self.autotype {
@@ -58,15 +74,15 @@ impl DumpXml for Entry {
         }
 
         if let Some(ref value) = self.foreground_color {
-            SimpleTag("ForegroundColor", &escape_xml(&value.to_string())).dump_xml(writer, inner_cipher)?;
+            SimpleTag("ForegroundColor", &value.to_string()).dump_xml(writer, inner_cipher)?;
         }
 
         if let Some(ref value) = self.background_color {
-            SimpleTag("BackgroundColor", &escape_xml(&value.to_string())).dump_xml(writer, inner_cipher)?;
+            SimpleTag("BackgroundColor", &value.to_string()).dump_xml(writer, inner_cipher)?;
         }
 
         if let Some(ref value) = self.override_url {
-            SimpleTag("OverrideURL", &escape_xml(value)).dump_xml(writer, inner_cipher)?;
+            SimpleTag("OverrideURL", &xml_safe(value)).dump_xml(writer, inner_cipher)?;
         }
 
         if let Some(value) = self.quality_check {
@@ -74,7 +90,7 @@ impl DumpXml for Entry {
         }
 
         if let Some(ref value) = self.history {
-            value.dump_xml(writer, inner_cipher)?;
+            value.dump_xml_with_progress(writer, inner_cipher, progress)?;
         }
 
         writer.write(WriterEvent::end_element())?; // Entry
@@ -83,11 +99,25 @@ impl DumpXml for Entry {
     }
 }
 
+impl DumpXml for AttachmentRef {
+    fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
+        writer.write(WriterEvent::start_element("Binary"))?;
+
+        SimpleTag("Key", &self.name).dump_xml(writer, inner_cipher)?;
+
+        writer.write(WriterEvent::start_element("Value").attr("Ref", &self.identifier))?;
+        writer.write(WriterEvent::end_element())?; // Value
+
+        writer.write(WriterEvent::end_element())?; // Binary
+        Ok(())
+    }
+}
+
 impl DumpXml for Value {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
         match self {
             Value::Bytes(b) => SimpleTag("Value", std::str::from_utf8(b).expect("utf-8")).dump_xml(writer, inner_cipher),
-            Value::Unprotected(s) => SimpleTag("Value", &escape_xml(s)).dump_xml(writer, inner_cipher),
+            Value::Unprotected(s) => SimpleTag("Value", &xml_safe(s)).dump_xml(writer, inner_cipher),
             Value::Protected(p) => {
                 writer.write(WriterEvent::start_element("Value").attr("Protected", "True"))?;
 
@@ -109,9 +139,10 @@ impl DumpXml for AutoType {
         writer.write(WriterEvent::start_element("AutoType"))?;
 
         SimpleTag("Enabled", self.enabled).dump_xml(writer, inner_cipher)?;
+        SimpleTag("DataTransferObfuscation", usize::from(self.data_transfer_obfuscation)).dump_xml(writer, inner_cipher)?;
 
         if let Some(ref value) = self.sequence {
-            SimpleTag("DefaultSequence", &escape_xml(value)).dump_xml(writer, inner_cipher)?;
+            SimpleTag("DefaultSequence", &xml_safe(value)).dump_xml(writer, inner_cipher)?;
         }
 
         for assoc in &self.associations {
@@ -128,11 +159,11 @@ impl DumpXml for AutoTypeAssociation {
         writer.write(WriterEvent::start_element("Association"))?;
 
         if let Some(ref value) = self.window {
-            SimpleTag("Window", &escape_xml(value)).dump_xml(writer, inner_cipher)?;
+            SimpleTag("Window", &xml_safe(value)).dump_xml(writer, inner_cipher)?;
         }
 
         if let Some(ref value) = self.sequence {
-            SimpleTag("KeystrokeSequence", &escape_xml(value)).dump_xml(writer, inner_cipher)?;
+            SimpleTag("KeystrokeSequence", &xml_safe(value)).dump_xml(writer, inner_cipher)?;
         }
 
         writer.write(WriterEvent::end_element())?;
@@ -142,10 +173,19 @@ impl DumpXml for AutoTypeAssociation {
 
 impl DumpXml for History {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
+        self.dump_xml_with_progress(writer, inner_cipher, &mut DumpProgress::new(&mut |_, _| {}, 0))
+    }
+
+    fn dump_xml_with_progress<E: std::io::Write>(
+        &self,
+        writer: &mut EventWriter<E>,
+        inner_cipher: &mut dyn Cipher,
+        progress: &mut DumpProgress,
+    ) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("History"))?;
 
         for entry in &self.entries {
-            entry.dump_xml(writer, inner_cipher)?;
+            entry.dump_xml_with_progress(writer, inner_cipher, progress)?;
         }
 
         writer.write(WriterEvent::end_element())?;
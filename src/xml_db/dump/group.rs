@@ -1,12 +1,21 @@
 use crate::{
     crypt::ciphers::Cipher,
     db::{with_node, Entry, Group, NodePtr},
-    xml_db::dump::{DumpXml, SimpleTag},
+    xml_db::dump::{DumpProgress, DumpXml, SimpleTag},
 };
 use xml::writer::{EventWriter, XmlEvent as WriterEvent};
 
 impl DumpXml for Group {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
+        self.dump_xml_with_progress(writer, inner_cipher, &mut DumpProgress::new(&mut |_, _| {}, 0))
+    }
+
+    fn dump_xml_with_progress<E: std::io::Write>(
+        &self,
+        writer: &mut EventWriter<E>,
+        inner_cipher: &mut dyn Cipher,
+        progress: &mut DumpProgress,
+    ) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("Group"))?;
 
         SimpleTag("Name", self.name.as_deref().unwrap_or("")).dump_xml(writer, inner_cipher)?;
@@ -45,8 +54,12 @@ impl DumpXml for Group {
             SimpleTag("LastTopVisibleEntry", value).dump_xml(writer, inner_cipher)?;
         }
 
+        if !self.tags.is_empty() {
+            SimpleTag("Tags", &self.tags.join(";")).dump_xml(writer, inner_cipher)?;
+        }
+
         for child in &self.children {
-            child.dump_xml(writer, inner_cipher)?;
+            child.dump_xml_with_progress(writer, inner_cipher, progress)?;
         }
 
         writer.write(WriterEvent::end_element())?; // Group
@@ -57,11 +70,25 @@ impl DumpXml for Group {
 
 impl DumpXml for NodePtr {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
+        self.dump_xml_with_progress(writer, inner_cipher, &mut DumpProgress::new(&mut |_, _| {}, 0))
+    }
+
+    fn dump_xml_with_progress<E: std::io::Write>(
+        &self,
+        writer: &mut EventWriter<E>,
+        inner_cipher: &mut dyn Cipher,
+        progress: &mut DumpProgress,
+    ) -> Result<(), xml::writer::Error> {
         use std::io::{Error, ErrorKind::Other};
         let err = xml::writer::Error::Io(Error::new(Other, "Node is neither an entry nor a group"));
-        let _ = with_node::<Group, _, _>(self, |g| g.dump_xml(writer, inner_cipher))
-            .or_else(|| with_node::<Entry, _, _>(self, |e| e.dump_xml(writer, inner_cipher)))
-            .ok_or(err)?;
-        Ok(())
+        with_node::<Group, _, _>(self, |g| g.dump_xml_with_progress(writer, inner_cipher, progress))
+            .or_else(|| {
+                with_node::<Entry, _, _>(self, |e| {
+                    e.dump_xml_with_progress(writer, inner_cipher, progress)?;
+                    progress.entry_dumped();
+                    Ok(())
+                })
+            })
+            .ok_or(err)?
     }
 }
@@ -2,9 +2,8 @@ use base64::{engine::general_purpose as base64_engine, Engine as _};
 use xml::writer::{EventWriter, XmlEvent as WriterEvent};
 
 use crate::{
-    compression::{Compression, GZipCompression},
     crypt::ciphers::Cipher,
-    db::meta::{BinaryAttachment, BinaryAttachments, CustomIcons, Icon, MemoryProtection, Meta},
+    db::meta::{BinaryAttachment, BinaryAttachments, CustomIcons, Icon, MemoryProtection, Meta, DEFAULT_GENERATOR},
     xml_db::dump::{DumpXml, SimpleTag},
 };
 
@@ -12,9 +11,8 @@ impl DumpXml for Meta {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("Meta"))?;
 
-        if let Some(ref value) = self.generator {
-            SimpleTag("Generator", value).dump_xml(writer, inner_cipher)?;
-        }
+        let generator = self.generator.as_deref().unwrap_or(DEFAULT_GENERATOR);
+        SimpleTag("Generator", generator).dump_xml(writer, inner_cipher)?;
 
         if let Some(ref value) = self.database_name {
             SimpleTag("DatabaseName", value).dump_xml(writer, inner_cipher)?;
@@ -162,13 +160,7 @@ impl DumpXml for BinaryAttachment {
 
         writer.write(start_tag)?;
 
-        let data = if self.compressed {
-            GZipCompression.compress(&self.content)?
-        } else {
-            self.content.clone()
-        };
-
-        let buf = base64_engine::STANDARD.encode(data);
+        let buf = base64_engine::STANDARD.encode(&self.content);
 
         writer.write(WriterEvent::characters(&buf))?;
 
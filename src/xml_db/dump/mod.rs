@@ -13,32 +13,96 @@ use xml::{
 
 use crate::{
     crypt::ciphers::Cipher,
-    db::{Color, CustomData, CustomDataItem, Database, DeletedObject, DeletedObjects, Times},
+    db::{
+        node_is_entry, Color, CustomData, CustomDataItem, Database, DeletedObject, DeletedObjects, MemoryProtection, NodeIterator, Times,
+    },
     xml_db::get_epoch_baseline,
 };
 
-/// Format a timestamp suitable for an XML database
+/// Format a timestamp suitable for an XML database. This is the Base64 encoded form of KDBX4, a
+/// KDBX3 dumper would have to write ISO 8601 strings instead.
 pub fn format_xml_timestamp(timestamp: &chrono::NaiveDateTime) -> String {
     let timestamp = timestamp.and_utc().timestamp() - get_epoch_baseline().and_utc().timestamp();
     let timestamp_bytes = i64::to_le_bytes(timestamp);
     base64_engine::STANDARD.encode(timestamp_bytes)
 }
 
+/// Dump the XML document of a database. With `pretty`, elements are indented on separate lines for
+/// human inspection; text content is written unchanged either way, so this does not alter values.
 #[allow(dead_code)]
-pub(crate) fn dump(db: &Database, inner_cipher: &mut dyn Cipher, writer: &mut dyn Write) -> Result<(), xml::writer::Error> {
-    let mut xml_writer = EmitterConfig::new().perform_indent(false).create_writer(writer);
+pub(crate) fn dump(db: &Database, inner_cipher: &mut dyn Cipher, pretty: bool, writer: &mut dyn Write) -> Result<(), xml::writer::Error> {
+    dump_with_progress(db, inner_cipher, pretty, &mut |_, _| {}, writer)
+}
 
-    db.dump_xml(&mut xml_writer, inner_cipher)?;
+/// Like `dump`, calling `progress` with the number of entries dumped so far and the total number
+/// of entries after each entry of the group tree is written
+pub(crate) fn dump_with_progress(
+    db: &Database,
+    inner_cipher: &mut dyn Cipher,
+    pretty: bool,
+    progress: &mut dyn FnMut(usize, usize),
+    writer: &mut dyn Write,
+) -> Result<(), xml::writer::Error> {
+    let mut xml_writer = EmitterConfig::new().perform_indent(pretty).create_writer(writer);
+
+    let total = NodeIterator::new(&db.root).filter(node_is_entry).count();
+    let mut progress = DumpProgress::new(progress, total).with_memory_protection(db.meta.memory_protection.as_ref());
+    db.dump_xml_with_progress(&mut xml_writer, inner_cipher, &mut progress)?;
 
     Ok(())
 }
 
+/// Counts the entries written while dumping a group tree, history entries are not counted. Also
+/// carries the memory protection settings of the database down to the entries.
+pub(crate) struct DumpProgress<'a> {
+    dumped: usize,
+    total: usize,
+    callback: &'a mut dyn FnMut(usize, usize),
+    memory_protection: Option<&'a MemoryProtection>,
+}
+
+impl<'a> DumpProgress<'a> {
+    pub(crate) fn new(callback: &'a mut dyn FnMut(usize, usize), total: usize) -> Self {
+        DumpProgress {
+            dumped: 0,
+            total,
+            callback,
+            memory_protection: None,
+        }
+    }
+
+    pub(crate) fn with_memory_protection(mut self, memory_protection: Option<&'a MemoryProtection>) -> Self {
+        self.memory_protection = memory_protection;
+        self
+    }
+
+    /// Whether the memory protection settings require the field `field_name` to be protected
+    pub(crate) fn protects_field(&self, field_name: &str) -> bool {
+        self.memory_protection
+            .is_some_and(|memory_protection| memory_protection.protects_field(field_name))
+    }
+
+    pub(crate) fn entry_dumped(&mut self) {
+        self.dumped += 1;
+        (self.callback)(self.dumped, self.total);
+    }
+}
+
 /// A trait that denotes an inner `KeePass` database object can be stored into an XML database.
 ///
 /// Using an `xml::writer::EventWriter` and an inner cipher, emit a series of `XmlEvent`s to the
 /// writer to build up the XML document.
 pub(crate) trait DumpXml {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error>;
+    /// Like `dump_xml`, reporting every entry of the group tree that is written to `progress`
+    fn dump_xml_with_progress<E: std::io::Write>(
+        &self,
+        writer: &mut EventWriter<E>,
+        inner_cipher: &mut dyn Cipher,
+        _progress: &mut DumpProgress,
+    ) -> Result<(), xml::writer::Error> {
+        self.dump_xml(writer, inner_cipher)
+    }
     fn normalize_empty_elements(&self) -> bool {
         false
     }
@@ -115,13 +179,22 @@ impl<S: AsRef<str>, D: DumpXml> DumpXml for SimpleTag<S, D> {
 
 impl DumpXml for Database {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
+        self.dump_xml_with_progress(writer, inner_cipher, &mut DumpProgress::new(&mut |_, _| {}, 0))
+    }
+
+    fn dump_xml_with_progress<E: std::io::Write>(
+        &self,
+        writer: &mut EventWriter<E>,
+        inner_cipher: &mut dyn Cipher,
+        progress: &mut DumpProgress,
+    ) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("KeePassFile"))?;
 
         self.meta.dump_xml(writer, inner_cipher)?;
 
         writer.write(WriterEvent::start_element("Root"))?;
 
-        self.root.dump_xml(writer, inner_cipher)?;
+        self.root.dump_xml_with_progress(writer, inner_cipher, progress)?;
 
         self.deleted_objects.dump_xml(writer, inner_cipher)?;
 
@@ -136,7 +209,9 @@ impl DumpXml for Database {
 impl DumpXml for Times {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("Times"))?;
-        for (time_name, time) in &self.times {
+        let mut times: Vec<_> = self.times.iter().collect();
+        times.sort_by(|a, b| a.0.cmp(b.0));
+        for (time_name, time) in times {
             SimpleTag(time_name, time).dump_xml(writer, inner_cipher)?;
         }
 
@@ -153,7 +228,9 @@ impl DumpXml for CustomData {
     fn dump_xml<E: std::io::Write>(&self, writer: &mut EventWriter<E>, inner_cipher: &mut dyn Cipher) -> Result<(), xml::writer::Error> {
         writer.write(WriterEvent::start_element("CustomData"))?;
 
-        for (key, item) in &self.items {
+        let mut items: Vec<_> = self.items.iter().collect();
+        items.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, item) in items {
             writer.write(WriterEvent::start_element("Item"))?;
 
             SimpleTag("Key", key).dump_xml(writer, inner_cipher)?;
@@ -2,16 +2,94 @@
 pub mod dump;
 pub mod parse;
 
+/// Whether XML 1.0 can represent the character `c`
+pub(crate) fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'..)
+}
+
 /// In KDBX4, timestamps are stored as seconds, Base64 encoded, since 0001-01-01 00:00:00.
 /// This function returns the epoch baseline used by KDBX for date serialization.
 pub fn get_epoch_baseline() -> chrono::NaiveDateTime {
     chrono::NaiveDateTime::parse_from_str("0001-01-01T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap()
 }
 
+/// Rewrite an XML database with the plaintext of its protected values in place of the encrypted
+/// Base64, marking them with `ProtectInMemory="True"` like the unencrypted XML export of `KeePass`.
+/// The values are decrypted in document order, the order the parser reads them in.
+pub(crate) fn decrypt_protected_values(
+    xml: &[u8],
+    inner_cipher: &mut dyn crate::crypt::ciphers::Cipher,
+) -> Result<Vec<u8>, crate::error::DatabaseIntegrityError> {
+    use crate::error::{DatabaseIntegrityError, XmlParseError};
+    use base64::{engine::general_purpose as base64_engine, Engine as _};
+    use xml::{reader::XmlEvent, writer::XmlEvent as WriterEvent, EmitterConfig, ParserConfig};
+
+    let xml = xml.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(xml);
+    let reader = ParserConfig::new().ignore_comments(false).create_reader(xml);
+    let mut output = Vec::new();
+    let mut writer = EmitterConfig::new().normalize_empty_elements(false).create_writer(&mut output);
+    let malformed = |e: xml::writer::Error| DatabaseIntegrityError::Malformed { message: e.to_string() };
+
+    // the name of the field a value belongs to, for error messages
+    let mut field = String::new();
+    let mut in_key = false;
+    let mut in_protected_value = false;
+    for event in reader {
+        let event = event.map_err(XmlParseError::from)?;
+        match event {
+            XmlEvent::StartElement {
+                ref name, ref attributes, ..
+            } if name.local_name == "Value"
+                && attributes
+                    .iter()
+                    .any(|attribute| attribute.name.local_name == "Protected" && attribute.value.eq_ignore_ascii_case("true")) =>
+            {
+                in_protected_value = true;
+                let mut element = WriterEvent::start_element("Value");
+                for attribute in attributes.iter().filter(|attribute| attribute.name.local_name != "Protected") {
+                    element = element.attr(attribute.name.borrow(), &attribute.value);
+                }
+                writer.write(element.attr("ProtectInMemory", "True")).map_err(malformed)?;
+            }
+            XmlEvent::Characters(ref text) if in_protected_value => {
+                let invalid = |reason: String| XmlParseError::InvalidProtectedValue {
+                    field: field.clone(),
+                    reason,
+                };
+                let buf = base64_engine::STANDARD.decode(text).map_err(|e| invalid(e.to_string()))?;
+                let plaintext = inner_cipher.decrypt(&buf).map_err(|e| invalid(e.to_string()))?;
+                writer
+                    .write(WriterEvent::characters(&String::from_utf8_lossy(&plaintext)))
+                    .map_err(malformed)?;
+            }
+            _ => {
+                match event {
+                    XmlEvent::StartElement { ref name, .. } if name.local_name == "Key" => {
+                        in_key = true;
+                        field.clear();
+                    }
+                    XmlEvent::Characters(ref text) if in_key => field.clone_from(text),
+                    XmlEvent::EndElement { .. } => {
+                        in_key = false;
+                        in_protected_value = false;
+                    }
+                    _ => {}
+                }
+                if let Some(event) = event.as_writer_event() {
+                    writer.write(event).map_err(malformed)?;
+                }
+            }
+        }
+    }
+
+    Ok(output)
+}
+
 #[cfg(feature = "save_kdbx4")]
 #[cfg(test)]
 mod tests {
     use crate::{
+        compression::{Compression, GZipCompression},
         config::{DatabaseConfig, InnerCipherConfig},
         db::{
             entry::History,
@@ -19,12 +97,12 @@ mod tests {
             iconid::IconId,
             meta::{BinaryAttachments, CustomIcons, Icon, MemoryProtection},
             node::*,
-            node_is_equals_to, rc_refcell_node, AutoType, AutoTypeAssociation, BinaryAttachment, CustomData, CustomDataItem, Database,
-            DeletedObject, Entry, Group, Meta, Times, Value,
+            node_is_equals_to, rc_refcell_node, AttachmentRef, AutoType, AutoTypeAssociation, BinaryAttachment, CustomData, CustomDataItem,
+            Database, DeletedObject, Entry, Group, HeaderAttachment, Meta, Times, Value,
         },
         format::kdbx4,
         key::DatabaseKey,
-        xml_db::dump::DumpXml,
+        xml_db::dump::{self, DumpXml},
     };
     use chrono::NaiveDateTime;
     use secstr::SecStr;
@@ -65,6 +143,7 @@ mod tests {
 
         entry.set_autotype(Some(AutoType {
             enabled: true,
+            data_transfer_obfuscation: false,
             sequence: Some("Autotype-sequence".to_string()),
             associations: vec![
                 AutoTypeAssociation {
@@ -190,6 +269,42 @@ mod tests {
         assert!(node_is_equals_to(&decrypted_db.root, &root_group));
     }
 
+    #[test]
+    pub fn test_nested_group_expansion() {
+        assert!(Group::new("Default").is_expanded());
+
+        // Root > Level 1 (collapsed) > Level 2 (expanded) > Level 3 (collapsed)
+        let root_group = rc_refcell_node(Group::new("Root"));
+        let mut parent = root_group.clone();
+        for (level, expanded) in [true, false, true, false].into_iter().enumerate().skip(1) {
+            let group = rc_refcell_node(Group::new(&format!("Level {level}")));
+            with_node_mut::<Group, _, _>(&group, |group| group.set_expanded(expanded)).unwrap();
+            group_add_child(&parent, group.clone(), 0).unwrap();
+            parent = group;
+        }
+
+        let mut db = Database::new(DatabaseConfig::default());
+        db.root = root_group.into();
+
+        let expansion = |db: &Database| {
+            NodeIterator::new(&db.root)
+                .filter_map(|node| with_node::<Group, _, _>(&node, |group| group.is_expanded()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(expansion(&db), vec![true, false, true, false]);
+
+        let db_key = make_key();
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+        assert_eq!(expansion(&decrypted_db), vec![true, false, true, false]);
+
+        db.set_all_groups_expanded(false);
+        assert_eq!(expansion(&db), vec![false; 4]);
+        db.set_all_groups_expanded(true);
+        assert_eq!(expansion(&db), vec![true; 4]);
+    }
+
     #[test]
     pub fn test_meta() {
         let mut db = Database::new(DatabaseConfig::default());
@@ -240,12 +355,14 @@ mod tests {
                     BinaryAttachment {
                         identifier: Some("2".to_string()),
                         compressed: true,
-                        content: b"i am compressed binary data".to_vec(),
+                        content: GZipCompression.compress(b"i am compressed binary data").unwrap(),
                     },
                     BinaryAttachment {
                         identifier: None,
                         compressed: true,
-                        content: b"i am compressed binary data without an identifier".to_vec(),
+                        content: GZipCompression
+                            .compress(b"i am compressed binary data without an identifier")
+                            .unwrap(),
                     },
                 ],
             },
@@ -287,6 +404,174 @@ mod tests {
         assert_eq!(decrypted_db.meta, meta);
     }
 
+    #[test]
+    fn test_generator() {
+        let db_key = make_key();
+
+        let mut db = Database::new(DatabaseConfig::default());
+        db.set_generator("my-password-manager 1.2.3");
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+        assert_eq!(decrypted_db.meta.generator.as_deref(), Some("my-password-manager 1.2.3"));
+
+        // databases without a generator get the crate's name and version
+        db.meta.generator = None;
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+        let expected = format!("keepass-ng {}", env!("CARGO_PKG_VERSION"));
+        assert_eq!(decrypted_db.meta.generator, Some(expected));
+    }
+
+    #[test]
+    fn test_quality_check() {
+        let dump_xml = |quality_check: Option<bool>| {
+            let db = Database::new(DatabaseConfig::default());
+            let entry = Entry {
+                quality_check,
+                ..Default::default()
+            };
+            group_add_child(&db.root, rc_refcell_node(entry), 0).unwrap();
+            let mut inner_cipher = InnerCipherConfig::Plain.get_cipher(&[]);
+            let mut xml = Vec::new();
+            dump::dump(&db, &mut *inner_cipher, false, &mut xml).unwrap();
+            String::from_utf8(xml).unwrap()
+        };
+
+        assert!(!dump_xml(None).contains("QualityCheck"));
+        assert!(dump_xml(Some(false)).contains("<QualityCheck>False</QualityCheck>"));
+    }
+
+    #[test]
+    fn test_memory_protection_on_dump() {
+        let mut db = Database::new(DatabaseConfig::default());
+        db.meta.memory_protection = Some(MemoryProtection {
+            protect_username: true,
+            ..Default::default()
+        });
+        let mut entry = Entry::default();
+        entry.set_title(Some("Title"));
+        entry.set_username(Some("jdoe"));
+        entry
+            .fields
+            .insert("Password".to_string(), Value::Unprotected("secret".to_string()));
+        entry.update_history();
+        group_add_child(&db.root, rc_refcell_node(entry), 0).unwrap();
+
+        let db_key = make_key();
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+
+        let entry = group_get_children(&decrypted_db.root).unwrap()[0].clone();
+        with_node::<Entry, _, _>(&entry, |entry| {
+            assert_eq!(entry.get_username(), Some("jdoe"));
+            assert_eq!(entry.is_field_protected("UserName"), Some(true));
+            assert_eq!(entry.is_field_protected("Password"), Some(true));
+            assert_eq!(entry.is_field_protected("Title"), Some(false));
+
+            let version = &entry.history.as_ref().unwrap().get_entries()[0];
+            assert_eq!(version.is_field_protected("UserName"), Some(true));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_autotype_data_transfer_obfuscation() {
+        for data_transfer_obfuscation in [true, false] {
+            let db = Database::new(DatabaseConfig::default());
+            let mut entry = Entry::default();
+            entry.set_autotype(Some(AutoType {
+                enabled: true,
+                data_transfer_obfuscation,
+                ..Default::default()
+            }));
+            group_add_child(&db.root, rc_refcell_node(entry), 0).unwrap();
+
+            let mut inner_cipher = InnerCipherConfig::Plain.get_cipher(&[]);
+            let mut xml = Vec::new();
+            dump::dump(&db, &mut *inner_cipher, false, &mut xml).unwrap();
+            let expected = format!(
+                "<DataTransferObfuscation>{}</DataTransferObfuscation>",
+                usize::from(data_transfer_obfuscation)
+            );
+            assert!(String::from_utf8(xml).unwrap().contains(&expected));
+
+            let db_key = make_key();
+            let mut encrypted_db = Vec::new();
+            kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+            let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+
+            assert_eq!(decrypted_db, db);
+            let entry = group_get_children(&decrypted_db.root).unwrap()[0].clone();
+            let autotype = with_node::<Entry, _, _>(&entry, |e| e.get_autotype().cloned()).flatten().unwrap();
+            assert_eq!(autotype.data_transfer_obfuscation, data_transfer_obfuscation);
+        }
+    }
+
+    #[test]
+    fn test_group_tags() {
+        let db = Database::new(DatabaseConfig::default());
+
+        let mut group = Group::new("Tagged");
+        group.get_tags_mut().push("work".to_string());
+        group.get_tags_mut().push("shared".to_string());
+        group_add_child(&db.root, rc_refcell_node(group), 0).unwrap();
+        group_add_child(&db.root, rc_refcell_node(Group::new("Untagged")), 1).unwrap();
+
+        let db_key = make_key();
+
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+
+        assert_eq!(decrypted_db, db);
+        let children = group_get_children(&decrypted_db.root).unwrap();
+        with_node::<Group, _, _>(&children[0], |g| {
+            assert_eq!(g.get_tags(), &vec!["work".to_string(), "shared".to_string()])
+        });
+        with_node::<Group, _, _>(&children[1], |g| assert!(g.get_tags().is_empty()));
+    }
+
+    #[test]
+    fn test_entry_attachments() {
+        let mut db = Database::new(DatabaseConfig::default());
+        db.header_attachments = vec![
+            HeaderAttachment {
+                flags: 1,
+                content: b"first".to_vec(),
+            },
+            HeaderAttachment {
+                flags: 1,
+                content: b"second".to_vec(),
+            },
+        ];
+
+        let mut entry = Entry::default();
+        entry.get_attachments_mut().push(AttachmentRef {
+            name: "notes.txt".to_string(),
+            identifier: "1".to_string(),
+        });
+        group_add_child(&db.root, rc_refcell_node(entry), 0).unwrap();
+
+        let db_key = make_key();
+
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+
+        assert_eq!(decrypted_db, db);
+        let children = group_get_children(&decrypted_db.root).unwrap();
+        let attachments = with_node::<Entry, _, _>(&children[0], |e| e.get_attachments().to_vec()).unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].name, "notes.txt");
+        assert_eq!(
+            decrypted_db.resolve_attachment(&attachments[0]).unwrap().as_deref(),
+            Some(&b"second"[..])
+        );
+    }
+
     #[test]
     fn test_deleted_objects() {
         let mut db = Database::new(DatabaseConfig::default());
@@ -309,4 +594,134 @@ mod tests {
 
         assert_eq!(decrypted_db, db);
     }
+
+    #[test]
+    fn test_deterministic_dump() {
+        fn fixed_times() -> Times {
+            let mut times = Times::default();
+            times.set_creation(Some(Times::epoch()));
+            times.set_last_modification(Some(Times::epoch()));
+            times.set_last_access(Some(Times::epoch()));
+            times.set_location_changed(Some(Times::epoch()));
+            times.set_expiry_time(Some(Times::epoch()));
+            times
+        }
+
+        fn make_db() -> Database {
+            let db = Database::new(DatabaseConfig::default());
+            with_node_mut::<Group, _, _>(&db.root, |root| {
+                root.uuid = uuid!("00000000000000000000000000000001");
+                root.times = fixed_times();
+            });
+
+            let mut entry = Entry {
+                uuid: uuid!("00000000000000000000000000000002"),
+                times: fixed_times(),
+                ..Default::default()
+            };
+            entry.set_title(Some("Title"));
+            entry.set_username(Some("user"));
+            entry.set_url(Some("https://example.com"));
+            for i in 0..20 {
+                entry.fields.insert(format!("field-{i}"), Value::Unprotected(format!("value-{i}")));
+                entry.custom_data.items.insert(
+                    format!("custom-{i}"),
+                    CustomDataItem {
+                        value: Some(Value::Unprotected(format!("custom-value-{i}"))),
+                        last_modification_time: Some(NaiveDateTime::default()),
+                    },
+                );
+            }
+            entry.tags = vec!["b".to_string(), "a".to_string()];
+            group_add_child(&db.root, rc_refcell_node(entry), 0).unwrap();
+            db
+        }
+
+        let dump_xml = |db: &Database| {
+            let mut inner_cipher = InnerCipherConfig::Plain.get_cipher(&[]);
+            let mut xml = Vec::new();
+            dump::dump(db, &mut *inner_cipher, false, &mut xml).unwrap();
+            xml
+        };
+
+        // each database gets its own HashMap hasher state, so iteration order would differ
+        assert_eq!(dump_xml(&make_db()), dump_xml(&make_db()));
+    }
+
+    #[test]
+    fn test_pretty_dump() {
+        let db = Database::new(DatabaseConfig::default());
+        let group = rc_refcell_node(Group::new("Sub"));
+        let mut entry = Entry::default();
+        entry.set_title(Some("Title"));
+        entry.set_password(Some("  secret  "));
+        entry.set_notes(Some("  first line\n\n    indented line\n"));
+        group_add_child(&group, rc_refcell_node(entry), 0).unwrap();
+        group_add_child(&db.root, group, 0).unwrap();
+
+        let xml = db.get_xml_pretty().unwrap();
+        assert!(String::from_utf8(xml.clone()).unwrap().contains("\n    <Group>"));
+
+        let mut inner_cipher = InnerCipherConfig::Plain.get_cipher(&[]);
+        let parsed = crate::xml_db::parse::parse(&xml, &mut *inner_cipher).unwrap();
+        let parsed_root = rc_refcell_node(parsed.root.group);
+        assert!(node_is_equals_to(&parsed_root, &db.root));
+
+        let entry = &group_get_children(&group_get_children(&parsed_root).unwrap()[0]).unwrap()[0];
+        let notes = with_node::<Entry, _, _>(entry, |e| e.get_notes().map(str::to_string)).unwrap();
+        assert_eq!(notes.as_deref(), Some("  first line\n\n    indented line\n"));
+    }
+
+    #[test]
+    fn test_json_field_roundtrip() {
+        let kprpc = r#"{"version":1,"priority":0,"hide":false,"hTTPRealm":"","formFieldList":[{"name":"user","displayName":"Ünïcödé \"name\" <b>","value":"{USERNAME}","type":"FFTusername","id":"u","page":-1,"placeholderHandling":"Default"},{"name":"pass","value":"{PASSWORD}","type":"FFTpassword","id":"p","page":-1}],"altURLs":["https://example.com/?a=1&b=2"],"regExURLs":[],"blockedURLs":[],"mode":"&lt; literal &amp; entities é 🔑"}"#;
+
+        let db = Database::new(DatabaseConfig::default());
+        let mut entry = Entry::default();
+        entry.fields.insert("KPRPC JSON".to_string(), Value::Unprotected(kprpc.to_string()));
+        entry.fields.insert("<&>".to_string(), Value::Unprotected("&lt;".to_string()));
+        entry.tags = vec!["R&D".to_string(), "<web>".to_string()];
+        entry.override_url = Some("cmd://open?a=1&b=2".to_string());
+        group_add_child(&db.root, rc_refcell_node(entry), 0).unwrap();
+
+        let db_key = make_key();
+        let mut encrypted_db = Vec::new();
+        kdbx4::dump_kdbx4(&db, &db_key, &mut encrypted_db).unwrap();
+        let decrypted_db = kdbx4::parse_kdbx4(&encrypted_db, &db_key).unwrap();
+
+        let entry = group_get_children(&decrypted_db.root).unwrap()[0].clone();
+        with_node::<Entry, _, _>(&entry, |entry| {
+            assert_eq!(entry.get("KPRPC JSON"), Some(kprpc));
+            assert_eq!(entry.get("<&>"), Some("&lt;"));
+            assert_eq!(entry.tags, vec!["R&D".to_string(), "<web>".to_string()]);
+            assert_eq!(entry.override_url.as_deref(), Some("cmd://open?a=1&b=2"));
+        });
+        assert_eq!(decrypted_db, db);
+    }
+
+    #[test]
+    fn test_repair_xml_escaping() {
+        use crate::{crypt::ciphers::PlainCipher, xml_db::parse::parse_from_bytes};
+
+        // an entry as written by the dumper up to 0.9.0, which escaped values before the XML
+        // writer escaped them again
+        let xml = "<Entry><UUID>AAAAAAAAAAAAAAAAAAAAAA==</UUID><Tags>R&amp;amp;D</Tags>\
+                   <String><Key>a&amp;amp;b</Key><Value>&amp;lt;b&amp;gt; &amp;quot;x&amp;quot; &amp;amp;lt;</Value></String>\
+                   <String><Key>Plain</Key><Value>a &amp; b</Value></String>\
+                   <AutoType><Enabled>True</Enabled><DataTransferObfuscation>0</DataTransferObfuscation>\
+                   <DefaultSequence>{USERNAME}&amp;lt;</DefaultSequence></AutoType></Entry>";
+        let entry = parse_from_bytes::<Entry>(xml.as_bytes(), &mut PlainCipher).unwrap();
+        assert_eq!(entry.get("a&amp;b"), Some("&lt;b&gt; &quot;x&quot; &amp;lt;"));
+
+        let mut db = Database::new(DatabaseConfig::default());
+        let entry = rc_refcell_node(entry);
+        group_add_child(&db.root, entry.clone(), 0).unwrap();
+        assert_eq!(db.repair_xml_escaping(), 4);
+        with_node::<Entry, _, _>(&entry, |entry| {
+            assert_eq!(entry.get("a&b"), Some("<b> \"x\" &lt;"));
+            assert_eq!(entry.get("Plain"), Some("a & b"));
+            assert_eq!(entry.tags, vec!["R&D".to_string()]);
+            assert_eq!(entry.autotype.as_ref().unwrap().sequence.as_deref(), Some("{USERNAME}<"));
+        });
+    }
 }
@@ -1,22 +1,13 @@
 use crate::{
     crypt::ciphers::Cipher,
-    db::{iconid::IconId, AutoType, AutoTypeAssociation, Color, Entry, History, Times, Value},
+    db::{iconid::IconId, AttachmentRef, AutoType, AutoTypeAssociation, Color, Entry, History, Times, Value},
     xml_db::parse::{bad_event, CustomData, FromXml, IgnoreSubfield, SimpleTag, SimpleXmlEvent, XmlParseError},
 };
 use base64::{engine::general_purpose as base64_engine, Engine as _};
 use secstr::SecStr;
-use std::{convert::TryFrom, iter::Peekable};
+use std::iter::Peekable;
 use uuid::Uuid;
 
-fn decode_xml(input: &str) -> String {
-    input
-        .replace("&amp;", "&")
-        .replace("&lt;", "<")
-        .replace("&gt;", ">")
-        .replace("&quot;", "\"")
-        .replace("&apos;", "'")
-}
-
 impl FromXml for Entry {
     type Parses = Self;
 
@@ -52,9 +43,11 @@ impl FromXml for Entry {
                         out.custom_data = CustomData::from_xml(iterator, inner_cipher)?;
                     }
                     "Binary" => {
-                        let _field = BinaryField::from_xml(iterator, inner_cipher)?;
-                        // TODO reference into a binary field from the Meta. Might only appear in
-                        // kdbx3
+                        let field = BinaryField::from_xml(iterator, inner_cipher)?;
+                        out.attachments.push(AttachmentRef {
+                            name: field.key,
+                            identifier: field.identifier,
+                        });
                     }
                     "AutoType" => {
                         out.autotype = Some(AutoType::from_xml(iterator, inner_cipher)?);
@@ -63,9 +56,8 @@ impl FromXml for Entry {
                         out.times = Times::from_xml(iterator, inner_cipher)?;
                     }
                     "IconID" => {
-                        out.icon_id = SimpleTag::<Option<usize>>::from_xml(iterator, inner_cipher)?
-                            .value
-                            .map(|id| IconId::try_from(id).unwrap_or(IconId::KEY));
+                        // keep unknown indices instead of failing, like KeePass does
+                        out.icon_id = SimpleTag::<Option<usize>>::from_xml(iterator, inner_cipher)?.value.map(IconId);
                     }
                     "CustomIconUUID" => {
                         out.custom_icon_uuid = SimpleTag::<Option<Uuid>>::from_xml(iterator, inner_cipher)?.value;
@@ -123,10 +115,16 @@ impl FromXml for StringField {
             match event {
                 SimpleXmlEvent::Start(name, _) => match &name[..] {
                     "Key" => {
-                        out.key = decode_xml(&SimpleTag::<String>::from_xml(iterator, inner_cipher)?.value);
+                        out.key = SimpleTag::<String>::from_xml(iterator, inner_cipher)?.value;
                     }
                     "Value" => {
-                        let value = Value::from_xml(iterator, inner_cipher)?;
+                        let value = Value::from_xml(iterator, inner_cipher).map_err(|e| match e {
+                            XmlParseError::InvalidProtectedValue { reason, .. } => XmlParseError::InvalidProtectedValue {
+                                field: out.key.clone(),
+                                reason,
+                            },
+                            e => e,
+                        })?;
                         if !value.is_empty() {
                             out.value = Some(value);
                         }
@@ -146,7 +144,6 @@ impl FromXml for StringField {
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub(crate) struct BinaryField {
     pub key: String,
     pub identifier: String,
@@ -204,15 +201,19 @@ impl FromXml for Value {
                     .map_or(Ok(false), |v| v.to_lowercase().parse::<bool>())?;
 
                 let content = Option::<String>::from_xml(iterator, inner_cipher)?.unwrap_or(String::new());
-                let decoded_content = decode_xml(&content);
 
                 let value = if protected {
-                    let buf = base64_engine::STANDARD.decode(&decoded_content)?;
-                    let buf_decrypted = inner_cipher.decrypt(&buf)?;
+                    // the field name is filled in by the enclosing StringField
+                    let invalid = |reason: String| XmlParseError::InvalidProtectedValue {
+                        field: String::new(),
+                        reason,
+                    };
+                    let buf = base64_engine::STANDARD.decode(&content).map_err(|e| invalid(e.to_string()))?;
+                    let buf_decrypted = inner_cipher.decrypt(&buf).map_err(|e| invalid(e.to_string()))?;
                     let value = String::from_utf8_lossy(&buf_decrypted).to_string();
                     Value::Protected(SecStr::from(value))
                 } else {
-                    Value::Unprotected(decoded_content)
+                    Value::Unprotected(content)
                 };
 
                 let close_value_tag = iterator.next().ok_or(XmlParseError::Eof)?;
@@ -248,12 +249,11 @@ impl FromXml for AutoType {
                         out.enabled = SimpleTag::<bool>::from_xml(iterator, inner_cipher)?.value;
                     }
                     "DefaultSequence" => {
-                        let sequence = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
-                        out.sequence = sequence.map(|s| decode_xml(&s));
+                        out.sequence = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
                     }
                     "DataTransferObfuscation" => {
-                        let _value = SimpleTag::<Option<usize>>::from_xml(iterator, inner_cipher)?.value;
-                        // TODO probably not needed?
+                        let value = SimpleTag::<Option<usize>>::from_xml(iterator, inner_cipher)?.value;
+                        out.data_transfer_obfuscation = value.is_some_and(|v| v != 0);
                     }
                     "Association" => {
                         let ata = AutoTypeAssociation::from_xml(iterator, inner_cipher)?;
@@ -291,12 +291,10 @@ impl FromXml for AutoTypeAssociation {
             match event {
                 SimpleXmlEvent::Start(name, _) => match &name[..] {
                     "Window" => {
-                        let window = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
-                        out.window = window.map(|w| decode_xml(&w));
+                        out.window = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
                     }
                     "KeystrokeSequence" => {
-                        let sequence = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
-                        out.sequence = sequence.map(|s| decode_xml(&s));
+                        out.sequence = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
                     }
                     _ => IgnoreSubfield::from_xml(iterator, inner_cipher)?,
                 },
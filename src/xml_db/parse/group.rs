@@ -2,7 +2,6 @@ use crate::{
     db::{iconid::IconId, rc_refcell_node, CustomData, Entry, Group, Times},
     xml_db::parse::{bad_event, FromXml, IgnoreSubfield, SimpleTag, SimpleXmlEvent, XmlParseError},
 };
-use std::convert::TryFrom;
 use uuid::Uuid;
 
 impl FromXml for Group {
@@ -32,13 +31,17 @@ impl FromXml for Group {
                         out.notes = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value;
                     }
                     "IconID" => {
-                        out.icon_id = SimpleTag::<Option<usize>>::from_xml(iterator, inner_cipher)?
-                            .value
-                            .map(|v| IconId::try_from(v).unwrap_or(IconId::FOLDER));
+                        // keep unknown indices instead of failing, like KeePass does
+                        out.icon_id = SimpleTag::<Option<usize>>::from_xml(iterator, inner_cipher)?.value.map(IconId);
                     }
                     "CustomIconUUID" => {
                         out.custom_icon_uuid = SimpleTag::<Option<Uuid>>::from_xml(iterator, inner_cipher)?.value;
                     }
+                    "Tags" => {
+                        if let Some(tags) = SimpleTag::<Option<String>>::from_xml(iterator, inner_cipher)?.value {
+                            out.tags = tags.split([';', ',']).map(std::borrow::ToOwned::to_owned).collect();
+                        }
+                    }
                     "Times" => {
                         out.times = Times::from_xml(iterator, inner_cipher)?;
                     }
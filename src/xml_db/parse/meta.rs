@@ -3,7 +3,6 @@ use chrono::NaiveDateTime;
 use uuid::Uuid;
 
 use crate::{
-    compression::{Compression, GZipCompression},
     db::{
         meta::{BinaryAttachment, BinaryAttachments, CustomIcons, Icon, MemoryProtection, Meta},
         Color,
@@ -233,11 +232,7 @@ impl FromXml for BinaryAttachment {
 
         out.identifier = identifier;
         out.compressed = compressed;
-        out.content = if compressed {
-            Compression::decompress(&GZipCompression, &buf).map_err(XmlParseError::Compression)?
-        } else {
-            buf
-        };
+        out.content = buf;
 
         // no need to check for the correct closing tag - checked by XmlReader
         let _close_tag = iterator.next().ok_or(XmlParseError::Eof)?;
@@ -407,6 +402,36 @@ mod parse_meta_test {
         Ok(())
     }
 
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn test_compressed_binary_attachment() -> Result<(), XmlParseError> {
+        use crate::compression::{Compression, GZipCompression};
+        use base64::{engine::general_purpose as base64_engine, Engine as _};
+
+        let compressed = GZipCompression.compress(b"Compressed Binary Data").unwrap();
+        let xml = format!(
+            "<Binary ID=\"1\" Compressed=\"True\">{}</Binary>",
+            base64_engine::STANDARD.encode(&compressed)
+        );
+
+        let value = parse_test_xml::<BinaryAttachment>(&xml)?;
+        assert!(value.compressed);
+        assert_eq!(value.content, compressed);
+        assert_ne!(value.content, b"Compressed Binary Data");
+        assert_eq!(value.content_decompressed().unwrap().as_ref(), b"Compressed Binary Data");
+
+        let xml = format!(
+            "<Binary ID=\"1\" Compressed=\"True\">{}</Binary>",
+            base64_engine::STANDARD.encode(b"not gzip")
+        );
+        // broken content is only reported when it is read
+        let value = parse_test_xml::<BinaryAttachment>(&xml)?;
+        assert_eq!(value.content, b"not gzip");
+        assert!(value.content_decompressed().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_custom_icons() -> Result<(), XmlParseError> {
         let _value = parse_test_xml::<CustomIcons>("<CustomIcons/>")?;
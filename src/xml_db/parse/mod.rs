@@ -11,23 +11,34 @@ use xml::{name::OwnedName, reader::XmlEvent, EventReader};
 
 use crate::{
     crypt::ciphers::Cipher,
-    db::{Color, CustomData, CustomDataItem, CustomDataItemDenormalized, DeletedObject, DeletedObjects, Group, Meta, Times, Value},
+    db::{
+        rc_refcell_node, Color, CustomData, CustomDataItem, CustomDataItemDenormalized, DeletedObject, DeletedObjects, Group, Meta, Times,
+        Value,
+    },
     error::XmlParseError,
     xml_db::get_epoch_baseline,
 };
 
-/// Parse a `KeePass` timestamp string
+/// Parse a `KeePass` timestamp string, either an ISO 8601 string as in KDBX3 or the Base64 encoded
+/// seconds since 0001-01-01 of KDBX4. ISO strings with a UTC offset are converted to UTC.
 pub fn xml_timestamp(t: &str) -> Result<chrono::NaiveDateTime, XmlParseError> {
     if let Ok(ndt) = chrono::NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%SZ") {
         Ok(ndt)
+    } else if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(t) {
+        Ok(dt.naive_utc())
+    } else if let Ok(ndt) = chrono::NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%S") {
+        Ok(ndt)
     } else {
         let v = base64_engine::STANDARD.decode(t)?;
 
         // Cast the decoded base64 Vec into the array expected by i64::from_le_bytes
-        let mut a: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
-        a.copy_from_slice(&v[0..8]);
-        let ndt = get_epoch_baseline() + chrono::Duration::seconds(i64::from_le_bytes(a));
-        Ok(ndt)
+        let a: [u8; 8] = v
+            .get(0..8)
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(|| XmlParseError::InvalidTimestamp(t.to_string()))?;
+        chrono::Duration::try_seconds(i64::from_le_bytes(a))
+            .and_then(|offset| get_epoch_baseline().checked_add_signed(offset))
+            .ok_or_else(|| XmlParseError::InvalidTimestamp(t.to_string()))
     }
 }
 
@@ -65,6 +76,15 @@ pub(crate) fn parse(xml: &[u8], inner_cipher: &mut dyn Cipher) -> Result<KeePass
 }
 
 pub(crate) fn parse_from_bytes<P: FromXml>(xml: &[u8], inner_cipher: &mut dyn Cipher) -> Result<<P as FromXml>::Parses, XmlParseError> {
+    let xml = xml.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(xml);
+
+    // KeePass always writes UTF-8, refuse anything else instead of guessing
+    if let Some(Ok(XmlEvent::StartDocument { encoding, .. })) = EventReader::new(xml).into_iter().next() {
+        if !encoding.eq_ignore_ascii_case("utf-8") {
+            return Err(XmlParseError::UnsupportedEncoding(encoding));
+        }
+    }
+
     let mut reader = EventReader::new(xml)
         .into_iter()
         .filter_map(|e| {
@@ -310,6 +330,7 @@ impl FromXml for Times {
 pub(crate) struct Root {
     pub(crate) group: Group,
     pub(crate) deleted_objects: DeletedObjects,
+    pub(crate) warnings: Vec<String>,
 }
 
 impl FromXml for Root {
@@ -325,12 +346,13 @@ impl FromXml for Root {
         }
 
         let mut out = Root::default();
+        let mut groups = Vec::new();
 
         while let Some(event) = iterator.peek() {
             match event {
                 SimpleXmlEvent::Start(name, _) => match &name[..] {
                     "Group" => {
-                        out.group = Group::from_xml(iterator, inner_cipher)?;
+                        groups.push(Group::from_xml(iterator, inner_cipher)?);
                     }
                     "DeletedObjects" => {
                         out.deleted_objects = DeletedObjects::from_xml(iterator, inner_cipher)?;
@@ -345,6 +367,18 @@ impl FromXml for Root {
         // no need to check for the correct closing tag - checked by XmlReader
         let _close_tag = iterator.next().ok_or(XmlParseError::Eof)?;
 
+        if groups.len() > 1 {
+            // Some third-party writers produce several root groups, keep all of them
+            out.warnings
+                .push(format!("Found {} root groups, moved them into a new root group", groups.len()));
+            out.group = Group::new("Root");
+            for (index, group) in groups.into_iter().enumerate() {
+                out.group.add_child(rc_refcell_node(group), index);
+            }
+        } else if let Some(group) = groups.pop() {
+            out.group = group;
+        }
+
         Ok(out)
     }
 }
@@ -547,7 +581,10 @@ mod parse_test {
     use crate::{
         config::InnerCipherConfig,
         crypt::ciphers::PlainCipher,
-        db::{AutoType, AutoTypeAssociation, CustomData, CustomDataItemDenormalized, Entry, History, Times, Value},
+        db::{
+            group_get_children, iconid::IconId, AutoType, AutoTypeAssociation, CustomData, CustomDataItemDenormalized, Entry, Group,
+            History, Times, Value,
+        },
         xml_db::parse::{entry::StringField, DeletedObject, DeletedObjects, IgnoreSubfield, Root},
     };
 
@@ -568,6 +605,20 @@ mod parse_test {
         Ok(())
     }
 
+    #[test]
+    fn test_encoding() -> Result<(), XmlParseError> {
+        let value = parse_test_xml::<Group>("\u{feff}<?xml version=\"1.0\" encoding=\"utf-8\"?><Group><Name>Tést</Name></Group>")?;
+        assert_eq!(value.name, Some("Tést".to_string()));
+
+        let value = parse_test_xml::<Group>("\u{feff}<Group><Name>Test</Name></Group>")?;
+        assert_eq!(value.name, Some("Test".to_string()));
+
+        let value = parse_test_xml::<Group>("<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><Group><Name>Test</Name></Group>");
+        assert!(matches!(value, Err(XmlParseError::UnsupportedEncoding(ref e)) if e == "ISO-8859-1"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_simple_tag() -> Result<(), XmlParseError> {
         // String tag
@@ -652,6 +703,18 @@ mod parse_test {
         let value = parse_test_xml::<Times>("<Times><TestTime>8i481Q4AAAA=</TestTime></Times>")?;
         assert_eq!(value.times.len(), 1);
 
+        // KDBX3 writes ISO 8601 strings, KDBX4 the Base64 encoded seconds since 0001-01-01
+        let expected = "2020-01-02T03:04:05".parse().unwrap();
+        for time in [
+            "2020-01-02T03:04:05Z",
+            "2020-01-02T05:04:05+02:00",
+            "2020-01-02T03:04:05",
+            "pVSf1Q4AAAA=",
+        ] {
+            let value = parse_test_xml::<Times>(&format!("<Times><TestTime>{time}</TestTime></Times>"))?;
+            assert_eq!(value.times.get("TestTime"), Some(&expected), "{time}");
+        }
+
         let value = parse_test_xml::<Times>("<TestTag>SomeData</TestTag>");
         assert!(matches!(value, Err(XmlParseError::BadEvent { .. })));
 
@@ -664,6 +727,31 @@ mod parse_test {
         Ok(())
     }
 
+    #[test]
+    fn test_multiple_root_groups() -> Result<(), XmlParseError> {
+        let value = parse_test_xml::<Root>("<Root><Group><Name>Root</Name></Group></Root>")?;
+        assert_eq!(value.group.name, Some("Root".to_string()));
+        assert!(value.warnings.is_empty());
+
+        let value = parse_test_xml::<Root>(
+            "<Root><Group><Name>First</Name><Entry/></Group><Group><Name>Second</Name></Group><DeletedObjects/></Root>",
+        )?;
+        assert_eq!(value.warnings.len(), 1);
+        let names: Vec<_> = value
+            .group
+            .groups()
+            .iter()
+            .map(|g| g.borrow().get_title().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec!["First", "Second"]);
+        assert_eq!(value.group.children.len(), 2);
+        let first = &value.group.groups()[0];
+        assert_eq!(first.borrow().get_parent(), Some(value.group.uuid));
+        assert_eq!(group_get_children(first).unwrap().len(), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_root_failures() -> Result<(), XmlParseError> {
         let value = parse_test_xml::<Root>("<TestTag>SomeData</TestTag>");
@@ -811,6 +899,21 @@ mod parse_test {
         Ok(())
     }
 
+    #[test]
+    fn test_unknown_icon_id() -> Result<(), XmlParseError> {
+        let value = parse_test_xml::<Entry>("<Entry><IconID>200</IconID></Entry>")?;
+        assert_eq!(value.icon_id, Some(IconId(200)));
+        assert!(!IconId(200).is_standard());
+
+        let value = parse_test_xml::<Group>("<Group><IconID>200</IconID></Group>")?;
+        assert_eq!(value.icon_id, Some(IconId(200)));
+
+        let value = parse_test_xml::<Entry>("<Entry><IconID>43</IconID></Entry>")?;
+        assert_eq!(value.icon_id, Some(IconId::RECYCLE_BIN));
+
+        Ok(())
+    }
+
     #[test]
     fn test_entry_failures() -> Result<(), XmlParseError> {
         let value = parse_test_xml::<Entry>("<Entry>");
@@ -859,6 +962,30 @@ mod parse_test {
         Ok(())
     }
 
+    #[test]
+    fn test_invalid_protected_value() -> Result<(), XmlParseError> {
+        let value = parse_test_xml::<StringField>("<String><Key>Password</Key><Value Protected=\"True\">not base64!</Value></String>");
+        assert!(matches!(value, Err(XmlParseError::InvalidProtectedValue { ref field, .. }) if field == "Password"));
+
+        let value = parse_test_xml::<Entry>(
+            "<Entry><String><Key>Title</Key><Value>Test</Value></String>\
+             <String><Key>Password</Key><Value Protected=\"True\">%%%%</Value></String></Entry>",
+        );
+        assert!(matches!(value, Err(XmlParseError::InvalidProtectedValue { ref field, .. }) if field == "Password"));
+
+        let value = parse_test_xml::<StringField>("<String><Key>Password</Key><Value Protected=\"True\">c2VjcmV0</Value></String>");
+        assert!(value.is_ok());
+
+        let value = parse_test_xml::<Times>("<Times><TestTime>AAAA</TestTime></Times>");
+        assert!(matches!(value, Err(XmlParseError::InvalidTimestamp(_))));
+
+        // out of the representable range
+        let value = parse_test_xml::<Times>("<Times><TestTime>/////////38=</TestTime></Times>");
+        assert!(matches!(value, Err(XmlParseError::InvalidTimestamp(_))));
+
+        Ok(())
+    }
+
     #[test]
     fn test_autotype() -> Result<(), XmlParseError> {
         let value = parse_test_xml::<AutoType>("<AutoType>");
@@ -883,6 +1010,13 @@ mod parse_test {
         let value = parse_test_xml::<AutoType>("<AutoType><StrangeTag>Data</StrangeTag></AutoType>");
         assert!(value.is_ok());
 
+        // associations keep the order of the document
+        let value = parse_test_xml::<AutoType>(
+            "<AutoType><Association><Window>B</Window></Association><Association><Window>A</Window></Association></AutoType>",
+        )?;
+        let windows: Vec<_> = value.associations.iter().map(|a| a.window.as_deref()).collect();
+        assert_eq!(windows, vec![Some("B"), Some("A")]);
+
         Ok(())
     }
 
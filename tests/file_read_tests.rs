@@ -2,11 +2,19 @@ mod file_read_tests {
     #[cfg(feature = "challenge_response")]
     use keepass_ng::ChallengeResponseKey;
     use keepass_ng::{
-        db::{group_get_children, with_node, Database, Entry, Group, Node, NodeIterator, NodePtr},
-        error::{DatabaseIntegrityError, DatabaseOpenError},
+        config::{DatabaseConfig, DatabaseVersion, KdfConfig, SecurityIssue, Severity},
+        db::{group_get_children, node_is_entry, with_node, Database, Entry, Group, Node, NodeIterator, NodePtr},
+        error::{DatabaseIntegrityError, DatabaseKeyError, DatabaseOpenError},
         DatabaseKey,
     };
-    use std::{fs::File, path::Path};
+    use std::{
+        fs::File,
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    };
     use uuid::uuid;
 
     #[test]
@@ -43,6 +51,38 @@ mod file_read_tests {
         Ok(())
     }
 
+    #[test]
+    fn validate_header_without_key() -> Result<(), DatabaseOpenError> {
+        for path in [
+            "tests/resources/test_db_with_password.kdbx",
+            "tests/resources/test_db_kdbx4_with_password_argon2.kdbx",
+            "tests/resources/test_db_kdb_with_password.kdb",
+        ] {
+            Database::validate_header(&mut File::open(path)?)?;
+        }
+
+        let data = std::fs::read("tests/resources/test_db_kdbx4_with_password_argon2.kdbx")?;
+        let version = Database::validate_header(&mut data.as_slice())?;
+        assert_eq!(version.to_string(), "KDBX4.0");
+
+        let result = Database::validate_header(&mut &data[..100]);
+        assert!(matches!(
+            result,
+            Err(DatabaseOpenError::DatabaseIntegrity(
+                DatabaseIntegrityError::TruncatedOuterHeader { .. }
+            ))
+        ));
+
+        let result = Database::open(&mut &data[..100], DatabaseKey::new().with_password("demopass"));
+        assert!(matches!(
+            result,
+            Err(DatabaseOpenError::DatabaseIntegrity(
+                DatabaseIntegrityError::TruncatedOuterHeader { .. }
+            ))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn open_kdbx3_with_keyfile() -> Result<(), DatabaseOpenError> {
         let path = Path::new("tests/resources/test_db_with_keyfile.kdbx");
@@ -154,6 +194,75 @@ mod file_read_tests {
         Ok(())
     }
 
+    #[test]
+    fn security_assessment_of_aes_kdf() -> Result<(), DatabaseOpenError> {
+        let path = Path::new("tests/resources/test_db_kdbx4_with_password_aes.kdbx");
+        let key = DatabaseKey::new().with_password("demopass");
+        let db = Database::open(&mut File::open(path)?, key)?;
+
+        let assessment = db.security_assessment();
+        let finding = assessment.findings.iter().find(|f| f.issue == SecurityIssue::AesKdf).unwrap();
+        assert_eq!(finding.severity, Severity::Warning);
+        assert!(finding.recommendation.contains("Argon2"));
+        assert!(!assessment.has_issue(SecurityIssue::OutdatedFormat));
+
+        let path = Path::new("tests/resources/test_db_kdbx4_with_password_argon2id_chacha20.kdbx");
+        let db = Database::open(&mut File::open(path)?, DatabaseKey::new().with_password("demopass"))?;
+        assert!(!db.security_assessment().has_issue(SecurityIssue::AesKdf));
+
+        let assessment = DatabaseConfig::default_for_version(DatabaseVersion::KDB3(1)).security_assessment();
+        assert!(assessment.has_issue(SecurityIssue::OutdatedFormat));
+        assert!(assessment.has_issue(SecurityIssue::Salsa20InnerCipher));
+        assert_eq!(assessment.highest_severity(), Some(Severity::Warning));
+
+        Ok(())
+    }
+
+    #[test]
+    fn security_assessment_of_low_argon2_memory() {
+        let with_argon2 = |mebibytes: u64| {
+            let mut config = DatabaseConfig::default();
+            if let KdfConfig::Argon2 { memory, iterations, .. } = &mut config.kdf_config {
+                *memory = mebibytes * 1024 * 1024;
+                *iterations = 10;
+            }
+            config.security_assessment()
+        };
+        assert!(with_argon2(2).has_issue(SecurityIssue::LowKdfCost));
+        assert!(!with_argon2(64).has_issue(SecurityIssue::LowKdfCost));
+    }
+
+    #[test]
+    fn open_kdbx4_with_cancelled_kdf() -> Result<(), DatabaseOpenError> {
+        for path in [
+            "tests/resources/test_db_kdbx4_with_password_aes.kdbx",
+            "tests/resources/test_db_kdbx4_with_password_argon2.kdbx",
+        ] {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = calls.clone();
+            let key = DatabaseKey::new().with_password("demopass").with_kdf_progress(move |_, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                false
+            });
+            let db = Database::open(&mut File::open(path)?, key);
+            assert!(matches!(db, Err(DatabaseOpenError::Key(DatabaseKeyError::Cancelled))));
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+            let reports = Arc::new(Mutex::new(Vec::new()));
+            let recorder = reports.clone();
+            let key = DatabaseKey::new().with_password("demopass").with_kdf_progress(move |done, total| {
+                recorder.lock().unwrap().push((done, total));
+                true
+            });
+            Database::open(&mut File::open(path)?, key)?;
+            let reports = reports.lock().unwrap();
+            let (done, total) = *reports.last().unwrap();
+            assert_eq!(done, total);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn open_kdbx4_with_password_kdf_argon2_cipher_twofish() -> Result<(), DatabaseOpenError> {
         let path = Path::new("tests/resources/test_db_kdbx4_with_password_argon2_twofish.kdbx");
@@ -182,6 +291,24 @@ mod file_read_tests {
         Ok(())
     }
 
+    #[test]
+    fn config_summary_kdf_argon2_cipher_chacha20() -> Result<(), DatabaseOpenError> {
+        let path = Path::new("tests/resources/test_db_kdbx4_with_password_argon2_chacha20.kdbx");
+
+        let key = DatabaseKey::new().with_password("demopass");
+        let db = Database::open(&mut File::open(path)?, key)?;
+
+        let summary = db.config.summary();
+        println!("{summary}");
+        assert_eq!(summary.version, "KDBX4.0");
+        assert_eq!(summary.outer_cipher, "ChaCha20");
+        assert!(summary.kdf.starts_with("Argon2d ("));
+        assert_eq!(summary.inner_cipher, "ChaCha20");
+        assert_eq!(summary.compression, "GZip");
+
+        Ok(())
+    }
+
     #[test]
     fn open_kdbx4_with_password_kdf_argon2id_cipher_twofish() -> Result<(), DatabaseOpenError> {
         let path = Path::new("tests/resources/test_db_kdbx4_with_password_argon2id_twofish.kdbx");
@@ -245,6 +372,53 @@ mod file_read_tests {
         Ok(())
     }
 
+    #[test]
+    fn check_key_without_parsing() -> Result<(), DatabaseOpenError> {
+        for path in [
+            "tests/resources/test_db_with_password.kdbx",
+            "tests/resources/test_db_kdbx4_with_password_aes.kdbx",
+            "tests/resources/test_db_kdbx4_with_password_argon2.kdbx",
+        ] {
+            let mut file = File::open(path)?;
+            assert!(Database::check_key(&mut file, DatabaseKey::new().with_password("demopass"))?);
+            assert!(!Database::check_key(&mut file, DatabaseKey::new().with_password("wrong password"))?);
+
+            // the reader was rewound, so the database can still be opened from it
+            Database::open(&mut file, DatabaseKey::new().with_password("demopass"))?;
+        }
+
+        // only the start of a KDBX3 payload is decrypted, so a damaged end does not matter
+        let mut data = std::fs::read("tests/resources/test_db_with_password.kdbx")?;
+        data.truncate(data.len() - 7);
+        let mut reader = std::io::Cursor::new(&data);
+        assert!(Database::check_key(&mut reader, DatabaseKey::new().with_password("demopass"))?);
+        assert!(!Database::check_key(
+            &mut reader,
+            DatabaseKey::new().with_password("wrong password")
+        )?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn probe_key_requirements() -> Result<(), DatabaseOpenError> {
+        let path = Path::new("tests/resources/test_db_kdbx4_with_password_aes.kdbx");
+        let probe = Database::probe_key_requirements(&mut File::open(path)?)?;
+        assert!(matches!(probe.version, DatabaseVersion::KDB4(_)));
+        assert!(matches!(probe.kdf_config, KdfConfig::Aes { .. }));
+        assert!(!probe.opens_with_empty_password);
+        assert!(probe.key_required());
+
+        let path = Path::new("tests/resources/test_db_kdbx4_with_password_argon2id.kdbx");
+        let probe = Database::probe_key_requirements(&mut File::open(path)?)?;
+        assert!(matches!(probe.kdf_config, KdfConfig::Argon2id { .. }));
+        assert!(probe.key_required());
+
+        let path = Path::new("tests/resources/broken_random_data.kdbx");
+        assert!(Database::probe_key_requirements(&mut File::open(path)?).is_err());
+        Ok(())
+    }
+
     #[test]
     #[should_panic(expected = r#"InvalidKDBXIdentifier"#)]
     fn open_broken_random_data() {
@@ -327,6 +501,125 @@ mod file_read_tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_borrowed_larger_than_1mb_file() -> Result<(), DatabaseOpenError> {
+        let data = std::fs::read("tests/resources/test_db_kdb3_with_file_larger_1mb.kdbx")?;
+        let key = DatabaseKey::new().with_password("samplepassword");
+
+        let start = std::time::Instant::now();
+        let db = Database::parse_borrowed(&data, key.clone())?;
+        println!("Parsed {} bytes in {:?}", data.len(), start.elapsed());
+
+        assert_eq!(db, Database::open(&mut data.as_slice(), key)?);
+        let largest = db
+            .meta
+            .binaries
+            .binaries
+            .iter()
+            .map(|b| b.content_decompressed().unwrap().len())
+            .max()
+            .unwrap_or(0);
+        assert!(largest > 1024 * 1024, "largest attachment has {largest} bytes");
+        Ok(())
+    }
+
+    #[test]
+    fn open_kdb2_pre_release() -> Result<(), DatabaseOpenError> {
+        use keepass_ng::config::DatabaseVersion;
+
+        // there is no pre-release fixture, but apart from the signature the format is KDBX3
+        let mut data = std::fs::read("tests/resources/test_db_with_password.kdbx")?;
+        data[4..8].copy_from_slice(&0xb54b_fb66_u32.to_le_bytes());
+        let key = DatabaseKey::new().with_password("demopass");
+
+        assert!(matches!(Database::get_version(&mut data.as_slice())?, DatabaseVersion::KDB2(_)));
+        assert!(Database::check_key(&mut std::io::Cursor::new(&data), key.clone())?);
+        let db = Database::parse(&data, key.clone())?;
+        assert!(matches!(db.config.version, DatabaseVersion::KDB2(_)));
+        assert_eq!(db.root.borrow().get_title().unwrap(), "sample");
+        assert_eq!(group_get_children(&db.root).unwrap().len(), 5);
+
+        let kdbx3 = Database::open(&mut File::open("tests/resources/test_db_with_password.kdbx")?, key)?;
+        assert!(keepass_ng::db::node_is_equals_to(&db.root, &kdbx3.root));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_malformed_input() {
+        let key = DatabaseKey::new().with_password("demopass");
+        let mut inputs: Vec<Vec<u8>> = vec![
+            vec![],
+            vec![0x03, 0xd9, 0xa2, 0x9a],
+            vec![0xff; 64],
+            b"\x03\xd9\xa2\x9a\x67\xfb\x4b\xb5\x01\x00\x04\x00garbage".to_vec(),
+        ];
+        for fixture in [
+            "test_db_with_password.kdbx",
+            "test_db_kdbx4_with_password_aes.kdbx",
+            "test_db_kdb_with_password.kdb",
+        ] {
+            let data = std::fs::read(Path::new("tests/resources").join(fixture)).unwrap();
+            // every cut through the outer header and the header hashes that follow it
+            for len in (0..300).chain([data.len() / 2, data.len() - 1]) {
+                inputs.push(data[..len].to_vec());
+            }
+            let mut flipped = data.clone();
+            let last = flipped.len() - 1;
+            flipped[last] ^= 0xff;
+            inputs.push(flipped);
+        }
+
+        for input in inputs {
+            assert!(Database::parse(&input, key.clone()).is_err(), "{} bytes", input.len());
+        }
+    }
+
+    #[test]
+    fn resolve_kdbx3_attachment() -> Result<(), DatabaseOpenError> {
+        let path = Path::new("tests/resources/test_db_kdb3_with_file_larger_1mb.kdbx");
+        let key = DatabaseKey::new().with_password("samplepassword");
+        let db = Database::open(&mut File::open(path)?, key)?;
+
+        let entry = NodeIterator::new(&db.root).find(node_is_entry).unwrap();
+        let attachments = with_node::<Entry, _, _>(&entry, |e| e.get_attachments().to_vec()).unwrap();
+        assert_eq!(attachments.len(), 1);
+
+        let content = db.resolve_attachment(&attachments[0])?.unwrap();
+        assert_eq!(content, db.meta.binaries.binaries[0].content_decompressed()?);
+        assert!(content.len() > 1024 * 1024);
+        Ok(())
+    }
+
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn resolve_kdbx4_attachment() -> Result<(), DatabaseOpenError> {
+        use keepass_ng::db::{with_node_mut, AttachmentRef, HeaderAttachment};
+
+        let mut db = Database::new(Default::default());
+        db.header_attachments.push(HeaderAttachment {
+            flags: 1,
+            content: b"attached".to_vec(),
+        });
+        let root_uuid = db.root.borrow().get_uuid();
+        let entry = db.create_new_entry(root_uuid, 0).unwrap();
+        with_node_mut::<Entry, _, _>(&entry, |e| {
+            e.get_attachments_mut().push(AttachmentRef {
+                name: "file.txt".to_string(),
+                identifier: "0".to_string(),
+            })
+        });
+
+        let key = DatabaseKey::new().with_password("demopass");
+        let mut buffer = Vec::new();
+        db.save(&mut buffer, key.clone()).unwrap();
+        let db = Database::parse(&buffer, key)?;
+
+        let entry = NodeIterator::new(&db.root).find(node_is_entry).unwrap();
+        let attachments = with_node::<Entry, _, _>(&entry, |e| e.get_attachments().to_vec()).unwrap();
+        assert_eq!(db.resolve_attachment(&attachments[0])?.as_deref(), Some(&b"attached"[..]));
+        Ok(())
+    }
+
     #[test]
     fn open_kdbx4_with_password_deleted_entry() -> Result<(), DatabaseOpenError> {
         let path = Path::new("tests/resources/test_db_kdbx4_with_password_deleted_entry.kdbx");
@@ -63,4 +63,25 @@ mod large_file_roundtrip_tests {
         assert_eq!(entry_counter, LARGE_DATABASE_ENTRY_COUNT);
         Ok(())
     }
+
+    /// The progress callback sees every entry of a large database exactly once, in order.
+    #[cfg(feature = "save_kdbx4")]
+    #[test]
+    fn save_large_database_with_progress() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Default::default());
+        for i in 0..LARGE_DATABASE_ENTRY_COUNT {
+            let entry = rc_refcell_node(Entry::default());
+            entry.borrow_mut().set_title(Some(&format!("Entry_{i}")));
+            group_add_child(&db.root, entry, i)?;
+        }
+
+        let key = DatabaseKey::new().with_password(TEST_DATABASE_PASSWORD);
+        let mut reported = Vec::new();
+        db.save_with_progress(&mut Vec::new(), key, |dumped, total| reported.push((dumped, total)))?;
+
+        assert_eq!(reported.len(), LARGE_DATABASE_ENTRY_COUNT);
+        assert_eq!(reported.first(), Some(&(1, LARGE_DATABASE_ENTRY_COUNT)));
+        assert_eq!(reported.last(), Some(&(LARGE_DATABASE_ENTRY_COUNT, LARGE_DATABASE_ENTRY_COUNT)));
+        Ok(())
+    }
 }